@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use serde_json::{json, Value as JsonValue};
+
+use matrix_visualisations::model::dag::{Batch, BatchKind, DataSet, RoomEvents};
+
+const EVENTS_COUNT: usize = 100_000;
+const BATCH_SIZE: usize = 100;
+
+// The number of events of the comparison with the rebuild of the full data set after each batch,
+// which is too slow for a DAG of `EVENTS_COUNT` events since it grows quadratically
+const COMPARISON_EVENTS_COUNT: usize = 10_000;
+
+const SYNC_BATCH: Batch = Batch {
+    kind: BatchKind::Sync,
+    received_at: 0.0,
+};
+const MESSAGES_BATCH: Batch = Batch {
+    kind: BatchKind::Messages,
+    received_at: 0.0,
+};
+
+// Generates a DAG of `count` events in the format of the v1 rooms. The room regularly forks into
+// up to 4 concurrent branches which are merged back together later, like when several servers
+// send events at the same time.
+fn generate_events(count: usize) -> Vec<JsonValue> {
+    let mut events = Vec::with_capacity(count);
+    let mut extremities = vec!["$0:example.org".to_string()];
+
+    events.push(json!({
+        "room_id": "!bench:example.org",
+        "sender": "@alice:example.org",
+        "origin": "example.org",
+        "origin_server_ts": 0,
+        "type": "m.room.create",
+        "state_key": "",
+        "content": { "creator": "@alice:example.org" },
+        "prev_events": [],
+        "depth": 1,
+        "auth_events": [],
+        "event_id": "$0:example.org",
+        "hashes": {},
+        "signatures": {},
+    }));
+
+    for i in 1..count {
+        let prev_events = if i % 11 == 0 && extremities.len() > 1 {
+            // Merge two branches
+            vec![extremities.remove(0), extremities.remove(0)]
+        } else if i % 7 == 0 && extremities.len() < 4 {
+            // Fork from the oldest branch
+            vec![extremities[0].clone()]
+        } else {
+            let k = i % extremities.len();
+
+            vec![extremities.remove(k)]
+        };
+
+        let event_id = format!("${}:example.org", i);
+
+        events.push(json!({
+            "room_id": "!bench:example.org",
+            "sender": "@alice:example.org",
+            "origin": "example.org",
+            "origin_server_ts": i,
+            "type": "m.room.message",
+            "content": { "body": format!("Message {}", i) },
+            "prev_events": prev_events.iter().map(|id| json!([id, {}])).collect::<Vec<_>>(),
+            "depth": i + 1,
+            "auth_events": [["$0:example.org", {}]],
+            "event_id": event_id,
+            "hashes": {},
+            "signatures": {},
+        }));
+
+        extremities.push(event_id);
+    }
+
+    events
+}
+
+fn build_dag(events: &[JsonValue]) -> RoomEvents {
+    let mut dag = RoomEvents::new("example.org", &HashSet::new());
+
+    for batch in events.chunks(BATCH_SIZE) {
+        dag.add_events(batch.to_vec(), SYNC_BATCH);
+    }
+
+    dag
+}
+
+fn bench_add_events(c: &mut Criterion) {
+    let events = generate_events(EVENTS_COUNT);
+
+    // Like a `/sync` loop receiving the whole room
+    c.bench_function("add 100k events by batches of 100", move |b| {
+        b.iter(|| build_dag(&events))
+    });
+
+    let events = generate_events(EVENTS_COUNT);
+
+    // Like paginating backwards with `/messages`, every batch leaves orphans behind
+    c.bench_function("add 100k events by batches of 100 backwards", move |b| {
+        b.iter(|| {
+            let mut dag = RoomEvents::new("example.org", &HashSet::new());
+
+            for batch in events.rchunks(BATCH_SIZE) {
+                dag.add_events(batch.to_vec(), MESSAGES_BATCH);
+            }
+
+            dag
+        })
+    });
+}
+
+fn bench_data_sets(c: &mut Criterion) {
+    let events = generate_events(EVENTS_COUNT + BATCH_SIZE);
+    let (events, last_batch) = events.split_at(EVENTS_COUNT);
+
+    let mut dag = build_dag(events);
+    let from = dag.latest_events();
+    dag.add_events(last_batch.to_vec(), SYNC_BATCH);
+
+    let dag = Rc::new(dag);
+    let full_dag = dag.clone();
+
+    // The traversal towards the newer events doesn't clone the DAG anymore
+    c.bench_function("new events data set in a 100k-event DAG", move |b| {
+        b.iter(|| {
+            let mut data_set = DataSet::default();
+            dag.add_new_events_to_data_set(&mut data_set, from.clone());
+
+            data_set
+        })
+    });
+
+    c.bench_function("full data set of a 100k-event DAG", move |b| {
+        b.iter(|| full_dag.create_data_set())
+    });
+}
+
+fn bench_display_updates(c: &mut Criterion) {
+    let events = generate_events(COMPARISON_EVENTS_COUNT);
+
+    // The vis.js network is updated with the data set of the new events of each batch
+    c.bench_function(
+        "display 10k events by batches of 100 with the new events only",
+        move |b| {
+            b.iter(|| {
+                let mut dag = RoomEvents::new("example.org", &HashSet::new());
+
+                events
+                    .chunks(BATCH_SIZE)
+                    .map(|batch| dag.add_events(batch.to_vec(), SYNC_BATCH))
+                    .collect::<Vec<DataSet>>()
+            })
+        },
+    );
+
+    let events = generate_events(COMPARISON_EVENTS_COUNT);
+
+    // For comparison, the data set of the whole DAG is rebuilt after each batch. The events are
+    // still added incrementally, unlike before, when every edge of the DAG was scanned again
+    c.bench_function(
+        "display 10k events by batches of 100 with the full data set",
+        move |b| {
+            b.iter(|| {
+                let mut dag = RoomEvents::new("example.org", &HashSet::new());
+
+                events
+                    .chunks(BATCH_SIZE)
+                    .map(|batch| {
+                        dag.add_events(batch.to_vec(), SYNC_BATCH);
+                        dag.create_data_set()
+                    })
+                    .collect::<Vec<DataSet>>()
+            })
+        },
+    );
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_add_events, bench_data_sets, bench_display_updates
+}
+criterion_main!(benches);
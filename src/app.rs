@@ -0,0 +1,4779 @@
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use failure::Error;
+use serde_json::Value as JsonValue;
+use stdweb::unstable::TryInto;
+use stdweb::web;
+use stdweb::web::IParentNode;
+use yew::services::fetch::FetchTask;
+use yew::services::reader::{FileData, ReaderService, ReaderTask};
+use yew::services::timeout::TimeoutTask;
+use yew::services::{ConsoleService, TimeoutService};
+use yew::{html, Callback, Component, ComponentLink, Html, Renderable, ShouldRender};
+
+use crate::backoff::Backoff;
+use crate::cs_backend::api::{
+    self as cs_api, ConnectionResponse, ContextResponse, FilterResponse, JoinedRooms, LoginFlows,
+    MessagesResponse, RegisterResponse, RoomAliasResponse, RoomInitialSync, SyncResponse,
+    WellKnown, WhoAmIResponse,
+};
+use crate::cs_backend::backend::CSBackend;
+use crate::cs_backend::error::MatrixError;
+use crate::cs_backend::session::{FilterSettings, LoginMethod, Session as CSSession};
+use crate::federation_backend::backend::{
+    FederationBackend, MissingEventsResponse, StateIdsResponse, Transaction,
+};
+use crate::federation_backend::session::Session as FederationSession;
+use crate::file_backend::backend::FileBackend;
+use crate::file_backend::session::Session as FileSession;
+use crate::keys_backend::KeysBackend;
+use crate::model;
+use crate::model::crypto::{HashCheck, ServerKeys, SignatureCheck, SigningKey};
+use crate::model::dag::{Batch, BatchKind, DataSet, EdgeKind, ReplayOrder, ReplayStep, RoomEvents};
+use crate::model::diff::DagDiff;
+use crate::model::event::Field;
+use crate::model::snapshot::Snapshot;
+use crate::model::state::StateResolver;
+use crate::mv_backend::api::EventsResponse;
+use crate::mv_backend::backend::MatrixVisualisationsBackend;
+use crate::mv_backend::session::Session as MVSession;
+use crate::visjs::VisJsService;
+use crate::BackendChoice;
+
+pub type ViewIndex = usize;
+
+// The number of `/messages` requests whose events are displayed at once when loading the full
+// history of a room, since redrawing the DAG after each of them would be too slow
+const HISTORY_BATCH_PAGES: usize = 10;
+
+pub struct Model {
+    console: ConsoleService,
+    timeout: TimeoutService,
+    vis: VisJsService,
+    link: ComponentLink<Self>,
+
+    bk_type: Arc<RwLock<BackendChoice>>,
+    view_idx: ViewIndex,
+    views: Vec<View>,
+    event_body: Option<String>,
+    event_body_redacted: bool, // Whether `event_body` is the redacted form of the event
+    event_hash_check: Option<HashCheck>,
+    event_signature_check: Option<SignatureCheck>,
+    event_arrival: Option<Batch>, // The batch which brought the event of `event_body`
+    room_state: Option<String>,
+    local_room_state: Option<String>,
+    fields_choice: FieldsChoice,
+    edges_choice: EdgesChoice,
+    branches_colored: bool,
+    diff_views: (ViewIndex, ViewIndex), // The views whose DAGs are compared
+    dag_diff: Option<DagDiff>,
+    replay_order: ReplayOrder,
+    replay_speed: u32, // The number of events added per second during the replay
+    replay: Option<Replay>,
+    received_login_token: Option<String>, // The login token given to the application after an SSO login
+    history_depth_limit: Option<i64>, // The depth at which the loading of the full history stops
+    history_events_limit: Option<usize>, // The number of events after which it stops
+    gap_depth_limit: usize, // The number of generations of missing ancestors fetched to fill the gaps
+
+    server_keys: ServerKeys, // The signing keys of the servers, shared by every view
+    keys_backend: KeysBackend,
+    keys_tasks: Vec<FetchTask>,
+    reader: ReaderService,
+    reader_tasks: Vec<ReaderTask>,
+}
+
+pub enum View {
+    CS(CSView),
+    MV(MVView),
+    File(FileView),
+    Federation(FederationView),
+}
+
+impl View {
+    pub fn get_id(&self) -> ViewIndex {
+        match self {
+            View::CS(v) => v.id,
+            View::MV(v) => v.id,
+            View::File(v) => v.id,
+            View::Federation(v) => v.id,
+        }
+    }
+
+    pub fn get_events_dag(&self) -> &Option<Arc<RwLock<RoomEvents>>> {
+        match self {
+            View::CS(v) => &v.events_dag,
+            View::MV(v) => &v.events_dag,
+            View::File(v) => &v.events_dag,
+            View::Federation(v) => &v.events_dag,
+        }
+    }
+
+    /// Get the server name and the room ID of the session of the view.
+    pub fn get_room(&self) -> (String, String) {
+        match self {
+            View::CS(v) => {
+                let session = v.session.read().unwrap();
+
+                (session.server_name.clone(), session.room_id.clone())
+            }
+            View::MV(v) => {
+                let session = v.session.read().unwrap();
+
+                (session.server_name.clone(), session.room_id.clone())
+            }
+            View::File(v) => {
+                let session = v.session.read().unwrap();
+
+                (session.server_name.clone(), session.room_id.clone())
+            }
+            View::Federation(v) => {
+                let session = v.session.read().unwrap();
+
+                (session.server_name.clone(), session.room_id.clone())
+            }
+        }
+    }
+
+    /// Get the number of times the view has tried to reconnect to its homeserver since its
+    /// requests started to fail, along with the delay before the current attempt.
+    pub fn reconnection(&self) -> Option<(u32, std::time::Duration)> {
+        let backoff = match self {
+            View::CS(v) => &v.backoff,
+            View::MV(v) => &v.backoff,
+            View::File(_) | View::Federation(_) => return None,
+        };
+
+        backoff.delay().map(|delay| (backoff.attempts(), delay))
+    }
+
+    /// Get the last failure of the requests of the view to its homeserver, if it hasn't
+    /// succeeded since then.
+    pub fn error(&self) -> Option<&MatrixError> {
+        match self {
+            View::CS(v) => v.error.as_ref(),
+            View::MV(v) => v.error.as_ref(),
+            View::File(_) | View::Federation(_) => None,
+        }
+    }
+
+    /// Whether the view is observing a room, so the backend can't be changed.
+    pub fn is_connected(&self) -> bool {
+        match self {
+            View::CS(v) => v.session.read().unwrap().access_token.is_some(),
+            View::MV(v) => v.session.read().unwrap().connected,
+            View::File(v) => v.events_dag.is_some(),
+            View::Federation(v) => v.session.read().unwrap().connected,
+        }
+    }
+}
+
+// This contains every informations needed for the observation of a room from a given HS by using
+// the CS API.
+pub struct CSView {
+    id: ViewIndex,
+
+    discovery_callback: Callback<Result<WellKnown, Error>>,
+    discovery_task: Option<FetchTask>,
+
+    alias_callback: Callback<Result<RoomAliasResponse, Error>>,
+    alias_task: Option<FetchTask>,
+
+    login_flows_callback: Callback<Result<LoginFlows, Error>>,
+    login_flows_task: Option<FetchTask>,
+
+    connection_callback: Callback<Result<ConnectionResponse, Error>>,
+    whoami_callback: Callback<Result<WhoAmIResponse, Error>>,
+    guest_callback: Callback<Result<RegisterResponse, Error>>,
+    connection_task: Option<FetchTask>, // Also used when checking an access token given by the user or registering a guest
+
+    listing_rooms_callback: Callback<Result<JoinedRooms, Error>>,
+    listing_rooms_task: Option<FetchTask>,
+
+    joining_room_callback: Callback<Result<(), Error>>,
+    joining_room_task: Option<FetchTask>,
+
+    filter_callback: Callback<Result<FilterResponse, Error>>,
+    filter_task: Option<FetchTask>,
+    inline_filter: bool, // Whether the filter is given in each `/sync` request, if it couldn't be uploaded
+
+    sync_callback: Callback<Result<SyncResponse, Error>>,
+    initial_sync_callback: Callback<Result<RoomInitialSync, Error>>, // When peeking at the room
+    next_msg_callback: Callback<Result<MessagesResponse, Error>>, // Replaces `/sync` when peeking at the room
+    sync_task: Option<FetchTask>,
+    sync_retry_task: Option<TimeoutTask>, // The task sending `/sync` again after a failure, or polling the new events when peeking
+    backoff: Backoff,
+
+    more_msg_callback: Callback<Result<MessagesResponse, Error>>,
+    more_msg_task: Option<FetchTask>,
+    history: Option<HistoryLoading>, // Also uses `more_msg_task` for its requests
+
+    missing_event_callback: Callback<Result<ContextResponse, Error>>,
+    missing_event_task: Option<FetchTask>,
+    gap_filling: Option<GapFilling>,
+
+    state_callback: Callback<Result<ContextResponse, Error>>,
+    state_task: Option<FetchTask>,
+
+    leaving_room_callback: Callback<Result<(), Error>>,
+    leaving_room_task: Option<FetchTask>,
+
+    disconnection_callback: Callback<Result<(), Error>>,
+    disconnection_task: Option<FetchTask>,
+
+    session: Arc<RwLock<CSSession>>,
+    backend: CSBackend,
+    events_dag: Option<Arc<RwLock<RoomEvents>>>,
+    error: Option<MatrixError>, // The last failure of the requests of the view, shown to the user
+}
+
+impl CSView {
+    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> CSView {
+        let session = Arc::new(RwLock::new(CSSession::empty()));
+
+        CSView {
+            id,
+
+            discovery_callback: link.send_back(move |response: Result<WellKnown, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Discovered(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::DiscoveryFailed(id, e.into())),
+                }
+            }),
+            discovery_task: None,
+
+            alias_callback: link.send_back(move |response: Result<RoomAliasResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::AliasResolved(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::AliasResolutionFailed(id, e.into())),
+                }
+            }),
+            alias_task: None,
+
+            login_flows_callback: link.send_back(move |response: Result<LoginFlows, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::LoginFlows(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::LoginFlowsFailed(id, e.into())),
+                }
+            }),
+            login_flows_task: None,
+
+            connection_callback: link.send_back(
+                move |response: Result<ConnectionResponse, Error>| match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Connected(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::ConnectionFailed(id, e.into())),
+                },
+            ),
+            whoami_callback: link.send_back(move |response: Result<WhoAmIResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::TokenChecked(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::TokenCheckFailed(id, e.into())),
+                }
+            }),
+            guest_callback: link.send_back(move |response: Result<RegisterResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::GuestRegistered(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::ConnectionFailed(id, e.into())),
+                }
+            }),
+            connection_task: None,
+
+            listing_rooms_callback: link.send_back(move |response: Result<JoinedRooms, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::RoomsList(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::ListingRoomsFailed(id, e.into())),
+                }
+            }),
+            listing_rooms_task: None,
+
+            joining_room_callback: link.send_back(
+                move |response: Result<(), Error>| match response {
+                    Ok(_) => Msg::BkRes(BkResponse::RoomJoined(id)),
+                    Err(e) => Msg::BkRes(BkResponse::JoiningRoomFailed(id, e.into())),
+                },
+            ),
+            joining_room_task: None,
+
+            filter_callback: link.send_back(move |response: Result<FilterResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::FilterUploaded(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::FilterUploadFailed(id, e.into())),
+                }
+            }),
+            filter_task: None,
+            inline_filter: false,
+
+            sync_callback: link.send_back(move |response: Result<SyncResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Synced(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::SyncFailed(id, e.into())),
+                }
+            }),
+            initial_sync_callback: link.send_back(
+                move |response: Result<RoomInitialSync, Error>| match response {
+                    Ok(res) => Msg::BkRes(BkResponse::RoomPeeked(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::SyncFailed(id, e.into())),
+                },
+            ),
+            next_msg_callback: link.send_back(move |response: Result<MessagesResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::NewMsgGot(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::SyncFailed(id, e.into())),
+                }
+            }),
+            sync_task: None,
+            sync_retry_task: None,
+            backoff: Backoff::new(),
+
+            more_msg_callback: link.send_back(move |response: Result<MessagesResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::MsgGot(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::MoreMsgFailed(id, e.into())),
+                }
+            }),
+            more_msg_task: None,
+            history: None,
+
+            missing_event_callback: link.send_back(
+                move |response: Result<ContextResponse, Error>| match response {
+                    Ok(res) => Msg::BkRes(BkResponse::MissingEventGot(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::MissingEventFailed(id, e.into())),
+                },
+            ),
+            missing_event_task: None,
+            gap_filling: None,
+
+            state_callback: link.send_back(move |response: Result<ContextResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::StateFetched(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::FetchStateFailed(id, e.into())),
+                }
+            }),
+            state_task: None,
+
+            leaving_room_callback: link.send_back(
+                move |response: Result<(), Error>| match response {
+                    Ok(_) => Msg::BkRes(BkResponse::RoomLeft(id)),
+                    Err(e) => Msg::BkRes(BkResponse::LeavingRoomFailed(id, e.into())),
+                },
+            ),
+            leaving_room_task: None,
+
+            disconnection_callback: link.send_back(
+                move |response: Result<(), Error>| match response {
+                    Ok(_) => Msg::BkRes(BkResponse::Disconnected(id)),
+                    Err(e) => Msg::BkRes(BkResponse::DisconnectionFailed(id, e.into())),
+                },
+            ),
+            disconnection_task: None,
+
+            session: session.clone(),
+            backend: CSBackend::with_session(session),
+            events_dag: None,
+            error: None,
+        }
+    }
+}
+
+// This contains every informations needed for the observation of a room from a given HS by using
+// the Matrix Visualisations' backend.
+pub struct MVView {
+    id: ViewIndex,
+
+    discovery_callback: Callback<Result<WellKnown, Error>>,
+    discovery_task: Option<FetchTask>,
+
+    alias_callback: Callback<Result<RoomAliasResponse, Error>>,
+    alias_task: Option<FetchTask>,
+
+    deepest_callback: Callback<Result<EventsResponse, Error>>,
+    deepest_task: Option<FetchTask>,
+
+    ancestors_callback: Callback<Result<EventsResponse, Error>>,
+    ancestors_task: Option<FetchTask>,
+
+    stop_callback: Callback<Result<(), Error>>,
+    stop_task: Option<FetchTask>,
+
+    descendants_callback: Callback<Result<EventsResponse, Error>>,
+    descendants_task: Option<FetchTask>,
+    descendants_timeout_task: Option<TimeoutTask>, // Also used to retry after a failure
+    backoff: Backoff,
+
+    state_callback: Callback<Result<EventsResponse, Error>>,
+    state_task: Option<FetchTask>,
+
+    session: Arc<RwLock<MVSession>>,
+    backend: MatrixVisualisationsBackend,
+    events_dag: Option<Arc<RwLock<RoomEvents>>>,
+    error: Option<MatrixError>, // The last failure of the requests of the view, shown to the user
+}
+
+impl MVView {
+    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> MVView {
+        let session = Arc::new(RwLock::new(MVSession::empty()));
+
+        MVView {
+            id,
+
+            discovery_callback: link.send_back(move |response: Result<WellKnown, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Discovered(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::DiscoveryFailed(id, e.into())),
+                }
+            }),
+            discovery_task: None,
+
+            alias_callback: link.send_back(move |response: Result<RoomAliasResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::AliasResolved(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::AliasResolutionFailed(id, e.into())),
+                }
+            }),
+            alias_task: None,
+
+            deepest_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::DeepestEvents(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::DeepestRqFailed(id, e.into())),
+                }
+            }),
+            deepest_task: None,
+
+            ancestors_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Ancestors(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::AncestorsRqFailed(id, e.into())),
+                }
+            }),
+            ancestors_task: None,
+
+            descendants_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Descendants(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::DescendantsRqFailed(id, e.into())),
+                }
+            }),
+            descendants_task: None,
+            descendants_timeout_task: None,
+            backoff: Backoff::new(),
+
+            state_callback: link.send_back(move |response: Result<EventsResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::State(id, res)),
+                    Err(e) => Msg::BkRes(BkResponse::StateRqFailed(id, e.into())),
+                }
+            }),
+            state_task: None,
+
+            stop_callback: link.send_back(move |response: Result<(), Error>| match response {
+                Ok(_) => Msg::BkRes(BkResponse::Disconnected(id)),
+                Err(e) => Msg::BkRes(BkResponse::DisconnectionFailed(id, e.into())),
+            }),
+            stop_task: None,
+
+            session: session.clone(),
+            backend: MatrixVisualisationsBackend::with_session(session),
+            events_dag: None,
+            error: None,
+        }
+    }
+}
+
+// This contains every informations needed for the observation of a room from the events read in
+// local files.
+pub struct FileView {
+    id: ViewIndex,
+
+    reading_callback: Callback<Result<usize, Error>>,
+    reading_tasks: Vec<ReaderTask>,
+
+    session: Arc<RwLock<FileSession>>,
+    backend: FileBackend,
+    events_dag: Option<Arc<RwLock<RoomEvents>>>,
+}
+
+impl FileView {
+    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> FileView {
+        let session = Arc::new(RwLock::new(FileSession::empty()));
+
+        FileView {
+            id,
+
+            reading_callback: link.send_back(
+                move |response: Result<usize, Error>| match response {
+                    Ok(count) => Msg::BkRes(BkResponse::FileRead(id, count)),
+                    Err(_) => Msg::BkRes(BkResponse::FileReadFailed(id)),
+                },
+            ),
+            reading_tasks: Vec::new(),
+
+            session: session.clone(),
+            backend: FileBackend::with_session(session),
+            events_dag: None,
+        }
+    }
+}
+
+// This contains every informations needed for the observation of a room from a given HS by using
+// the federation API.
+pub struct FederationView {
+    id: ViewIndex,
+
+    event_callback: Callback<Result<Transaction, Error>>,
+    event_task: Option<FetchTask>,
+
+    backfill_callback: Callback<Result<Transaction, Error>>,
+    backfill_task: Option<FetchTask>,
+
+    missing_events_callback: Callback<Result<MissingEventsResponse, Error>>,
+    missing_events_task: Option<FetchTask>,
+
+    state_ids_callback: Callback<Result<StateIdsResponse, Error>>,
+    state_ids_task: Option<FetchTask>,
+
+    session: Arc<RwLock<FederationSession>>,
+    backend: FederationBackend,
+    events_dag: Option<Arc<RwLock<RoomEvents>>>,
+}
+
+impl FederationView {
+    pub fn new(id: ViewIndex, link: &mut ComponentLink<Model>) -> FederationView {
+        let session = Arc::new(RwLock::new(FederationSession::empty()));
+
+        FederationView {
+            id,
+
+            event_callback: link.send_back(move |response: Result<Transaction, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::FederationEvent(id, res)),
+                    Err(_) => Msg::BkRes(BkResponse::EventRqFailed(id)),
+                }
+            }),
+            event_task: None,
+
+            backfill_callback: link.send_back(move |response: Result<Transaction, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::Backfilled(id, res)),
+                    Err(_) => Msg::BkRes(BkResponse::BackfillRqFailed(id)),
+                }
+            }),
+            backfill_task: None,
+
+            missing_events_callback: link.send_back(
+                move |response: Result<MissingEventsResponse, Error>| match response {
+                    Ok(res) => Msg::BkRes(BkResponse::MissingEvents(id, res)),
+                    Err(_) => Msg::BkRes(BkResponse::MissingEventsRqFailed(id)),
+                },
+            ),
+            missing_events_task: None,
+
+            state_ids_callback: link.send_back(move |response: Result<StateIdsResponse, Error>| {
+                match response {
+                    Ok(res) => Msg::BkRes(BkResponse::StateIds(id, res)),
+                    Err(_) => Msg::BkRes(BkResponse::StateIdsRqFailed(id)),
+                }
+            }),
+            state_ids_task: None,
+
+            session: session.clone(),
+            backend: FederationBackend::with_session(session),
+            events_dag: None,
+        }
+    }
+}
+
+// The replay of the construction of the DAG of a view, one event at a time. The events received
+// during the replay are only displayed once it is stopped.
+struct Replay {
+    view_id: ViewIndex,
+    steps: Vec<ReplayStep>,
+    position: usize, // The number of steps currently displayed
+    playing: bool,
+    timeout_task: Option<TimeoutTask>, // The task triggering the next step while playing
+}
+
+// The loading of the full history of the room observed by a CS view, with `/messages` requests
+// made one after the other until the `m.room.create` event or one of the limits is reached.
+struct HistoryLoading {
+    depth_limit: Option<i64>,
+    events_limit: Option<usize>,
+    pages: usize,         // The number of `/messages` requests made so far
+    events: usize,        // The number of events received so far
+    pending: DataSet,     // The events added to the DAG but not displayed yet
+    pending_pages: usize, // The number of requests which brought the pending events
+}
+
+impl HistoryLoading {
+    // Get the events which haven't been displayed yet, which are then considered displayed.
+    fn take_pending(&mut self) -> DataSet {
+        self.pending_pages = 0;
+
+        std::mem::replace(&mut self.pending, DataSet::default())
+    }
+}
+
+// The filling of the gaps in the DAG of a CS view, by fetching the missing `prev_events` of its
+// orphan events one by one. Each generation of missing ancestors is displayed once all of its
+// events have been requested, and the filling stops after `depth_limit` generations.
+struct GapFilling {
+    depth_limit: usize,
+    depth: usize,               // The generation of missing ancestors being fetched
+    queue: Vec<String>,         // The missing events of this generation which are still to request
+    requested: HashSet<String>, // Every event requested so far, so the unavailable ones are only requested once
+    events: Vec<JsonValue>,     // The events of this generation received so far
+    fetched: usize,             // The number of events received so far
+}
+
+impl GapFilling {
+    fn new(depth_limit: usize) -> Self {
+        GapFilling {
+            depth_limit,
+            depth: 0,
+            queue: Vec::new(),
+            requested: HashSet::new(),
+            events: Vec::new(),
+            fetched: 0,
+        }
+    }
+
+    // Moves on to the next generation, made of the missing `prev_events` of the orphan events of
+    // `dag` which haven't been requested yet, and get the first of them to request, unless there
+    // is none or the depth limit has been reached.
+    fn next_generation(&mut self, dag: &RoomEvents) -> Option<String> {
+        if self.depth >= self.depth_limit {
+            return None;
+        }
+
+        let requested = &self.requested;
+
+        self.depth += 1;
+        self.queue = dag
+            .missing_prev_events()
+            .into_iter()
+            .filter(|id| !requested.contains(id))
+            .collect();
+
+        self.next_event()
+    }
+
+    // Get the next missing event of the current generation to request.
+    fn next_event(&mut self) -> Option<String> {
+        let id = self.queue.pop()?;
+        self.requested.insert(id.clone());
+
+        Some(id)
+    }
+}
+
+// This defines which fields of the event body will be displayed in the nodes of the displayed DAG.
+struct FieldsChoice {
+    sender: bool,
+    origin: bool,
+    origin_server_ts: bool,
+    etype: bool,
+    state_key: bool,
+    prev_events: bool,
+    depth: bool,
+    redacts: bool,
+    event_id: bool,
+
+    fields: HashSet<Field>,
+}
+
+// This defines which kinds of edges will be displayed in the DAG.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum EdgesChoice {
+    Prev,
+    Auth,
+    Both,
+}
+
+// This defines the formats in which the DAG can be exported.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum ExportFormat {
+    Dot,
+    GraphML,
+    Gexf,
+}
+
+// This defines which views are included in the exports of the DAG.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum ExportScope {
+    CurrentView,
+    AllViews,
+}
+
+pub enum Msg {
+    UI(UIEvent),
+    UICmd(UICommand),
+    BkCmd(BkCommand),
+    BkRes(BkResponse),
+}
+
+/// These messages notifies the application of changes in the data modifiable via the UI.
+pub enum UIEvent {
+    ChooseCSBackend,
+    ChooseMVBackend,
+    ChooseFileBackend,
+    ChooseFederationBackend,
+    ViewChoice(ViewIndex),
+    AddView,
+    ServerName(html::ChangeData),
+    BaseUrl(html::ChangeData),
+    RoomId(html::ChangeData),
+    TogglePeek,
+
+    ChooseLoginMethod(LoginMethod),
+    Username(html::ChangeData),
+    Password(html::ChangeData),
+    Token(html::ChangeData),
+
+    EventsFiles(html::ChangeData),
+
+    FederationAddress(html::ChangeData),
+    Origin(html::ChangeData),
+    SigningKey(html::ChangeData),
+    EventId(html::ChangeData),
+
+    ToggleSender,
+    ToggleOrigin,
+    ToggleOriginServerTS,
+    ToggleType,
+    ToggleStateKey,
+    TogglePrevEvents,
+    ToggleDepth,
+    ToggleRedacts,
+    ToggleEventID,
+
+    ChooseEdges(EdgesChoice),
+    ToggleBranches,
+
+    DiffViewA(ViewIndex),
+    DiffViewB(ViewIndex),
+
+    LoadSnapshot(html::ChangeData),
+    SnapshotLoaded(FileData),
+
+    ImportKeys(html::ChangeData),
+    KeysFileLoaded(FileData),
+
+    ChooseReplayOrder(ReplayOrder),
+    ReplaySpeed(html::ChangeData),
+    ReplayPosition(html::ChangeData),
+
+    HistoryDepthLimit(html::ChangeData),
+    HistoryEventsLimit(html::ChangeData),
+    GapDepthLimit(html::ChangeData),
+
+    FilterTypes(html::ChangeData),
+    FilterNotTypes(html::ChangeData),
+    FilterSenders(html::ChangeData),
+    FilterNotSenders(html::ChangeData),
+    ToggleLazyLoadMembers,
+    TimelineLimit(html::ChangeData),
+}
+
+pub enum UICommand {
+    DisplayEventBody,
+    DisplayRedactedBody,
+    ResolveState,
+    CompareViews,
+    ClearComparison,
+    Export(ExportFormat, ExportScope),
+    SaveSnapshot,
+    LoginViaSso,
+    StartReplay,
+    PlayReplay,
+    PauseReplay,
+    ReplayTick,
+    StopReplay,
+}
+
+/// These messages are used by the frontend to send commands to the backend.
+pub enum BkCommand {
+    GetLoginFlows(ViewIndex),
+    Connect(ViewIndex),
+    ListRooms(ViewIndex),
+    JoinRoom(ViewIndex),
+    Sync(ViewIndex),
+    MoreMsg,
+    LoadHistory(ViewIndex),
+    StopLoadingHistory(ViewIndex),
+    FetchState,
+    LeaveRoom(ViewIndex),
+    Disconnect(ViewIndex),
+    FetchMissingEvents(ViewIndex),
+    FetchServerKeys,
+}
+
+/// These messages are responses from the backend to the frontend.
+pub enum BkResponse {
+    Discovered(ViewIndex, WellKnown),
+    DiscoveryFailed(ViewIndex, MatrixError),
+    AliasResolved(ViewIndex, RoomAliasResponse),
+    AliasResolutionFailed(ViewIndex, MatrixError),
+    LoginFlows(ViewIndex, LoginFlows),
+    Connected(ViewIndex, ConnectionResponse),
+    TokenChecked(ViewIndex, WhoAmIResponse),
+    GuestRegistered(ViewIndex, RegisterResponse),
+    RoomsList(ViewIndex, JoinedRooms),
+    RoomJoined(ViewIndex),
+    FilterUploaded(ViewIndex, FilterResponse),
+    Synced(ViewIndex, SyncResponse),
+    RoomPeeked(ViewIndex, RoomInitialSync),
+    NewMsgGot(ViewIndex, MessagesResponse),
+    MsgGot(ViewIndex, MessagesResponse),
+    MissingEventGot(ViewIndex, ContextResponse),
+    StateFetched(ViewIndex, ContextResponse),
+    RoomLeft(ViewIndex),
+    Disconnected(ViewIndex),
+
+    LoginFlowsFailed(ViewIndex, MatrixError),
+    ConnectionFailed(ViewIndex, MatrixError),
+    TokenCheckFailed(ViewIndex, MatrixError),
+    ListingRoomsFailed(ViewIndex, MatrixError),
+    JoiningRoomFailed(ViewIndex, MatrixError),
+    FilterUploadFailed(ViewIndex, MatrixError),
+    SyncFailed(ViewIndex, MatrixError),
+    MoreMsgFailed(ViewIndex, MatrixError),
+    MissingEventFailed(ViewIndex, MatrixError),
+    FetchStateFailed(ViewIndex, MatrixError),
+    LeavingRoomFailed(ViewIndex, MatrixError),
+    DisconnectionFailed(ViewIndex, MatrixError),
+
+    DeepestEvents(ViewIndex, EventsResponse),
+    Ancestors(ViewIndex, EventsResponse),
+    Descendants(ViewIndex, EventsResponse),
+    State(ViewIndex, EventsResponse),
+
+    FileRead(ViewIndex, usize),
+    FileReadFailed(ViewIndex),
+
+    FederationEvent(ViewIndex, Transaction),
+    Backfilled(ViewIndex, Transaction),
+    MissingEvents(ViewIndex, MissingEventsResponse),
+    StateIds(ViewIndex, StateIdsResponse),
+
+    EventRqFailed(ViewIndex),
+    BackfillRqFailed(ViewIndex),
+    MissingEventsRqFailed(ViewIndex),
+    StateIdsRqFailed(ViewIndex),
+
+    DeepestRqFailed(ViewIndex, MatrixError),
+    AncestorsRqFailed(ViewIndex, MatrixError),
+    DescendantsRqFailed(ViewIndex, MatrixError),
+    StateRqFailed(ViewIndex, MatrixError),
+
+    ServerResolved(String, String), // The name of a server and the address of its federation API
+    ServerKeys(JsonValue),
+    ServerKeysRqFailed(String),
+}
+
+impl Component for Model {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_: Self::Properties, mut link: ComponentLink<Self>) -> Self {
+        let bk_type = Arc::new(RwLock::new(BackendChoice::CS));
+        let default_view = CSView::new(0, &mut link);
+
+        // Log in the default view with the login token given after an SSO login, if any
+        let received_login_token = login_token_from_url();
+        if let Some(token) = &received_login_token {
+            let mut session = default_view.session.write().unwrap();
+
+            session.login_method = LoginMethod::Token;
+            session.token = token.clone();
+        }
+
+        let default_fields_choice = FieldsChoice {
+            sender: false,
+            origin: false,
+            origin_server_ts: false,
+            etype: false,
+            state_key: false,
+            prev_events: false,
+            depth: false,
+            redacts: false,
+            event_id: true,
+
+            fields: [Field::EventID].iter().cloned().collect(),
+        };
+
+        Model {
+            console: ConsoleService::new(),
+            timeout: TimeoutService::new(),
+            vis: VisJsService::new(bk_type.clone()),
+
+            link,
+
+            bk_type,
+            view_idx: 0,
+            views: vec![View::CS(default_view)],
+            event_body: None,
+            event_body_redacted: false,
+            event_hash_check: None,
+            event_signature_check: None,
+            event_arrival: None,
+            room_state: None,
+            local_room_state: None,
+            fields_choice: default_fields_choice,
+            edges_choice: EdgesChoice::Prev,
+            branches_colored: false,
+            diff_views: (0, 1),
+            dag_diff: None,
+            replay_order: ReplayOrder::Arrival,
+            replay_speed: 2,
+            replay: None,
+            received_login_token,
+            history_depth_limit: None,
+            history_events_limit: None,
+            gap_depth_limit: 5,
+
+            server_keys: ServerKeys::new(),
+            keys_backend: KeysBackend::new(),
+            keys_tasks: Vec::new(),
+            reader: ReaderService::new(),
+            reader_tasks: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::UI(ui) => self.process_ui_event(ui),
+            Msg::UICmd(cmd) => self.process_ui_command(cmd),
+            Msg::BkCmd(cmd) => self.process_bk_command(cmd),
+            Msg::BkRes(res) => self.process_bk_response(res),
+        }
+
+        if self.vis.is_active() {
+            self.refresh_dag_diff();
+        }
+
+        true
+    }
+}
+
+impl Model {
+    fn process_ui_event(&mut self, event: UIEvent) {
+        // Change the informations of the session whenever their corresponding entries in the UI
+        // are changed
+        match event {
+            UIEvent::ChooseCSBackend => {
+                *self.bk_type.write().unwrap() = BackendChoice::CS;
+
+                let mut new_views: Vec<CSView> = (0..self.views.len())
+                    .map(|id| CSView::new(id, &mut self.link))
+                    .collect();
+
+                for (old_view, new_view) in self.views.iter().zip(new_views.iter_mut()) {
+                    let (server_name, room_id) = old_view.get_room();
+                    let mut new_session = new_view.session.write().unwrap();
+
+                    new_session.server_name = server_name;
+                    new_session.room_id = room_id;
+                }
+
+                let new_views = new_views.into_iter().map(|view| View::CS(view)).collect();
+
+                self.views = new_views;
+            }
+            UIEvent::ChooseMVBackend => {
+                *self.bk_type.write().unwrap() = BackendChoice::MV;
+
+                let mut new_views: Vec<MVView> = (0..self.views.len())
+                    .map(|id| MVView::new(id, &mut self.link))
+                    .collect();
+
+                for (old_view, new_view) in self.views.iter().zip(new_views.iter_mut()) {
+                    let (server_name, room_id) = old_view.get_room();
+                    let mut new_session = new_view.session.write().unwrap();
+
+                    new_session.server_name = server_name;
+                    new_session.room_id = room_id;
+                }
+
+                let new_views = new_views.into_iter().map(|view| View::MV(view)).collect();
+
+                self.views = new_views;
+            }
+            UIEvent::ChooseFileBackend => {
+                *self.bk_type.write().unwrap() = BackendChoice::File;
+
+                let mut new_views: Vec<FileView> = (0..self.views.len())
+                    .map(|id| FileView::new(id, &mut self.link))
+                    .collect();
+
+                for (old_view, new_view) in self.views.iter().zip(new_views.iter_mut()) {
+                    let (server_name, room_id) = old_view.get_room();
+                    let mut new_session = new_view.session.write().unwrap();
+
+                    new_session.server_name = server_name;
+                    new_session.room_id = room_id;
+                }
+
+                let new_views = new_views.into_iter().map(|view| View::File(view)).collect();
+
+                self.views = new_views;
+            }
+            UIEvent::ChooseFederationBackend => {
+                *self.bk_type.write().unwrap() = BackendChoice::Federation;
+
+                let mut new_views: Vec<FederationView> = (0..self.views.len())
+                    .map(|id| FederationView::new(id, &mut self.link))
+                    .collect();
+
+                for (old_view, new_view) in self.views.iter().zip(new_views.iter_mut()) {
+                    let (server_name, room_id) = old_view.get_room();
+                    let mut new_session = new_view.session.write().unwrap();
+
+                    new_session.server_name = server_name;
+                    new_session.room_id = room_id;
+                }
+
+                let new_views = new_views
+                    .into_iter()
+                    .map(|view| View::Federation(view))
+                    .collect();
+
+                self.views = new_views;
+            }
+            UIEvent::ViewChoice(vc) => {
+                let input: web::html_element::InputElement = web::document()
+                    .query_selector("#server-name-input")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                input.set_raw_value("");
+
+                let input: web::html_element::InputElement = web::document()
+                    .query_selector("#room-id-input")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                input.set_raw_value("");
+
+                let bk_type = *self.bk_type.read().unwrap();
+                if bk_type == BackendChoice::CS || bk_type == BackendChoice::MV {
+                    clear_input("#base-url-input");
+                }
+
+                if *self.bk_type.read().unwrap() == BackendChoice::CS {
+                    clear_input("#username-input");
+                    clear_input("#password-input");
+                    clear_input("#token-input");
+                    clear_input("#filter-types-input");
+                    clear_input("#filter-not-types-input");
+                    clear_input("#filter-senders-input");
+                    clear_input("#filter-not-senders-input");
+                    clear_input("#timeline-limit-input");
+                }
+
+                if *self.bk_type.read().unwrap() == BackendChoice::Federation {
+                    let input: web::html_element::InputElement = web::document()
+                        .query_selector("#address-input")
+                        .expect("Couldn't get document element")
+                        .expect("Couldn't get document element")
+                        .try_into()
+                        .unwrap();
+                    input.set_raw_value("");
+
+                    let input: web::html_element::InputElement = web::document()
+                        .query_selector("#event-id-input")
+                        .expect("Couldn't get document element")
+                        .expect("Couldn't get document element")
+                        .try_into()
+                        .unwrap();
+                    input.set_raw_value("");
+                }
+
+                self.view_idx = vc;
+            }
+            UIEvent::AddView => {
+                let view = match *self.bk_type.read().unwrap() {
+                    BackendChoice::CS => View::CS(CSView::new(self.views.len(), &mut self.link)),
+                    BackendChoice::MV => View::MV(MVView::new(self.views.len(), &mut self.link)),
+                    BackendChoice::File => {
+                        View::File(FileView::new(self.views.len(), &mut self.link))
+                    }
+                    BackendChoice::Federation => {
+                        View::Federation(FederationView::new(self.views.len(), &mut self.link))
+                    }
+                };
+
+                self.views.push(view);
+
+                self.console.log("View added");
+            }
+            UIEvent::ServerName(sn) => {
+                if let html::ChangeData::Value(sn) = sn {
+                    // The base URL discovered for the previous server name doesn't apply anymore
+                    match &self.views[self.view_idx] {
+                        View::CS(view) => {
+                            let mut session = view.session.write().unwrap();
+
+                            session.server_name = sn;
+                            session.discovered_base_url = None;
+                        }
+                        View::MV(view) => {
+                            let mut session = view.session.write().unwrap();
+
+                            session.server_name = sn;
+                            session.discovered_base_url = None;
+                        }
+                        View::File(view) => view.session.write().unwrap().server_name = sn,
+                        View::Federation(view) => view.session.write().unwrap().server_name = sn,
+                    }
+                }
+            }
+            UIEvent::BaseUrl(url) => {
+                if let html::ChangeData::Value(url) = url {
+                    match &self.views[self.view_idx] {
+                        View::CS(view) => view.session.write().unwrap().base_url = url,
+                        View::MV(view) => view.session.write().unwrap().base_url = url,
+                        View::File(_) | View::Federation(_) => {}
+                    }
+                }
+            }
+            UIEvent::RoomId(ri) => {
+                if let html::ChangeData::Value(ri) = ri {
+                    for view in &self.views {
+                        match view {
+                            View::CS(view) => {
+                                let mut session = view.session.write().unwrap();
+
+                                session.room_id = ri.clone();
+                                session.via_servers = Vec::new();
+                            }
+                            View::MV(view) => {
+                                view.session.write().unwrap().room_id = ri.clone();
+                            }
+                            View::File(view) => {
+                                view.session.write().unwrap().room_id = ri.clone();
+                            }
+                            View::Federation(view) => {
+                                view.session.write().unwrap().room_id = ri.clone();
+                            }
+                        }
+                    }
+                }
+            }
+            UIEvent::TogglePeek => {
+                if let View::CS(view) = &mut self.views[self.view_idx] {
+                    let mut session = view.session.write().unwrap();
+
+                    session.peek = !session.peek;
+                }
+            }
+            UIEvent::ChooseLoginMethod(method) => {
+                if let View::CS(view) = &mut self.views[self.view_idx] {
+                    view.session.write().unwrap().login_method = method;
+                }
+            }
+            UIEvent::Username(u) => {
+                if let html::ChangeData::Value(u) = u {
+                    if let View::CS(view) = &mut self.views[self.view_idx] {
+                        view.session.write().unwrap().username = u;
+                    }
+                }
+            }
+            UIEvent::Password(p) => {
+                if let html::ChangeData::Value(p) = p {
+                    if let View::CS(view) = &mut self.views[self.view_idx] {
+                        view.session.write().unwrap().password = p;
+                    }
+                }
+            }
+            UIEvent::Token(t) => {
+                if let html::ChangeData::Value(t) = t {
+                    if let View::CS(view) = &mut self.views[self.view_idx] {
+                        view.session.write().unwrap().token = t;
+                    }
+                }
+            }
+            UIEvent::FederationAddress(a) => {
+                if let html::ChangeData::Value(a) = a {
+                    if let View::Federation(view) = &mut self.views[self.view_idx] {
+                        view.session.write().unwrap().address = a;
+                    }
+                }
+            }
+            UIEvent::Origin(o) => {
+                if let html::ChangeData::Value(o) = o {
+                    // Every view makes its requests on behalf of the same server
+                    for view in &self.views {
+                        if let View::Federation(view) = view {
+                            view.session.write().unwrap().origin = o.clone();
+                        }
+                    }
+                }
+            }
+            UIEvent::SigningKey(k) => {
+                if let html::ChangeData::Value(k) = k {
+                    // An invalid key doesn't replace the one in use, only an empty field removes it
+                    let signing_key = if k.trim().is_empty() {
+                        None
+                    } else {
+                        match SigningKey::from_key_file(&k) {
+                            Ok(signing_key) => Some(signing_key),
+                            Err(e) => {
+                                self.console
+                                    .log(&format!("Failed to read the signing key: {}", e));
+                                return;
+                            }
+                        }
+                    };
+
+                    for view in &self.views {
+                        if let View::Federation(view) = view {
+                            view.session.write().unwrap().signing_key = signing_key.clone();
+                        }
+                    }
+                }
+            }
+            UIEvent::EventId(e) => {
+                if let html::ChangeData::Value(e) = e {
+                    if let View::Federation(view) = &mut self.views[self.view_idx] {
+                        view.session.write().unwrap().event_id = e;
+                    }
+                }
+            }
+            UIEvent::ToggleSender => {
+                let fc = &mut self.fields_choice;
+
+                fc.sender = !fc.sender;
+
+                if fc.sender {
+                    fc.fields.insert(Field::Sender);
+                } else {
+                    fc.fields.remove(&Field::Sender);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::ToggleOrigin => {
+                let fc = &mut self.fields_choice;
+
+                fc.origin = !fc.origin;
+
+                if fc.origin {
+                    fc.fields.insert(Field::Origin);
+                } else {
+                    fc.fields.remove(&Field::Origin);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::ToggleOriginServerTS => {
+                let fc = &mut self.fields_choice;
+
+                fc.origin_server_ts = !fc.origin_server_ts;
+
+                if fc.origin_server_ts {
+                    fc.fields.insert(Field::OriginServerTS);
+                } else {
+                    fc.fields.remove(&Field::OriginServerTS);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::ToggleType => {
+                let fc = &mut self.fields_choice;
+
+                fc.etype = !fc.etype;
+
+                if fc.etype {
+                    fc.fields.insert(Field::Type);
+                } else {
+                    fc.fields.remove(&Field::Type);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::ToggleStateKey => {
+                let fc = &mut self.fields_choice;
+
+                fc.state_key = !fc.state_key;
+
+                if fc.state_key {
+                    fc.fields.insert(Field::StateKey);
+                } else {
+                    fc.fields.remove(&Field::StateKey);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::TogglePrevEvents => {
+                let fc = &mut self.fields_choice;
+
+                fc.prev_events = !fc.prev_events;
+
+                if fc.prev_events {
+                    fc.fields.insert(Field::PrevEvents);
+                } else {
+                    fc.fields.remove(&Field::PrevEvents);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::ToggleDepth => {
+                let fc = &mut self.fields_choice;
+
+                fc.depth = !fc.depth;
+
+                if fc.depth {
+                    fc.fields.insert(Field::Depth);
+                } else {
+                    fc.fields.remove(&Field::Depth);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::ToggleRedacts => {
+                let fc = &mut self.fields_choice;
+
+                fc.redacts = !fc.redacts;
+
+                if fc.redacts {
+                    fc.fields.insert(Field::Redacts);
+                } else {
+                    fc.fields.remove(&Field::Redacts);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::ToggleEventID => {
+                let fc = &mut self.fields_choice;
+
+                fc.event_id = !fc.event_id;
+
+                if fc.event_id {
+                    fc.fields.insert(Field::EventID);
+                } else {
+                    fc.fields.remove(&Field::EventID);
+                }
+
+                for view in &self.views {
+                    if let Some(events_dag) = view.get_events_dag() {
+                        let mut events_dag = events_dag.write().unwrap();
+
+                        events_dag.change_fields(&fc.fields);
+                    }
+
+                    if self.vis.is_active() {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_labels(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::ChooseEdges(ec) => {
+                self.edges_choice = ec;
+
+                match ec {
+                    EdgesChoice::Prev => self.vis.show_edges(true, false),
+                    EdgesChoice::Auth => self.vis.show_edges(false, true),
+                    EdgesChoice::Both => self.vis.show_edges(true, true),
+                }
+            }
+            UIEvent::ToggleBranches => {
+                self.branches_colored = !self.branches_colored;
+
+                self.vis.color_branches(self.branches_colored);
+
+                if self.vis.is_active() {
+                    for view in &self.views {
+                        if let Some(events_dag) = view.get_events_dag() {
+                            self.vis.update_colors(events_dag.clone(), view.get_id());
+                        }
+                    }
+                }
+            }
+            UIEvent::DiffViewA(view_id) => self.diff_views.0 = view_id,
+            UIEvent::DiffViewB(view_id) => self.diff_views.1 = view_id,
+            UIEvent::EventsFiles(cd) => {
+                if let html::ChangeData::Files(files) = cd {
+                    if let View::File(view) = &mut self.views[self.view_idx] {
+                        for file in files.iter() {
+                            let task = view
+                                .backend
+                                .read_events(view.reading_callback.clone(), file);
+
+                            view.reading_tasks.push(task);
+                        }
+                    }
+                }
+            }
+            UIEvent::LoadSnapshot(cd) => {
+                if let html::ChangeData::Files(files) = cd {
+                    self.reader_tasks.clear();
+
+                    for file in files.iter() {
+                        let callback = self
+                            .link
+                            .send_back(|file: FileData| Msg::UI(UIEvent::SnapshotLoaded(file)));
+
+                        self.reader_tasks
+                            .push(self.reader.read_file(file, callback));
+                    }
+                }
+            }
+            UIEvent::SnapshotLoaded(file) => match serde_json::from_slice(&file.content) {
+                Ok(snapshot) => self.load_snapshot(snapshot),
+                Err(e) => self.console.log(&format!(
+                    "Failed to parse the snapshot {}: {}",
+                    file.name, e
+                )),
+            },
+            UIEvent::ImportKeys(cd) => {
+                if let html::ChangeData::Files(files) = cd {
+                    self.reader_tasks.clear();
+
+                    for file in files.iter() {
+                        let callback = self
+                            .link
+                            .send_back(|file: FileData| Msg::UI(UIEvent::KeysFileLoaded(file)));
+
+                        self.reader_tasks
+                            .push(self.reader.read_file(file, callback));
+                    }
+                }
+            }
+            UIEvent::KeysFileLoaded(file) => match serde_json::from_slice(&file.content) {
+                Ok(json) => self.add_server_keys(&json),
+                Err(e) => self.console.log(&format!(
+                    "Failed to parse the keys file {}: {}",
+                    file.name, e
+                )),
+            },
+            UIEvent::ChooseReplayOrder(order) => {
+                self.replay_order = order;
+
+                // Replay the same number of events in the new order
+                if self.replay_running() {
+                    if let Some(replay) = &mut self.replay {
+                        if let Some(dag) = self.views[replay.view_id].get_events_dag() {
+                            replay.steps = dag.read().unwrap().replay(order);
+                            self.vis.show_replay_steps(
+                                replay.view_id,
+                                &replay.steps[..replay.position],
+                                true,
+                            );
+                        }
+                    }
+                }
+            }
+            UIEvent::ReplaySpeed(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    match v.parse::<u32>() {
+                        Ok(speed) if speed > 0 => self.replay_speed = speed,
+                        _ => self
+                            .console
+                            .log("The speed must be a positive number of events"),
+                    }
+                }
+            }
+            UIEvent::ReplayPosition(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    if let Ok(position) = v.parse() {
+                        self.seek_replay(position);
+                    }
+                }
+            }
+            UIEvent::HistoryDepthLimit(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    match v.trim() {
+                        "" => self.history_depth_limit = None,
+                        v => match v.parse() {
+                            Ok(depth) => self.history_depth_limit = Some(depth),
+                            Err(_) => self.console.log("The depth must be a number"),
+                        },
+                    }
+                }
+            }
+            UIEvent::HistoryEventsLimit(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    match v.trim() {
+                        "" => self.history_events_limit = None,
+                        v => match v.parse() {
+                            Ok(events) if events > 0 => self.history_events_limit = Some(events),
+                            _ => self
+                                .console
+                                .log("The number of events must be a positive number"),
+                        },
+                    }
+                }
+            }
+            UIEvent::GapDepthLimit(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    match v.trim().parse() {
+                        Ok(depth) if depth > 0 => self.gap_depth_limit = depth,
+                        _ => self.console.log("The depth must be a positive number"),
+                    }
+                }
+            }
+            UIEvent::FilterTypes(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    self.edit_filter(|filter| filter.types = parse_list(&v));
+                }
+            }
+            UIEvent::FilterNotTypes(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    self.edit_filter(|filter| filter.not_types = parse_list(&v));
+                }
+            }
+            UIEvent::FilterSenders(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    self.edit_filter(|filter| filter.senders = parse_list(&v));
+                }
+            }
+            UIEvent::FilterNotSenders(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    self.edit_filter(|filter| filter.not_senders = parse_list(&v));
+                }
+            }
+            UIEvent::ToggleLazyLoadMembers => {
+                self.edit_filter(|filter| filter.lazy_load_members = !filter.lazy_load_members);
+            }
+            UIEvent::TimelineLimit(cd) => {
+                if let html::ChangeData::Value(v) = cd {
+                    match v.trim() {
+                        "" => self.edit_filter(|filter| filter.timeline_limit = None),
+                        v => match v.parse() {
+                            Ok(limit) if limit > 0 => {
+                                self.edit_filter(|filter| filter.timeline_limit = Some(limit))
+                            }
+                            _ => self.console.log("The limit must be a positive number"),
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    fn process_ui_command(&mut self, cmd: UICommand) {
+        match cmd {
+            UICommand::DisplayEventBody => {
+                let (view_id, event_id) = selected_event();
+
+                if let Some(dag) = self.views[view_id].get_events_dag() {
+                    let dag = dag.read().unwrap();
+                    let event = dag.get_event(&event_id);
+
+                    self.event_body =
+                        event.map(|ev| serde_json::to_string_pretty(ev.json()).unwrap());
+                    self.event_body_redacted = false;
+                    self.event_hash_check = event.map(|ev| ev.hash_check);
+                    self.event_signature_check = event.map(|ev| ev.signature_check);
+                    self.event_arrival = dag.arrival(&event_id);
+                }
+            }
+            UICommand::DisplayRedactedBody => {
+                let (view_id, event_id) = selected_event();
+
+                if let Some(dag) = self.views[view_id].get_events_dag() {
+                    let dag = dag.read().unwrap();
+
+                    match (dag.get_event(&event_id), dag.room_version()) {
+                        (Some(event), Some(version)) => {
+                            let redacted = model::crypto::redact(event.json(), version);
+
+                            self.event_body = serde_json::to_string_pretty(&redacted).ok();
+                            self.event_body_redacted = true;
+                        }
+                        (Some(_), None) => {
+                            self.console.log(
+                                "The version of the room is unknown, the event can't be redacted",
+                            );
+                        }
+                        (None, _) => {}
+                    }
+                }
+            }
+            UICommand::ResolveState => {
+                let (view_id, event_id) = selected_event();
+
+                if let Some(dag) = self.views[view_id].get_events_dag() {
+                    let dag = dag.read().unwrap();
+
+                    // Run the state resolution over the events of the DAG held by the view
+                    let state = StateResolver::new(&dag)
+                        .and_then(|mut resolver| resolver.state_after(&event_id))
+                        .map(|state| model::state::state_to_json(&dag, &state));
+
+                    self.local_room_state = match state {
+                        Ok(state) => serde_json::to_string_pretty(&state).ok(),
+                        Err(e) => Some(format!("Failed to resolve the state: {}", e)),
+                    };
+                }
+            }
+            UICommand::CompareViews => {
+                let (view_a, view_b) = self.diff_views;
+
+                if view_a == view_b {
+                    self.console.log("Choose two different views to compare");
+                    return;
+                }
+
+                match (
+                    self.views[view_a].get_events_dag(),
+                    self.views[view_b].get_events_dag(),
+                ) {
+                    (Some(dag_a), Some(dag_b)) => {
+                        let diff = DagDiff::new(&dag_a.read().unwrap(), &dag_b.read().unwrap());
+
+                        self.vis.show_diff(view_a, view_b, &diff);
+                        self.dag_diff = Some(diff);
+                    }
+                    _ => self
+                        .console
+                        .log("Both views must have a DAG to be compared"),
+                }
+            }
+            UICommand::ClearComparison => {
+                if self.vis.is_active() {
+                    self.vis.clear_diff();
+                }
+
+                self.dag_diff = None;
+            }
+            UICommand::Export(format, scope) => {
+                // Only the DOT export follows the choice of the edges, the other formats always
+                // have both kinds of edges since the graph tools can filter them
+                let edge_kinds = match self.edges_choice {
+                    EdgesChoice::Prev => vec![EdgeKind::Prev],
+                    EdgesChoice::Auth => vec![EdgeKind::Auth],
+                    EdgesChoice::Both => vec![EdgeKind::Prev, EdgeKind::Auth],
+                };
+
+                let document = {
+                    let dags = self.exported_dags(scope);
+                    let dags: Vec<_> = dags.iter().map(|(id, dag)| (*id, &**dag)).collect();
+
+                    if dags.is_empty() {
+                        None
+                    } else {
+                        Some(match format {
+                            ExportFormat::Dot => model::export::to_dot(&dags, &edge_kinds),
+                            ExportFormat::GraphML => model::export::to_graphml(&dags),
+                            ExportFormat::Gexf => model::export::to_gexf(&dags),
+                        })
+                    }
+                };
+
+                let (filename, mime_type) = match format {
+                    ExportFormat::Dot => ("events.dot", "text/vnd.graphviz"),
+                    ExportFormat::GraphML => ("events.graphml", "application/graphml+xml"),
+                    ExportFormat::Gexf => ("events.gexf", "application/gexf+xml"),
+                };
+
+                match document {
+                    Some(document) => download(filename, mime_type, &document),
+                    None => self.console.log("There is no DAG to export"),
+                }
+            }
+            UICommand::SaveSnapshot => {
+                let view = &self.views[self.view_idx];
+                let (_, room_id) = view.get_room();
+                let backend = *self.bk_type.read().unwrap();
+
+                let snapshot = view.get_events_dag().as_ref().map(|dag| {
+                    let dag = dag.read().unwrap();
+                    let fetched_at = dag.last_received_at().unwrap_or_else(now);
+
+                    Snapshot::new(&dag, &room_id, backend, fetched_at)
+                });
+
+                match snapshot.map(|snapshot| serde_json::to_string_pretty(&snapshot)) {
+                    Some(Ok(json)) => download("snapshot.json", "application/json", &json),
+                    Some(Err(e)) => self
+                        .console
+                        .log(&format!("Failed to serialize the snapshot: {}", e)),
+                    None => self.console.log("There is no DAG to save"),
+                }
+            }
+            UICommand::LoginViaSso => {
+                if let View::CS(view) = &mut self.views[self.view_idx] {
+                    let mut session = view.session.write().unwrap();
+
+                    if session.server_name.is_empty() {
+                        self.console
+                            .log("Enter the name of the server to log in to");
+                    } else {
+                        // The SSO login happens in a new tab, which is given the login token once
+                        // the user is logged in so it can be pasted in this view
+                        let url = cs_api::sso_redirect_url(&session, &app_url());
+
+                        js! { window.open(@{url}, "_blank"); }
+
+                        session.login_method = LoginMethod::Token;
+                    }
+                }
+            }
+            UICommand::StartReplay => {
+                self.stop_replay();
+
+                let view_id = self.view_idx;
+
+                match self.views[view_id].get_events_dag() {
+                    Some(dag) if self.vis.is_active() => {
+                        let steps = dag.read().unwrap().replay(self.replay_order);
+
+                        self.vis.start_replay(view_id);
+                        self.replay = Some(Replay {
+                            view_id,
+                            steps,
+                            position: 0,
+                            playing: true,
+                            timeout_task: None,
+                        });
+                        self.schedule_replay_tick();
+                    }
+                    _ => self.console.log("There is no DAG to replay"),
+                }
+            }
+            UICommand::PlayReplay => {
+                if self.replay_running() {
+                    // Start again from the beginning if the replay has reached its end
+                    if let Some(replay) = &self.replay {
+                        if replay.position == replay.steps.len() {
+                            self.seek_replay(0);
+                        }
+                    }
+
+                    if let Some(replay) = &mut self.replay {
+                        replay.playing = true;
+                    }
+                    self.schedule_replay_tick();
+                }
+            }
+            UICommand::PauseReplay => {
+                if let Some(replay) = &mut self.replay {
+                    replay.playing = false;
+                    replay.timeout_task = None;
+                }
+            }
+            UICommand::ReplayTick => {
+                if self.replay_running() {
+                    let next_position = match &self.replay {
+                        Some(replay) if replay.playing => replay.position + 1,
+                        _ => return,
+                    };
+
+                    self.seek_replay(next_position);
+
+                    let finished = match &mut self.replay {
+                        Some(replay) => {
+                            replay.timeout_task = None;
+                            replay.position == replay.steps.len()
+                        }
+                        None => true,
+                    };
+
+                    if finished {
+                        if let Some(replay) = &mut self.replay {
+                            replay.playing = false;
+                        }
+                    } else {
+                        self.schedule_replay_tick();
+                    }
+                }
+            }
+            UICommand::StopReplay => self.stop_replay(),
+        }
+    }
+
+    fn process_bk_command(&mut self, cmd: BkCommand) {
+        let console_msg = match cmd {
+            BkCommand::Connect(_) => "Connecting...",
+            BkCommand::ListRooms(_) => "Listing joined rooms...",
+            BkCommand::JoinRoom(_) => "Joining the room...",
+            BkCommand::Sync(_) => "Syncing...",
+            BkCommand::MoreMsg => "Retrieving previous messages...",
+            BkCommand::LoadHistory(_) => "Loading the full history of the room...",
+            BkCommand::StopLoadingHistory(_) => "Stopping the loading of the history...",
+            BkCommand::FetchState => "Fetching the state of the room...",
+            BkCommand::LeaveRoom(_) => "Leaving the room...",
+            BkCommand::Disconnect(_) => "Disconnecting...",
+            BkCommand::FetchMissingEvents(_) => "Fetching the missing events...",
+            BkCommand::FetchServerKeys => "Fetching the signing keys of the servers...",
+        };
+
+        self.console.log(console_msg);
+
+        // Order the backend to make requests to the homeserver according to the command received
+        match cmd {
+            BkCommand::GetLoginFlows(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    match view.login_flows_task {
+                        None => {
+                            view.login_flows_task =
+                                view.backend.login_flows(view.login_flows_callback.clone())
+                        }
+                        Some(_) => self.console.log("Already retrieving the login flows"),
+                    }
+                }
+            }
+            BkCommand::Connect(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => {
+                    let (connected, needs_discovery, alias, login_method, token) = {
+                        let session = view.session.read().unwrap();
+
+                        (
+                            session.access_token.is_some(),
+                            session.needs_discovery(),
+                            cs_api::is_room_alias(&session.room_id),
+                            session.login_method,
+                            session.token.clone(),
+                        )
+                    };
+
+                    if connected {
+                        self.console.log("You are already connected");
+                    } else if view.connection_task.is_some()
+                        || view.discovery_task.is_some()
+                        || view.alias_task.is_some()
+                    {
+                        self.console.log("Already connecting");
+                    } else if needs_discovery {
+                        // Look for the homeserver first, the connection goes on once it is found
+                        view.discovery_task =
+                            view.backend.well_known(view.discovery_callback.clone());
+                    } else if alias {
+                        // Same for the ID of the room, when it was given by its alias
+                        view.alias_task = view.backend.resolve_alias(view.alias_callback.clone());
+                    } else {
+                        match login_method {
+                            LoginMethod::AccessToken if token.is_empty() => {
+                                self.console.log("Enter an access token")
+                            }
+                            LoginMethod::AccessToken => {
+                                // The access token is used as is, once we know it is valid
+                                view.session.write().unwrap().access_token = Some(token);
+                                view.connection_task =
+                                    view.backend.whoami(view.whoami_callback.clone());
+                            }
+                            LoginMethod::Token if token.is_empty() => {
+                                self.console.log("Enter a login token")
+                            }
+                            LoginMethod::Password | LoginMethod::Token => {
+                                view.connection_task =
+                                    view.backend.connect(view.connection_callback.clone())
+                            }
+                            LoginMethod::Guest => {
+                                view.connection_task =
+                                    view.backend.register_guest(view.guest_callback.clone())
+                            }
+                        }
+                    }
+                }
+                View::MV(view) => match view.events_dag {
+                    None => match (&view.deepest_task, &view.discovery_task, &view.alias_task) {
+                        (None, None, None) => {
+                            let (needs_discovery, alias) = {
+                                let session = view.session.read().unwrap();
+
+                                (
+                                    session.needs_discovery(),
+                                    cs_api::is_room_alias(&session.room_id),
+                                )
+                            };
+
+                            if needs_discovery {
+                                view.discovery_task =
+                                    view.backend.well_known(view.discovery_callback.clone());
+                            } else if alias {
+                                view.alias_task =
+                                    view.backend.resolve_alias(view.alias_callback.clone());
+                            } else {
+                                view.deepest_task =
+                                    view.backend.deepest(view.deepest_callback.clone());
+                            }
+                        }
+                        _ => self.console.log("Already fetching deepest events"),
+                    },
+                    Some(_) => self.console.log("Deepest events already fetched"),
+                },
+                View::File(_) => self.console.log("Choose the files to read the events from"),
+                View::Federation(view) => match view.events_dag {
+                    None => match view.event_task {
+                        None => {
+                            let (can_sign, event_id) = {
+                                let session = view.session.read().unwrap();
+
+                                (
+                                    session.signing_key.is_some() && !session.origin.is_empty(),
+                                    session.event_id.clone(),
+                                )
+                            };
+
+                            if !can_sign {
+                                self.console
+                                    .log("Enter the name of the origin server and its signing key");
+                            } else if event_id.is_empty() {
+                                self.console
+                                    .log("Enter the ID of the event to start the observation from");
+                            } else {
+                                view.event_task =
+                                    view.backend.event(view.event_callback.clone(), &event_id);
+                            }
+                        }
+                        Some(_) => self.console.log("Already fetching the event"),
+                    },
+                    Some(_) => self.console.log("The event has already been fetched"),
+                },
+            },
+            BkCommand::ListRooms(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    if view.session.read().unwrap().peeks() {
+                        // The room is observed without joining it, whether the user is in it or
+                        // not, so the joined rooms don't matter
+                        self.console.log("Peeking at the room without joining it");
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                            .emit(());
+                    } else {
+                        view.listing_rooms_task =
+                            view.backend.list_rooms(view.listing_rooms_callback.clone())
+                    }
+                }
+            }
+            BkCommand::JoinRoom(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.joining_room_task =
+                        view.backend.join_room(view.joining_room_callback.clone())
+                }
+            }
+            BkCommand::Sync(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => {
+                    let (peeks, next_batch_token, has_filter_id) = {
+                        let session = view.session.read().unwrap();
+
+                        (
+                            session.peeks(),
+                            session.next_batch_token.clone(),
+                            session.filter_id.is_some(),
+                        )
+                    };
+
+                    // The filter is uploaded before the first `/sync` request, which then refers
+                    // to it by its ID, as well as the following ones
+                    if !peeks && !has_filter_id && !view.inline_filter {
+                        match view.filter_task {
+                            None => {
+                                view.filter_task =
+                                    view.backend.upload_filter(view.filter_callback.clone())
+                            }
+                            Some(_) => self.console.log("Already uploading the filter"),
+                        }
+
+                        return;
+                    }
+
+                    // When peeking at the room, the initial sync is replaced by `/initialSync`
+                    // and the new events are retrieved with `/messages`
+                    view.sync_task = match (peeks, next_batch_token) {
+                        (false, next_batch_token) => view
+                            .backend
+                            .sync(view.sync_callback.clone(), next_batch_token),
+                        (true, None) => view
+                            .backend
+                            .room_initial_sync(view.initial_sync_callback.clone()),
+                        (true, Some(_)) => view
+                            .backend
+                            .get_next_messages(view.next_msg_callback.clone()),
+                    }
+                }
+                View::MV(view) => {
+                    if let Some(dag) = &view.events_dag {
+                        let from = dag.read().unwrap().latest_events();
+
+                        view.descendants_task = view
+                            .backend
+                            .descendants(view.descendants_callback.clone(), &from);
+                    }
+                }
+                View::File(_) => {}       // The files don't get new events
+                View::Federation(_) => {} // The new events are only sent to the servers in the room
+            },
+            BkCommand::MoreMsg => {
+                let view_selection_input: web::html_element::InputElement = web::document()
+                    .query_selector("#targeted-view")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let view_id: ViewIndex = view_selection_input
+                    .raw_value()
+                    .parse()
+                    .expect("Failed to parse view_id");
+
+                match &mut self.views[view_id] {
+                    View::CS(view) if view.session.read().unwrap().prev_batch_token.is_none() => {
+                        self.console
+                            .log("The beginning of the room has been reached")
+                    }
+                    View::CS(view) => match (&view.more_msg_task, &view.history) {
+                        (None, _) => {
+                            view.more_msg_task = view
+                                .backend
+                                .get_prev_messages(view.more_msg_callback.clone());
+                        }
+                        (Some(_), Some(_)) => self.console.log("Already loading the full history"),
+                        (Some(_), None) => self.console.log("Already fetching previous messages"),
+                    },
+                    View::MV(view) => match view.ancestors_task {
+                        None => match &view.events_dag {
+                            Some(_) => {
+                                let input: web::html_element::InputElement = web::document()
+                                    .query_selector("#ancestors-id")
+                                    .expect("Couldn't get document element")
+                                    .expect("Couldn't get document element")
+                                    .try_into()
+                                    .unwrap();
+                                let from = vec![input.raw_value()];
+
+                                view.ancestors_task = view
+                                    .backend
+                                    .ancestors(view.ancestors_callback.clone(), &from);
+                            }
+                            None => self.console.log("There was no DAG"),
+                        },
+                        Some(_) => self.console.log("Already fetching ancestors"),
+                    },
+                    View::File(view) => match (view.backend.next_chunk(), &view.events_dag) {
+                        (Some(res), Some(dag)) => {
+                            let new_events = dag
+                                .write()
+                                .unwrap()
+                                .add_events(res.events, new_batch(BatchKind::File));
+                            self.vis.update_dag(dag.clone(), view_id, new_events);
+                        }
+                        (Some(_), None) => self.console.log("There was no DAG"),
+                        (None, _) => self.console.log(
+                            "Every events have been loaded, read more files to get more events",
+                        ),
+                    },
+                    View::Federation(view) => match (&view.backfill_task, &view.events_dag) {
+                        (None, Some(dag)) => {
+                            let from = dag.read().unwrap().earliest_events();
+
+                            view.backfill_task =
+                                view.backend.backfill(view.backfill_callback.clone(), &from);
+                        }
+                        (None, None) => self.console.log("There was no DAG"),
+                        (Some(_), _) => self.console.log("Already backfilling the room"),
+                    },
+                }
+            }
+            BkCommand::LoadHistory(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    let has_prev_batch = view.session.read().unwrap().prev_batch_token.is_some();
+
+                    if view.events_dag.is_none() {
+                        self.console.log("There was no DAG");
+                    } else if view.history.is_some() {
+                        self.console.log("Already loading the full history");
+                    } else if view.more_msg_task.is_some() {
+                        self.console.log("Already fetching previous messages");
+                    } else if !has_prev_batch {
+                        self.console
+                            .log("The beginning of the room has already been reached");
+                    } else {
+                        view.history = Some(HistoryLoading {
+                            depth_limit: self.history_depth_limit,
+                            events_limit: self.history_events_limit,
+                            pages: 0,
+                            events: 0,
+                            pending: DataSet::default(),
+                            pending_pages: 0,
+                        });
+                        view.more_msg_task = view
+                            .backend
+                            .get_prev_messages(view.more_msg_callback.clone());
+                    }
+                }
+            }
+            BkCommand::StopLoadingHistory(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    if let Some(mut history) = view.history.take() {
+                        view.more_msg_task = None; // Cancel the `/messages` request in progress
+
+                        self.console.log(&format!(
+                            "Stopped loading the history after {} events",
+                            history.events
+                        ));
+
+                        // Display the events received since the last update of the DAG
+                        if let Some(dag) = view.events_dag.clone() {
+                            self.vis.update_dag(dag, view_id, history.take_pending());
+                        }
+                    }
+                }
+            }
+            BkCommand::FetchState => {
+                let view_selection_input: web::html_element::InputElement = web::document()
+                    .query_selector("#targeted-view")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let view_id: ViewIndex = view_selection_input
+                    .raw_value()
+                    .parse()
+                    .expect("Failed to parse view_id");
+
+                let event_id_input: web::html_element::InputElement = web::document()
+                    .query_selector("#selected-event")
+                    .expect("Couldn't get document element")
+                    .expect("Couldn't get document element")
+                    .try_into()
+                    .unwrap();
+                let event_id = event_id_input.raw_value();
+
+                match &mut self.views[view_id] {
+                    View::CS(view) => match view.state_task {
+                        None => {
+                            view.state_task = view
+                                .backend
+                                .room_state(view.state_callback.clone(), &event_id)
+                        }
+                        Some(_) => self.console.log("Already fetching the state of the room"),
+                    },
+                    View::MV(view) => match view.state_task {
+                        None => {
+                            view.state_task =
+                                view.backend.state(view.state_callback.clone(), &event_id)
+                        }
+                        Some(_) => self.console.log("Already fetching the state of the room"),
+                    },
+                    View::File(_) => self
+                        .console
+                        .log("The state of the room can't be fetched from files"),
+                    View::Federation(view) => match view.state_ids_task {
+                        None => {
+                            view.state_ids_task = view
+                                .backend
+                                .state_ids(view.state_ids_callback.clone(), &event_id)
+                        }
+                        Some(_) => self.console.log("Already fetching the state of the room"),
+                    },
+                }
+            }
+            BkCommand::LeaveRoom(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    let peeks = view.session.read().unwrap().peeks();
+
+                    match view.leaving_room_task {
+                        // Leaving would change the membership of the user, which peeking avoids
+                        None if peeks => {
+                            self.console.log(
+                                "The room was only peeked at, disconnecting without leaving it",
+                            );
+                            self.link
+                                .send_back(move |_: ()| Msg::BkCmd(BkCommand::Disconnect(view_id)))
+                                .emit(());
+                        }
+                        None => {
+                            view.leaving_room_task =
+                                view.backend.leave_room(view.leaving_room_callback.clone())
+                        }
+                        Some(_) => self.console.log("Already leaving the room"),
+                    }
+                }
+            }
+            BkCommand::Disconnect(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => {
+                    let (connected, login_method) = {
+                        let session = view.session.read().unwrap();
+
+                        (session.access_token.is_some(), session.login_method)
+                    };
+
+                    if !connected {
+                        self.console.log("You were not connected");
+                    } else if login_method == LoginMethod::AccessToken
+                        || login_method == LoginMethod::Guest
+                    {
+                        // Logging out would invalidate the access token given by the user, which
+                        // may still be used elsewhere, so it is only forgotten. The guests can't
+                        // log out.
+                        self.link
+                            .send_back(move |_: ()| Msg::BkRes(BkResponse::Disconnected(view_id)))
+                            .emit(());
+                    } else {
+                        match view.disconnection_task {
+                            None => {
+                                view.disconnection_task =
+                                    view.backend.disconnect(view.disconnection_callback.clone())
+                            }
+                            Some(_) => self.console.log("Already disconnecting"),
+                        }
+                    }
+                }
+                View::MV(view) => {
+                    if view.session.read().unwrap().connected {
+                        self.console.log("Stopping the backend");
+
+                        match view.stop_task {
+                            None => view.stop_task = view.backend.stop(view.stop_callback.clone()),
+                            Some(_) => self.console.log("Already stopping the backend"),
+                        }
+                    } else {
+                        self.console.log("You were not connected");
+                    }
+                }
+                View::File(_) | View::Federation(_) => self
+                    .link
+                    .send_back(move |_: ()| Msg::BkRes(BkResponse::Disconnected(view_id)))
+                    .emit(()),
+            },
+            BkCommand::FetchMissingEvents(view_id) => match &mut self.views[view_id] {
+                View::CS(view) => match (&view.gap_filling, &view.events_dag) {
+                    (None, Some(dag)) => {
+                        let mut gap_filling = GapFilling::new(self.gap_depth_limit);
+
+                        match gap_filling.next_generation(&dag.read().unwrap()) {
+                            Some(event_id) => {
+                                view.missing_event_task = view
+                                    .backend
+                                    .missing_event(view.missing_event_callback.clone(), &event_id);
+                                view.gap_filling = Some(gap_filling);
+                            }
+                            None => self.console.log("There are no missing events"),
+                        }
+                    }
+                    (None, None) => self.console.log("There was no DAG"),
+                    (Some(_), _) => self.console.log("Already fetching the missing events"),
+                },
+                View::Federation(view) => match (&view.missing_events_task, &view.events_dag) {
+                    (None, Some(dag)) => {
+                        let dag = dag.read().unwrap();
+                        let orphans: Vec<String> = dag
+                            .orphan_events()
+                            .into_iter()
+                            .map(|orphan| orphan.id)
+                            .collect();
+
+                        if orphans.is_empty() {
+                            self.console.log("There are no missing events");
+                        } else {
+                            // The forward extremities are enough for the server to know which
+                            // events are already known
+                            view.missing_events_task = view.backend.missing_events(
+                                view.missing_events_callback.clone(),
+                                dag.latest_events(),
+                                orphans,
+                                dag.min_depth(),
+                            );
+                        }
+                    }
+                    (None, None) => self.console.log("There was no DAG"),
+                    (Some(_), _) => self.console.log("Already fetching the missing events"),
+                },
+                View::MV(_) | View::File(_) => {}
+            },
+            BkCommand::FetchServerKeys => {
+                let mut servers: Vec<String> = self
+                    .views
+                    .iter()
+                    .filter_map(|view| view.get_events_dag().clone())
+                    .flat_map(|dag| dag.read().unwrap().signing_servers())
+                    .collect();
+                servers.sort();
+                servers.dedup();
+
+                if servers.is_empty() {
+                    self.console
+                        .log("There are no events whose signatures could be checked yet");
+                }
+
+                // The tasks of the previous requests are dropped, which cancels them
+                self.keys_tasks.clear();
+
+                // The keys are requested once the address of the federation API of each server
+                // is known
+                for server in servers {
+                    let server_name = server.clone();
+                    let callback =
+                        self.link
+                            .send_back(move |res: Result<String, Error>| match res {
+                                Ok(address) => Msg::BkRes(BkResponse::ServerResolved(
+                                    server_name.clone(),
+                                    address,
+                                )),
+                                Err(_) => {
+                                    Msg::BkRes(BkResponse::ServerKeysRqFailed(server_name.clone()))
+                                }
+                            });
+
+                    if let Some(task) = self.keys_backend.resolve_server(callback, &server) {
+                        self.keys_tasks.push(task);
+                    }
+                }
+            }
+        }
+    }
+
+    fn process_bk_response(&mut self, res: BkResponse) {
+        match res {
+            BkResponse::Discovered(view_id, res) => {
+                let base_url = res.homeserver.base_url;
+
+                self.console
+                    .log(&format!("Homeserver found at {} via .well-known", base_url));
+                self.end_discovery(view_id, base_url);
+            }
+            BkResponse::DiscoveryFailed(view_id, error) => {
+                let server_name = self.views[view_id].get_room().0;
+
+                // Without `.well-known`, the homeserver is reached at the server name itself, so
+                // this isn't shown as a failure of the view
+                self.console.log(&format!(
+                    "No homeserver found via .well-known ({}), using the server name",
+                    error
+                ));
+                self.end_discovery(view_id, server_name);
+            }
+            BkResponse::AliasResolved(view_id, res) => {
+                match &mut self.views[view_id] {
+                    View::CS(view) => {
+                        view.alias_task = None;
+
+                        let mut session = view.session.write().unwrap();
+
+                        self.console.log(&format!(
+                            "{} resolved to {}, joinable via {}",
+                            session.room_id,
+                            res.room_id,
+                            res.servers.join(", ")
+                        ));
+
+                        session.room_id = res.room_id;
+                        session.via_servers = res.servers;
+                    }
+                    View::MV(view) => {
+                        view.alias_task = None;
+
+                        let mut session = view.session.write().unwrap();
+
+                        self.console
+                            .log(&format!("{} resolved to {}", session.room_id, res.room_id));
+
+                        session.room_id = res.room_id;
+                    }
+                    View::File(_) | View::Federation(_) => return,
+                }
+
+                // Go on with the connection which needed the ID of the room
+                self.link
+                    .send_back(move |_: ()| Msg::BkCmd(BkCommand::Connect(view_id)))
+                    .emit(());
+            }
+            BkResponse::AliasResolutionFailed(view_id, error) => {
+                match &mut self.views[view_id] {
+                    View::CS(view) => view.alias_task = None,
+                    View::MV(view) => view.alias_task = None,
+                    View::File(_) | View::Federation(_) => {}
+                }
+
+                self.report_error(view_id, "Failed to resolve the alias of the room", error);
+            }
+            BkResponse::LoginFlows(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.login_flows_task = None;
+
+                    let flows: Vec<String> = res.flows.into_iter().map(|flow| flow.typo).collect();
+                    let mut session = view.session.write().unwrap();
+
+                    self.console
+                        .log(&format!("Supported login flows: {}", flows.join(", ")));
+
+                    // Switch to a login method supported by the homeserver if needed. An access
+                    // token can always be used since it doesn't need to log in, and the guest
+                    // registration isn't a login flow.
+                    let supported = |method| match method {
+                        LoginMethod::Password => flows.iter().any(|f| f == "m.login.password"),
+                        LoginMethod::AccessToken | LoginMethod::Guest => true,
+                        LoginMethod::Token => flows.iter().any(|f| f == "m.login.token"),
+                    };
+                    if !supported(session.login_method) {
+                        session.login_method = [LoginMethod::Password, LoginMethod::Token]
+                            .iter()
+                            .cloned()
+                            .find(|method| supported(*method))
+                            .unwrap_or(LoginMethod::AccessToken);
+                    }
+
+                    session.login_flows = Some(flows);
+                }
+            }
+            BkResponse::Connected(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.connection_task = None;
+                    view.error = None;
+
+                    let mut session = view.session.write().unwrap();
+
+                    // Save the informations given by the homeserver when connecting to it. The access
+                    // token will be used for authenticating subsequent requests.
+                    session.user_id = res.user_id;
+                    session.access_token = Some(res.access_token);
+                    session.device_id = Some(res.device_id);
+
+                    self.console.log(&format!(
+                        "Connected with token: {} and as {}",
+                        session.access_token.as_ref().unwrap(),
+                        session.device_id.as_ref().unwrap()
+                    ));
+
+                    // Request the list of the rooms joined by the user as soon as we are connected
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::ListRooms(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::TokenChecked(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.connection_task = None;
+                    view.error = None;
+
+                    let mut session = view.session.write().unwrap();
+
+                    session.user_id = res.user_id;
+                    session.device_id = res.device_id;
+
+                    self.console.log(&format!(
+                        "Connected with the access token as {}",
+                        session.user_id
+                    ));
+
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::ListRooms(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::GuestRegistered(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.connection_task = None;
+                    view.error = None;
+
+                    let mut session = view.session.write().unwrap();
+
+                    session.user_id = res.user_id;
+                    session.access_token = Some(res.access_token);
+                    session.device_id = res.device_id;
+
+                    self.console
+                        .log(&format!("Connected as the guest {}", session.user_id));
+
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::ListRooms(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::RoomsList(view_id, res) => {
+                self.console.log("Looking up in joined rooms");
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.listing_rooms_task = None;
+
+                    if res
+                        .joined_rooms
+                        .contains(&view.session.read().unwrap().room_id)
+                    {
+                        // If the user is already in the room to observe, make the initial sync
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                            .emit(());
+                    } else {
+                        // Join the room if the user is not already in it
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::JoinRoom(view_id)))
+                            .emit(());
+                    }
+                }
+            }
+            BkResponse::RoomJoined(view_id) => {
+                self.console.log("Room joined!");
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.joining_room_task = None;
+
+                    // Make the initial sync as soon as the user has joined the room
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::FilterUploaded(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.filter_task = None;
+                    view.error = None;
+
+                    let mut session = view.session.write().unwrap();
+
+                    // Sync with the filter unless the view was disconnected in the meantime
+                    if session.access_token.is_some() {
+                        session.filter_id = Some(res.filter_id);
+
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                            .emit(());
+                    }
+                }
+            }
+            BkResponse::Synced(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.sync_task = None;
+                    view.error = None;
+
+                    if view.backoff.attempts() > 0 {
+                        self.console.log(&format!(
+                            "Reconnected after {} attempts",
+                            view.backoff.attempts()
+                        ));
+                        view.backoff.reset();
+                    }
+
+                    let mut session = view.session.write().unwrap();
+                    let next_batch_token = res.next_batch.clone(); // Save the next batch token to get new events later
+
+                    match session.next_batch_token {
+                        None => {
+                            // Initialise the prev batch token on the initial sync
+                            if let Some(room) = res.rooms.join.get(&session.room_id) {
+                                session.prev_batch_token = room.timeline.prev_batch.clone();
+                            }
+
+                            // Create a new DAG if it is the initial sync
+                            if let Some(mut dag) = model::dag::RoomEvents::from_sync_response(
+                                &session.room_id,
+                                &session.server_name,
+                                &self.fields_choice.fields,
+                                res,
+                                now(),
+                            ) {
+                                dag.set_server_keys(&self.server_keys);
+                                view.events_dag = Some(Arc::new(RwLock::new(dag)));
+                            }
+
+                            match view.events_dag.clone() {
+                                Some(dag) => {
+                                    // Display the DAG with VisJs if it has been successfully built
+                                    if !self.vis.is_active() {
+                                        self.vis.init(
+                                            "#dag-vis",
+                                            "#targeted-view",
+                                            "#more-ev-target",
+                                            "#selected-event",
+                                            "#display-body-target",
+                                            "#ancestors-id",
+                                            "#ancestors-target",
+                                        );
+                                    }
+
+                                    self.vis.add_dag(dag, view_id);
+                                }
+                                None => self.console.log("Failed to build the DAG"),
+                            }
+                        }
+                        Some(_) => match view.events_dag.clone() {
+                            // Add new events to the DAG
+                            Some(dag) => {
+                                if let Some(room) = res.rooms.join.get(&session.room_id) {
+                                    let new_events = dag.write().unwrap().add_events(
+                                        room.timeline.events.clone(),
+                                        new_batch(BatchKind::Sync),
+                                    );
+                                    self.vis.update_dag(dag, view_id, new_events);
+                                }
+                            }
+                            None => self.console.log("There is no DAG"),
+                        },
+                    }
+
+                    session.next_batch_token = Some(next_batch_token);
+
+                    // Request for futur new events
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::RoomPeeked(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.sync_task = None;
+                    view.error = None;
+                    view.backoff.reset();
+
+                    let end = match res.messages {
+                        Some(messages) => messages.end,
+                        None => {
+                            self.console
+                                .log("The timeline of the room can't be peeked at");
+                            return;
+                        }
+                    };
+
+                    let server_name = {
+                        let mut session = view.session.write().unwrap();
+
+                        // The earlier events are retrieved backwards from the latest events of
+                        // the room and the new ones forwards
+                        session.prev_batch_token = Some(end.clone());
+                        session.next_batch_token = Some(end);
+
+                        session.server_name.clone()
+                    };
+
+                    // The DAG is empty until the response to the first `/messages` request
+                    let mut dag = model::dag::RoomEvents::from_room_state(
+                        &server_name,
+                        &self.fields_choice.fields,
+                        &res.state,
+                    );
+                    dag.set_server_keys(&self.server_keys);
+
+                    let dag = Arc::new(RwLock::new(dag));
+                    view.events_dag = Some(dag.clone());
+
+                    if !self.vis.is_active() {
+                        self.vis.init(
+                            "#dag-vis",
+                            "#targeted-view",
+                            "#more-ev-target",
+                            "#selected-event",
+                            "#display-body-target",
+                            "#ancestors-id",
+                            "#ancestors-target",
+                        );
+                    }
+
+                    self.vis.add_dag(dag, view_id);
+
+                    view.more_msg_task = view
+                        .backend
+                        .get_prev_messages(view.more_msg_callback.clone());
+                    view.sync_retry_task = Some(
+                        self.timeout.spawn(
+                            std::time::Duration::new(5, 0),
+                            self.link
+                                .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id))),
+                        ),
+                    );
+                }
+            }
+            BkResponse::NewMsgGot(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.sync_task = None;
+                    view.error = None;
+
+                    if view.backoff.attempts() > 0 {
+                        self.console.log(&format!(
+                            "Reconnected after {} attempts",
+                            view.backoff.attempts()
+                        ));
+                        view.backoff.reset();
+                    }
+
+                    // Without new events, the next request starts from the same point
+                    if !res.chunk.is_empty() {
+                        if let Some(end) = res.end {
+                            view.session.write().unwrap().next_batch_token = Some(end);
+                        }
+
+                        match view.events_dag.clone() {
+                            Some(dag) => {
+                                let new_events = dag
+                                    .write()
+                                    .unwrap()
+                                    .add_events(res.chunk, new_batch(BatchKind::Messages));
+
+                                self.vis.update_dag(dag, view_id, new_events);
+                            }
+                            None => self.console.log("There is no DAG"),
+                        }
+                    }
+
+                    // Poll the new events unless the view was disconnected in the meantime
+                    if view.session.read().unwrap().access_token.is_some() {
+                        view.sync_retry_task = Some(
+                            self.timeout.spawn(
+                                std::time::Duration::new(5, 0),
+                                self.link
+                                    .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id))),
+                            ),
+                        );
+                    }
+                }
+            }
+            BkResponse::MsgGot(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.more_msg_task = None;
+
+                    // Save the prev batch token for the next `/messages` request. There is none
+                    // once the beginning of the room has been reached.
+                    let end_reached = res.chunk.is_empty() || res.end.is_none();
+                    view.session.write().unwrap().prev_batch_token = res.end;
+
+                    let dag = match view.events_dag.clone() {
+                        Some(dag) => dag,
+                        None => {
+                            self.console.log("There was no DAG");
+                            return;
+                        }
+                    };
+
+                    // Add earlier event to the DAG and display them
+                    let count = res.chunk.len();
+                    let new_events = dag
+                        .write()
+                        .unwrap()
+                        .add_events(res.chunk, new_batch(BatchKind::Messages));
+
+                    let history = match &mut view.history {
+                        Some(history) => history,
+                        None => {
+                            self.vis.update_dag(dag, view_id, new_events);
+                            return;
+                        }
+                    };
+
+                    history.pages += 1;
+                    history.events += count;
+                    history.pending.append(new_events);
+                    history.pending_pages += 1;
+
+                    let stop_reason = {
+                        let dag = dag.read().unwrap();
+
+                        if dag.has_create_event() {
+                            Some("the creation of the room")
+                        } else if end_reached {
+                            Some("the beginning of the available history")
+                        } else if history.depth_limit.map_or(false, |d| dag.min_depth() <= d) {
+                            Some("the depth limit")
+                        } else if history.events_limit.map_or(false, |n| history.events >= n) {
+                            Some("the events limit")
+                        } else {
+                            None
+                        }
+                    };
+
+                    if stop_reason.is_some() || history.pending_pages >= HISTORY_BATCH_PAGES {
+                        self.vis
+                            .update_dag(dag.clone(), view_id, history.take_pending());
+                    }
+
+                    match stop_reason {
+                        Some(reason) => {
+                            self.console.log(&format!(
+                                "Full history loaded up to {}: {} events in {} requests",
+                                reason, history.events, history.pages
+                            ));
+
+                            view.history = None;
+                        }
+                        None => {
+                            view.more_msg_task = view
+                                .backend
+                                .get_prev_messages(view.more_msg_callback.clone())
+                        }
+                    }
+                }
+            }
+            BkResponse::MissingEventGot(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.missing_event_task = None;
+                    view.error = None;
+
+                    match &mut view.gap_filling {
+                        Some(gap_filling) => {
+                            gap_filling.events.push(res.event);
+                            gap_filling.fetched += 1;
+                        }
+                        None => return,
+                    }
+                }
+
+                self.fetch_next_missing_event(view_id);
+            }
+            BkResponse::StateFetched(view_id, res) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.state_task = None;
+
+                    let event_bodies = res.state.clone();
+
+                    let object = json!({ "events": event_bodies });
+
+                    self.room_state = match serde_json::to_string_pretty(&object) {
+                        Ok(state) => Some(state),
+                        Err(_) => None,
+                    }
+                }
+            }
+            BkResponse::RoomLeft(view_id) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.leaving_room_task = None;
+
+                    self.console.log("Room left!");
+
+                    // Disconnect as soon as we leave the room
+                    self.link
+                        .send_back(move |_: ()| Msg::BkCmd(BkCommand::Disconnect(view_id)))
+                        .emit(());
+                }
+            }
+            BkResponse::Disconnected(view_id) => {
+                match &mut self.views[view_id] {
+                    View::CS(view) => {
+                        self.console.log("Disconnected");
+
+                        view.sync_task = None; // If a `/sync` request was in progress, cancel it
+                        view.sync_retry_task = None;
+                        view.backoff.reset();
+                        view.disconnection_task = None;
+                        view.more_msg_task = None;
+                        view.history = None;
+                        view.missing_event_task = None;
+                        view.gap_filling = None;
+                        view.filter_task = None;
+                        view.inline_filter = false;
+                        view.error = None;
+
+                        let mut session = view.session.write().unwrap();
+
+                        // Erase the current session data so they won't be erroneously used if the user
+                        // logs in again
+                        session.access_token = None;
+                        session.device_id = None;
+                        session.filter_id = None;
+                        session.next_batch_token = None;
+                        session.prev_batch_token = None;
+
+                        view.events_dag = None;
+                        self.vis.remove_dag(view_id);
+
+                        self.event_body = None;
+                        self.event_hash_check = None;
+                        self.event_signature_check = None;
+                        self.room_state = None;
+                        self.local_room_state = None;
+
+                        self.vis.clear_diff();
+                        self.dag_diff = None;
+                    }
+                    View::File(view) => {
+                        self.console.log("Files closed");
+
+                        view.reading_tasks.clear();
+
+                        let mut session = view.session.write().unwrap();
+
+                        session.files.clear();
+                        session.pending_events.clear();
+
+                        if view.events_dag.take().is_some() {
+                            self.vis.remove_dag(view_id);
+                        }
+
+                        self.event_body = None;
+                        self.event_hash_check = None;
+                        self.event_signature_check = None;
+                        self.room_state = None;
+                        self.local_room_state = None;
+
+                        self.vis.clear_diff();
+                        self.dag_diff = None;
+                    }
+                    View::Federation(view) => {
+                        self.console.log("Observation stopped");
+
+                        view.event_task = None;
+                        view.backfill_task = None;
+                        view.missing_events_task = None;
+                        view.state_ids_task = None;
+
+                        view.session.write().unwrap().connected = false;
+
+                        if view.events_dag.take().is_some() {
+                            self.vis.remove_dag(view_id);
+                        }
+
+                        self.event_body = None;
+                        self.event_hash_check = None;
+                        self.event_signature_check = None;
+                        self.room_state = None;
+                        self.local_room_state = None;
+
+                        self.vis.clear_diff();
+                        self.dag_diff = None;
+                    }
+                    View::MV(view) => {
+                        self.console.log("Backend stopped");
+
+                        view.stop_task = None;
+
+                        let mut session = view.session.write().unwrap();
+
+                        session.connected = false;
+                        view.descendants_timeout_task = None;
+                        view.backoff.reset();
+                        view.error = None;
+                        view.events_dag = None;
+                        self.vis.remove_dag(view_id);
+
+                        self.event_body = None;
+                        self.event_hash_check = None;
+                        self.event_signature_check = None;
+                        self.room_state = None;
+                        self.local_room_state = None;
+
+                        self.vis.clear_diff();
+                        self.dag_diff = None;
+                    }
+                }
+            }
+
+            BkResponse::LoginFlowsFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.login_flows_task = None;
+                }
+
+                self.report_error(view_id, "Failed to get the login flows", error);
+            }
+            BkResponse::TokenCheckFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.connection_task = None;
+                    view.session.write().unwrap().access_token = None;
+                }
+
+                self.report_error(view_id, "The access token is invalid", error);
+            }
+            BkResponse::ConnectionFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.connection_task = None;
+                }
+
+                self.report_error(view_id, "Connection failed", error);
+            }
+            BkResponse::ListingRoomsFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.listing_rooms_task = None;
+                }
+
+                self.report_error(view_id, "Failed to get the list of joined rooms", error);
+            }
+            BkResponse::JoiningRoomFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.joining_room_task = None;
+                }
+
+                self.report_error(view_id, "Failed to join the room", error);
+            }
+            BkResponse::FilterUploadFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.filter_task = None;
+
+                    // Sync anyway, with the filter given in each request
+                    if view.session.read().unwrap().access_token.is_some() {
+                        view.inline_filter = true;
+
+                        self.link
+                            .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id)))
+                            .emit(());
+                    }
+                }
+
+                self.report_error(view_id, "Could not upload the filter", error);
+            }
+            BkResponse::SyncFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.sync_task = None;
+
+                    // Keep trying to sync unless the view was disconnected in the meantime
+                    if view.session.read().unwrap().access_token.is_some() {
+                        match view.backoff.retry(&error, random()) {
+                            Some(delay) => {
+                                self.console.log(&format!(
+                                    "Reconnecting in {} ms (attempt {})",
+                                    delay.as_millis(),
+                                    view.backoff.attempts()
+                                ));
+
+                                view.sync_retry_task = Some(self.timeout.spawn(
+                                    delay,
+                                    self.link.send_back(move |_: ()| {
+                                        Msg::BkCmd(BkCommand::Sync(view_id))
+                                    }),
+                                ));
+                            }
+                            None => {
+                                self.console.log("Giving up syncing");
+                                view.backoff.reset();
+                            }
+                        }
+                    }
+                }
+
+                self.report_error(view_id, "Could not sync", error);
+            }
+            BkResponse::MoreMsgFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.more_msg_task = None;
+
+                    // The loading of the history stops with the first failure, its events are
+                    // still displayed
+                    if let (Some(mut history), Some(dag)) =
+                        (view.history.take(), view.events_dag.clone())
+                    {
+                        self.vis.update_dag(dag, view_id, history.take_pending());
+                    }
+                }
+
+                self.report_error(view_id, "Could not retrieve previous messages", error);
+            }
+            BkResponse::MissingEventFailed(view_id, error) => {
+                // An event the homeserver refuses to give, e.g. because of the history visibility
+                // of the room, is skipped, but the filling stops if the homeserver can't be
+                // reached or the session has ended
+                let skipped = !error.is_transient() && !error.is_logged_out();
+
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.missing_event_task = None;
+
+                    if !skipped {
+                        if let (Some(gap_filling), Some(dag)) =
+                            (view.gap_filling.take(), view.events_dag.clone())
+                        {
+                            let new_events = dag
+                                .write()
+                                .unwrap()
+                                .add_events(gap_filling.events, new_batch(BatchKind::Context));
+
+                            self.vis.update_dag(dag, view_id, new_events);
+                        }
+                    }
+                }
+
+                self.report_error(view_id, "Could not retrieve a missing event", error);
+
+                if skipped {
+                    self.fetch_next_missing_event(view_id);
+                }
+            }
+            BkResponse::FetchStateFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.state_task = None;
+                }
+
+                self.report_error(view_id, "Could not fetch the state of the room", error);
+            }
+            BkResponse::LeavingRoomFailed(view_id, error) => {
+                if let View::CS(view) = &mut self.views[view_id] {
+                    view.leaving_room_task = None;
+                }
+
+                self.report_error(view_id, "Failed to leave the room", error);
+            }
+            BkResponse::DisconnectionFailed(view_id, error) => {
+                match &mut self.views[view_id] {
+                    View::CS(view) => view.disconnection_task = None,
+                    View::MV(view) => view.stop_task = None,
+                    View::File(_) | View::Federation(_) => {}
+                }
+
+                if error.is_logged_out() {
+                    // The session has already ended, e.g. because the access token expired, so
+                    // there is nothing left to log out from
+                    self.link
+                        .send_back(move |_: ()| Msg::BkRes(BkResponse::Disconnected(view_id)))
+                        .emit(());
+                } else {
+                    self.report_error(view_id, "Could not disconnect", error);
+                }
+            }
+
+            BkResponse::DeepestEvents(view_id, res) => {
+                if let View::MV(view) = &mut self.views[view_id] {
+                    view.deepest_task = None;
+                    view.error = None;
+
+                    let mut session = view.session.write().unwrap();
+                    session.connected = true;
+
+                    let mut dag = model::dag::RoomEvents::from_deepest_events(
+                        &session.server_name,
+                        &self.fields_choice.fields,
+                        res,
+                        new_batch(BatchKind::Deepest),
+                    );
+                    dag.set_server_keys(&self.server_keys);
+
+                    view.events_dag = Some(Arc::new(RwLock::new(dag)));
+
+                    match view.events_dag.clone() {
+                        Some(dag) => {
+                            if !self.vis.is_active() {
+                                self.vis.init(
+                                    "#dag-vis",
+                                    "#targeted-view",
+                                    "#more-ev-target",
+                                    "#selected-event",
+                                    "#display-body-target",
+                                    "#ancestors-id",
+                                    "#ancestors-target",
+                                );
+                            }
+
+                            self.vis.add_dag(dag, view_id);
+                        }
+                        None => self.console.log("Failed to build the DAG"),
+                    }
+
+                    view.descendants_timeout_task = Some(
+                        self.timeout.spawn(
+                            std::time::Duration::new(5, 0),
+                            self.link
+                                .send_back(move |_: ()| Msg::BkCmd(BkCommand::Sync(view_id))),
+                        ),
+                    );
+                }
+            }
+            BkResponse::Ancestors(view_id, res) => {
+                if let View::MV(view) = &mut self.views[view_id] {
+                    view.ancestors_task = None;
+
+                    match view.events_dag.clone() {
+                        // Add ancestors to the DAG and display them
+                        Some(dag) => {
+                            let new_events = dag
+                                .write()
+                                .unwrap()
+                                .add_events(res.events, new_batch(BatchKind::Ancestors));
+
+                            self.vis.update_dag(dag, view_id, new_events);
+                        }
+                        None => self.console.log("There was no DAG"),
+                    }
+                }
+            }
+            BkResponse::Descendants(view_id, res) => {
+                if let View::MV(view) = &mut self.views[view_id] {
+                    view.descendants_task = None;
+                    view.error = None;
+
+                    if view.backoff.attempts() > 0 {
+                        self.console.log(&format!(
+                            "Reconnected after {} attempts",
+                            view.backoff.attempts()
+                        ));
+                        view.backoff.reset();
+                    }
+
+                    match view.events_dag.clone() {
+                        Some(dag) => {
+                            let new_events = dag
+                                .write()
+                                .unwrap()
+                                .add_events(res.events, new_batch(BatchKind::Descendants));
+
+                            self.vis.update_dag(dag, view_id, new_events);
+
+                            if view.session.read().unwrap().connected {
+                                view.descendants_timeout_task = Some(self.timeout.spawn(
+                                    std::time::Duration::new(5, 0),
+                                    self.link.send_back(move |_: ()| {
+                                        Msg::BkCmd(BkCommand::Sync(view_id))
+                                    }),
+                                ));
+                            }
+                        }
+                        None => self.console.log("There was no DAG"),
+                    }
+                }
+            }
+            BkResponse::State(view_id, res) => {
+                if let View::MV(view) = &mut self.views[view_id] {
+                    view.state_task = None;
+
+                    self.room_state = match serde_json::to_string_pretty(&res) {
+                        Ok(state) => Some(state),
+                        Err(_) => None,
+                    };
+                }
+            }
+
+            BkResponse::DeepestRqFailed(view_id, error) => {
+                if let View::MV(view) = &mut self.views[view_id] {
+                    view.deepest_task = None;
+                }
+
+                self.report_error(
+                    view_id,
+                    "Could not retrieve the room's deepest events",
+                    error,
+                );
+            }
+            BkResponse::AncestorsRqFailed(view_id, error) => {
+                if let View::MV(view) = &mut self.views[view_id] {
+                    view.ancestors_task = None;
+                }
+
+                self.report_error(view_id, "Could not retrieve the events' ancestors", error);
+            }
+            BkResponse::DescendantsRqFailed(view_id, error) => {
+                if let View::MV(view) = &mut self.views[view_id] {
+                    view.descendants_task = None;
+
+                    // Keep polling unless the backend was stopped in the meantime
+                    if view.session.read().unwrap().connected {
+                        match view.backoff.retry(&error, random()) {
+                            Some(delay) => {
+                                self.console.log(&format!(
+                                    "Reconnecting in {} ms (attempt {})",
+                                    delay.as_millis(),
+                                    view.backoff.attempts()
+                                ));
+
+                                view.descendants_timeout_task = Some(self.timeout.spawn(
+                                    delay,
+                                    self.link.send_back(move |_: ()| {
+                                        Msg::BkCmd(BkCommand::Sync(view_id))
+                                    }),
+                                ));
+                            }
+                            None => {
+                                self.console.log("Giving up polling the descendants");
+                                view.backoff.reset();
+                            }
+                        }
+                    }
+                }
+
+                self.report_error(view_id, "Could not retrieve the events' descendants", error);
+            }
+            BkResponse::StateRqFailed(view_id, error) => {
+                if let View::MV(view) = &mut self.views[view_id] {
+                    view.state_task = None;
+                }
+
+                self.report_error(view_id, "Could not fetch the state of the room", error);
+            }
+            BkResponse::FileRead(view_id, count) => {
+                self.console.log(&format!("{} events read", count));
+
+                if let View::File(view) = &mut self.views[view_id] {
+                    // Build the DAG with the latest events if it is the first file read
+                    if view.events_dag.is_none() {
+                        if let Some(res) = view.backend.next_chunk() {
+                            let server_name = view.session.read().unwrap().server_name.clone();
+
+                            let mut dag = model::dag::RoomEvents::from_deepest_events(
+                                &server_name,
+                                &self.fields_choice.fields,
+                                res,
+                                new_batch(BatchKind::File),
+                            );
+                            dag.set_server_keys(&self.server_keys);
+
+                            let dag = Arc::new(RwLock::new(dag));
+                            view.events_dag = Some(dag.clone());
+
+                            if !self.vis.is_active() {
+                                self.vis.init(
+                                    "#dag-vis",
+                                    "#targeted-view",
+                                    "#more-ev-target",
+                                    "#selected-event",
+                                    "#display-body-target",
+                                    "#ancestors-id",
+                                    "#ancestors-target",
+                                );
+                            }
+
+                            self.vis.add_dag(dag, view_id);
+                        }
+                    }
+                }
+            }
+            BkResponse::FileReadFailed(view_id) => {
+                self.console.log("Failed to read the events file");
+
+                if let View::File(view) = &mut self.views[view_id] {
+                    view.reading_tasks.clear();
+                }
+            }
+            BkResponse::FederationEvent(view_id, res) => {
+                if let View::Federation(view) = &mut self.views[view_id] {
+                    view.event_task = None;
+
+                    let (server_name, event_id) = {
+                        let mut session = view.session.write().unwrap();
+                        session.connected = true;
+
+                        (session.server_name.clone(), session.event_id.clone())
+                    };
+
+                    let mut dag = model::dag::RoomEvents::from_deepest_events(
+                        &server_name,
+                        &self.fields_choice.fields,
+                        EventsResponse { events: res.pdus },
+                        new_batch(BatchKind::Event),
+                    );
+                    dag.set_server_keys(&self.server_keys);
+
+                    let dag = Arc::new(RwLock::new(dag));
+                    view.events_dag = Some(dag.clone());
+
+                    if !self.vis.is_active() {
+                        self.vis.init(
+                            "#dag-vis",
+                            "#targeted-view",
+                            "#more-ev-target",
+                            "#selected-event",
+                            "#display-body-target",
+                            "#ancestors-id",
+                            "#ancestors-target",
+                        );
+                    }
+
+                    self.vis.add_dag(dag, view_id);
+
+                    // Get the events preceding the first event right away, the session must not
+                    // be locked anymore as the backend reads it
+                    let from = vec![event_id];
+                    view.backfill_task =
+                        view.backend.backfill(view.backfill_callback.clone(), &from);
+                }
+            }
+            BkResponse::Backfilled(view_id, res) => {
+                if let View::Federation(view) = &mut self.views[view_id] {
+                    view.backfill_task = None;
+
+                    match view.events_dag.clone() {
+                        Some(dag) => {
+                            let new_events = dag
+                                .write()
+                                .unwrap()
+                                .add_events(res.pdus, new_batch(BatchKind::Backfill));
+
+                            self.vis.update_dag(dag, view_id, new_events);
+                        }
+                        None => self.console.log("There was no DAG"),
+                    }
+                }
+            }
+            BkResponse::MissingEvents(view_id, res) => {
+                if let View::Federation(view) = &mut self.views[view_id] {
+                    view.missing_events_task = None;
+
+                    match view.events_dag.clone() {
+                        Some(dag) => {
+                            let new_events = dag
+                                .write()
+                                .unwrap()
+                                .add_events(res.events, new_batch(BatchKind::MissingEvents));
+
+                            self.vis.update_dag(dag, view_id, new_events);
+                        }
+                        None => self.console.log("There was no DAG"),
+                    }
+                }
+            }
+            BkResponse::StateIds(view_id, res) => {
+                if let View::Federation(view) = &mut self.views[view_id] {
+                    view.state_ids_task = None;
+
+                    self.room_state = match serde_json::to_string_pretty(&res) {
+                        Ok(state) => Some(state),
+                        Err(_) => None,
+                    };
+                }
+            }
+
+            BkResponse::EventRqFailed(view_id) => {
+                self.console.log("Could not retrieve the event");
+
+                if let View::Federation(view) = &mut self.views[view_id] {
+                    view.event_task = None;
+                }
+            }
+            BkResponse::BackfillRqFailed(view_id) => {
+                self.console.log("Could not backfill the room");
+
+                if let View::Federation(view) = &mut self.views[view_id] {
+                    view.backfill_task = None;
+                }
+            }
+            BkResponse::MissingEventsRqFailed(view_id) => {
+                self.console.log("Could not retrieve the missing events");
+
+                if let View::Federation(view) = &mut self.views[view_id] {
+                    view.missing_events_task = None;
+                }
+            }
+            BkResponse::StateIdsRqFailed(view_id) => {
+                self.console.log("Could not fetch the state of the room");
+
+                if let View::Federation(view) = &mut self.views[view_id] {
+                    view.state_ids_task = None;
+                }
+            }
+
+            BkResponse::ServerResolved(server_name, address) => {
+                let callback =
+                    self.link
+                        .send_back(move |res: Result<JsonValue, Error>| match res {
+                            Ok(res) => Msg::BkRes(BkResponse::ServerKeys(res)),
+                            Err(_) => {
+                                Msg::BkRes(BkResponse::ServerKeysRqFailed(server_name.clone()))
+                            }
+                        });
+
+                if let Some(task) = self.keys_backend.server_keys(callback, &address) {
+                    self.keys_tasks.push(task);
+                }
+            }
+            BkResponse::ServerKeys(res) => self.add_server_keys(&res),
+            BkResponse::ServerKeysRqFailed(server_name) => {
+                self.console.log(&format!(
+                    "Could not fetch the signing keys of {}",
+                    server_name
+                ));
+            }
+        }
+    }
+
+    // Rebuilds the DAG saved in `snapshot` and displays it in the current view, in place of the
+    // DAG the view had. The view must not be connected to a homeserver.
+    fn load_snapshot(&mut self, snapshot: Snapshot) {
+        let view_id = self.view_idx;
+
+        let connected = match &self.views[view_id] {
+            View::File(_) => false,
+            view => view.is_connected(),
+        };
+
+        if connected {
+            self.console
+                .log("Disconnect the view before loading a snapshot in it");
+            return;
+        }
+
+        if snapshot.backend != *self.bk_type.read().unwrap() {
+            self.console
+                .log("The snapshot has been taken with the other backend");
+        }
+
+        let fetched_at: String = js! { return new Date(@{snapshot.fetched_at}).toISOString(); }
+            .try_into()
+            .unwrap();
+        self.console.log(&format!(
+            "Loading the snapshot of {} fetched from {} until {}",
+            snapshot.room_id, snapshot.server_name, fetched_at
+        ));
+
+        let room_id = snapshot.room_id.clone();
+        let mut dag = RoomEvents::from_snapshot(&self.fields_choice.fields, snapshot, now());
+        dag.set_server_keys(&self.server_keys);
+
+        let dag = Arc::new(RwLock::new(dag));
+        let had_dag = self.views[view_id].get_events_dag().is_some();
+
+        match &mut self.views[view_id] {
+            View::CS(view) => {
+                let mut session = view.session.write().unwrap();
+
+                session.server_name = dag.read().unwrap().server_name().to_string();
+                session.room_id = room_id;
+                view.events_dag = Some(dag.clone());
+            }
+            View::MV(view) => {
+                let mut session = view.session.write().unwrap();
+
+                session.server_name = dag.read().unwrap().server_name().to_string();
+                session.room_id = room_id;
+                view.events_dag = Some(dag.clone());
+            }
+            View::File(view) => {
+                let mut session = view.session.write().unwrap();
+
+                session.server_name = dag.read().unwrap().server_name().to_string();
+                session.room_id = room_id;
+                view.events_dag = Some(dag.clone());
+            }
+            View::Federation(view) => {
+                let mut session = view.session.write().unwrap();
+
+                session.server_name = dag.read().unwrap().server_name().to_string();
+                session.room_id = room_id;
+                view.events_dag = Some(dag.clone());
+            }
+        }
+
+        if !self.vis.is_active() {
+            self.vis.init(
+                "#dag-vis",
+                "#targeted-view",
+                "#more-ev-target",
+                "#selected-event",
+                "#display-body-target",
+                "#ancestors-id",
+                "#ancestors-target",
+            );
+        } else if had_dag {
+            self.vis.remove_dag(view_id);
+        }
+
+        self.vis.add_dag(dag, view_id);
+    }
+
+    // Computes again the differences between the compared views once the DAG of one of them
+    // changed, or clears them if one of the views has lost its DAG. This waits for the end of
+    // the replay of a compared view, whose DAG is only partially displayed until then.
+    fn refresh_dag_diff(&mut self) {
+        let (view_a, view_b) = match self.vis.outdated_diff() {
+            Some(views) => views,
+            None => return,
+        };
+
+        if self.vis.is_replaying(view_a) || self.vis.is_replaying(view_b) {
+            return;
+        }
+
+        match (
+            self.views[view_a].get_events_dag(),
+            self.views[view_b].get_events_dag(),
+        ) {
+            (Some(dag_a), Some(dag_b)) => {
+                let diff = DagDiff::new(&dag_a.read().unwrap(), &dag_b.read().unwrap());
+
+                self.vis.show_diff(view_a, view_b, &diff);
+                self.dag_diff = Some(diff);
+            }
+            _ => {
+                self.vis.clear_diff();
+                self.dag_diff = None;
+            }
+        }
+    }
+
+    // Whether a replay is running. The replay is dropped if its view has lost its DAG in the
+    // meantime, e.g. after a disconnection.
+    fn replay_running(&mut self) -> bool {
+        match &self.replay {
+            Some(replay) if self.vis.is_replaying(replay.view_id) => true,
+            Some(_) => {
+                self.replay = None;
+                false
+            }
+            None => false,
+        }
+    }
+
+    // Displays the first `position` steps of the replay, by adding the following steps to the
+    // ones displayed if it goes forward or by displaying the steps again from the beginning.
+    fn seek_replay(&mut self, position: usize) {
+        if !self.replay_running() {
+            return;
+        }
+
+        if let Some(replay) = &mut self.replay {
+            let position = position.min(replay.steps.len());
+
+            if position >= replay.position {
+                self.vis.show_replay_steps(
+                    replay.view_id,
+                    &replay.steps[replay.position..position],
+                    false,
+                );
+            } else {
+                self.vis
+                    .show_replay_steps(replay.view_id, &replay.steps[..position], true);
+            }
+
+            replay.position = position;
+        }
+    }
+
+    // Schedules the next step of the replay according to the chosen speed.
+    fn schedule_replay_tick(&mut self) {
+        if let Some(replay) = &mut self.replay {
+            let delay = std::time::Duration::from_millis(1000 / u64::from(self.replay_speed));
+
+            replay.timeout_task = Some(
+                self.timeout.spawn(
+                    delay,
+                    self.link
+                        .send_back(|_: ()| Msg::UICmd(UICommand::ReplayTick)),
+                ),
+            );
+        }
+    }
+
+    // Stops the replay, if any, and displays the whole DAG of its view again.
+    fn stop_replay(&mut self) {
+        if let Some(replay) = self.replay.take() {
+            match self.views[replay.view_id].get_events_dag() {
+                Some(dag) => self.vis.stop_replay(dag.clone(), replay.view_id),
+                None if self.vis.is_replaying(replay.view_id) => {
+                    self.vis.remove_dag(replay.view_id)
+                }
+                None => {}
+            }
+        }
+    }
+
+    // Saves the base URL of the homeserver discovered for the view `view_id` and then goes on with
+    // the connection which needed it.
+    fn end_discovery(&mut self, view_id: ViewIndex, base_url: String) {
+        match &mut self.views[view_id] {
+            View::CS(view) => {
+                view.discovery_task = None;
+                view.session.write().unwrap().discovered_base_url = Some(base_url);
+            }
+            View::MV(view) => {
+                view.discovery_task = None;
+                view.session.write().unwrap().discovered_base_url = Some(base_url);
+            }
+            View::File(_) | View::Federation(_) => return,
+        }
+
+        self.link
+            .send_back(move |_: ()| Msg::BkCmd(BkCommand::Connect(view_id)))
+            .emit(());
+    }
+
+    // Requests the next missing event to fill the gaps of the DAG of the CS view `view_id`. Once
+    // every event of the current generation has been requested, they are added to the DAG and the
+    // filling moves on to the next generation, or stops if there is none.
+    fn fetch_next_missing_event(&mut self, view_id: ViewIndex) {
+        if let View::CS(view) = &mut self.views[view_id] {
+            let (gap_filling, dag) = match (&mut view.gap_filling, view.events_dag.clone()) {
+                (Some(gap_filling), Some(dag)) => (gap_filling, dag),
+                _ => return,
+            };
+
+            let next_event = match gap_filling.next_event() {
+                Some(event_id) => Some(event_id),
+                None => {
+                    let events = std::mem::replace(&mut gap_filling.events, Vec::new());
+                    let new_events = dag
+                        .write()
+                        .unwrap()
+                        .add_events(events, new_batch(BatchKind::Context));
+
+                    self.vis.update_dag(dag.clone(), view_id, new_events);
+
+                    gap_filling.next_generation(&dag.read().unwrap())
+                }
+            };
+
+            match next_event {
+                Some(event_id) => {
+                    view.missing_event_task = view
+                        .backend
+                        .missing_event(view.missing_event_callback.clone(), &event_id)
+                }
+                None => {
+                    let remaining = dag.read().unwrap().missing_prev_events().len();
+
+                    self.console.log(&format!(
+                        "Fetched {} missing events over {} generations, {} events are still missing",
+                        gap_filling.fetched, gap_filling.depth, remaining
+                    ));
+
+                    view.gap_filling = None;
+                }
+            }
+        }
+    }
+
+    // Applies `edit` to the filter of the current view, which has to be uploaded again before the
+    // next `/sync` request.
+    fn edit_filter<F: FnOnce(&mut FilterSettings)>(&mut self, edit: F) {
+        if let View::CS(view) = &mut self.views[self.view_idx] {
+            {
+                let mut session = view.session.write().unwrap();
+
+                edit(&mut session.filter);
+                session.filter_id = None;
+            }
+
+            view.inline_filter = false;
+
+            // The response to the upload of the previous filter would store an outdated filter ID,
+            // so this upload is cancelled and the edited filter is uploaded in its place
+            if view.filter_task.take().is_some() {
+                view.filter_task = view.backend.upload_filter(view.filter_callback.clone());
+            }
+        }
+    }
+
+    // Logs the failure `error` of a request of the view `view_id` after `msg` and shows it in the
+    // view until one of its requests succeeds.
+    fn report_error(&mut self, view_id: ViewIndex, msg: &str, error: MatrixError) {
+        self.console.log(&format!("{}: {}", msg, error));
+
+        match &mut self.views[view_id] {
+            View::CS(view) => view.error = Some(error),
+            View::MV(view) => view.error = Some(error),
+            View::File(_) | View::Federation(_) => {}
+        }
+    }
+
+    // Get a read access to the DAGs of the views included in the exports with the scope `scope`,
+    // along with the index of their view.
+    fn exported_dags(&self, scope: ExportScope) -> Vec<(ViewIndex, RwLockReadGuard<RoomEvents>)> {
+        self.views
+            .iter()
+            .filter(|view| scope == ExportScope::AllViews || view.get_id() == self.view_idx)
+            .filter_map(|view| {
+                view.get_events_dag()
+                    .as_ref()
+                    .map(|dag| (view.get_id(), dag.read().unwrap()))
+            })
+            .collect()
+    }
+
+    // Adds the signing keys of servers from a response to a `/_matrix/key/v2/server` request or a
+    // keys file, then checks the signatures of the events of every view again with them.
+    fn add_server_keys(&mut self, json: &JsonValue) {
+        match self.server_keys.add_keys_json(json) {
+            Ok(servers) => {
+                self.console.log(&format!(
+                    "Added the signing keys of: {}",
+                    servers.join(", ")
+                ));
+            }
+            Err(e) => {
+                self.console
+                    .log(&format!("Failed to add the signing keys: {}", e));
+                return;
+            }
+        }
+
+        for view in &self.views {
+            if let Some(events_dag) = view.get_events_dag() {
+                let changed_nodes = events_dag
+                    .write()
+                    .unwrap()
+                    .set_server_keys(&self.server_keys);
+
+                if self.vis.is_active() {
+                    self.vis
+                        .update_dag(events_dag.clone(), view.get_id(), changed_nodes);
+                }
+            }
+        }
+    }
+
+    fn display_body(&self) -> Html<Model> {
+        match &self.event_body {
+            Some(body) => {
+                let hash_check = match self.event_hash_check {
+                    Some(hash_check) => format!("Content hash: {}", hash_check),
+                    None => String::new(),
+                };
+                let signature_check = match self.event_signature_check {
+                    Some(signature_check) => format!("Signatures: {}", signature_check),
+                    None => String::new(),
+                };
+                let arrival = match self.event_arrival {
+                    Some(batch) => format!(
+                        "Received at {} ({})",
+                        format_time(batch.received_at),
+                        batch.kind
+                    ),
+                    None => String::new(),
+                };
+                let form = if self.event_body_redacted {
+                    "Redacted form of the event:"
+                } else {
+                    "Event as received:"
+                };
+
+                html! {
+                    <>
+                        <p>{ hash_check }</p>
+                        <p>{ signature_check }</p>
+                        <p>{ arrival }</p>
+                        <p>{ form }</p>
+                        <pre><code>{ body }</code></pre>
+                    </>
+                }
+            }
+            None => {
+                html! {
+                    <p>{ "No JSON body to show yet" }</p>
+                }
+            }
+        }
+    }
+
+    fn display_room_state(&self) -> Html<Model> {
+        match &self.room_state {
+            Some(room_state) => {
+                html! {
+                    <pre><code>{ room_state }</code></pre>
+                }
+            }
+            None => {
+                html! {
+                    <p>{ "No room state to show yet" }</p>
+                }
+            }
+        }
+    }
+
+    fn display_local_room_state(&self) -> Html<Model> {
+        match &self.local_room_state {
+            Some(local_room_state) => {
+                html! {
+                    <pre><code>{ local_room_state }</code></pre>
+                }
+            }
+            None => {
+                html! {
+                    <p>{ "No locally resolved room state to show yet" }</p>
+                }
+            }
+        }
+    }
+
+    fn display_rejected_events(&self) -> Html<Model> {
+        let rejected_event = |err: &model::event::ParseError| {
+            let json = serde_json::to_string_pretty(&err.json).unwrap_or_default();
+
+            html! {
+                <li>
+                    <p>{ err.to_string() }</p>
+                    <pre><code>{ json }</code></pre>
+                </li>
+            }
+        };
+
+        let view_rejected_events = |view: &View| match view.get_events_dag() {
+            Some(dag) => {
+                let dag = dag.read().unwrap();
+                let rejected_events = dag.quarantine();
+
+                if rejected_events.is_empty() {
+                    html! {
+                        <></>
+                    }
+                } else {
+                    html! {
+                        <>
+                            <p>{ format!("View {}: {} rejected events", view.get_id() + 1, rejected_events.len()) }</p>
+
+                            <ul>
+                                { for rejected_events.iter().map(rejected_event) }
+                            </ul>
+                        </>
+                    }
+                }
+            }
+            None => {
+                html! {
+                    <></>
+                }
+            }
+        };
+
+        html! {
+            <>
+                { for self.views.iter().map(view_rejected_events) }
+            </>
+        }
+    }
+
+    fn display_dag_diff(&self) -> Html<Model> {
+        let view_entry = |id, chosen: ViewIndex, side: fn(ViewIndex) -> UIEvent| {
+            html! {
+                <option value=format!("view-{}", id), selected=(id == chosen), onclick=|_| Msg::UI(side(id)),>{ format!("View {}", id + 1) }</option>
+            }
+        };
+        let event_entry = |ev: &model::diff::MissingEvent| {
+            html! {
+                <li>{ format!("{} (depth {})", ev.id, ev.depth) }</li>
+            }
+        };
+        let (view_a, view_b) = self.diff_views;
+
+        let diff = match &self.dag_diff {
+            Some(diff) => {
+                let extremities = if diff.extremities_differ() {
+                    "The forward extremities differ"
+                } else {
+                    "The forward extremities are the same"
+                };
+
+                html! {
+                    <>
+                        <p>{ format!("{} shared events", diff.shared.len()) }</p>
+                        <p>{ extremities }</p>
+                        <p>{ format!("Forward extremities of view {}: {}", view_a + 1, diff.extremities_a.join(", ")) }</p>
+                        <p>{ format!("Forward extremities of view {}: {}", view_b + 1, diff.extremities_b.join(", ")) }</p>
+
+                        <p>{ format!("{} events only in view {}:", diff.only_in_a.len(), view_a + 1) }</p>
+                        <ul>
+                            { for diff.only_in_a.iter().map(event_entry) }
+                        </ul>
+
+                        <p>{ format!("{} events only in view {}:", diff.only_in_b.len(), view_b + 1) }</p>
+                        <ul>
+                            { for diff.only_in_b.iter().map(event_entry) }
+                        </ul>
+                    </>
+                }
+            }
+            None => {
+                html! {
+                    <p>{ "No comparison to show yet" }</p>
+                }
+            }
+        };
+
+        html! {
+            <>
+                <p>{ format!("Compare view {} with view {}:", view_a + 1, view_b + 1) }</p>
+
+                <select id="diff-view-a",>
+                    { for (0..self.views.len()).map(|id| view_entry(id, view_a, UIEvent::DiffViewA)) }
+                </select>
+                <select id="diff-view-b",>
+                    { for (0..self.views.len()).map(|id| view_entry(id, view_b, UIEvent::DiffViewB)) }
+                </select>
+
+                <button onclick=|_| Msg::UICmd(UICommand::CompareViews), disabled=self.views.len() < 2,>
+                    { "Compare the DAGs of the views" }
+                </button>
+                <button onclick=|_| Msg::UICmd(UICommand::ClearComparison), disabled=self.dag_diff.is_none(),>
+                    { "Clear the comparison" }
+                </button>
+
+                { diff }
+            </>
+        }
+    }
+
+    fn display_replay(&self) -> Html<Model> {
+        let order = self.replay_order;
+
+        let controls = match &self.replay {
+            Some(replay) => {
+                let current_step = match replay.position.checked_sub(1) {
+                    Some(i) => {
+                        let step = &replay.steps[i];
+
+                        format!(
+                            "{}/{}: {} received at {} ({})",
+                            replay.position,
+                            replay.steps.len(),
+                            step.event_id,
+                            format_time(step.batch.received_at),
+                            step.batch.kind
+                        )
+                    }
+                    None => format!("0/{}", replay.steps.len()),
+                };
+
+                html! {
+                    <>
+                        <p>{ format!("Replaying view {}", replay.view_id + 1) }</p>
+
+                        <input type="range", id="replay-position", min=0, max=replay.steps.len(), value=replay.position, onchange=|e| Msg::UI(UIEvent::ReplayPosition(e)),/>
+
+                        <button onclick=|_| Msg::UICmd(UICommand::PlayReplay), disabled=replay.playing,>{ "Play" }</button>
+                        <button onclick=|_| Msg::UICmd(UICommand::PauseReplay), disabled=(!replay.playing),>{ "Pause" }</button>
+                        <button onclick=|_| Msg::UICmd(UICommand::StopReplay),>{ "Stop" }</button>
+
+                        <p>{ current_step }</p>
+                    </>
+                }
+            }
+            None => {
+                html! {
+                    <button onclick=|_| Msg::UICmd(UICommand::StartReplay),>{ "Replay the construction of the DAG of the current view" }</button>
+                }
+            }
+        };
+
+        html! {
+            <>
+                <input type="radio", id="replay-arrival", name="replay-order", value="replay-arrival", checked=(order == ReplayOrder::Arrival), onclick=|_| Msg::UI(UIEvent::ChooseReplayOrder(ReplayOrder::Arrival)),/>
+                <label for="replay-arrival",>{ "Arrival order" }</label>
+                <input type="radio", id="replay-ts", name="replay-order", value="replay-ts", checked=(order == ReplayOrder::OriginServerTS), onclick=|_| Msg::UI(UIEvent::ChooseReplayOrder(ReplayOrder::OriginServerTS)),/>
+                <label for="replay-ts",>{ "Origin server time stamp order" }</label>
+
+                <label for="replay-speed",>{ "Events per second:" }</label>
+                <input type="number", id="replay-speed", min=1, value=self.replay_speed, onchange=|e| Msg::UI(UIEvent::ReplaySpeed(e)),/>
+
+                { controls }
+            </>
+        }
+    }
+
+    fn display_history(&self) -> Html<Model> {
+        let view_id = self.view_idx;
+
+        let (history, dag) = match &self.views[view_id] {
+            View::CS(view) => match &view.events_dag {
+                Some(dag) => (&view.history, dag),
+                None => return html! { <></> },
+            },
+            _ => return html! { <></> },
+        };
+
+        let controls = match history {
+            Some(history) => {
+                let progress = format!(
+                    "Loading the full history: {} events received in {} requests, down to depth {}",
+                    history.events,
+                    history.pages,
+                    dag.read().unwrap().min_depth(),
+                );
+
+                html! {
+                    <>
+                        <p>{ progress }</p>
+                        <button onclick=|_| Msg::BkCmd(BkCommand::StopLoadingHistory(view_id)),>{ "Cancel" }</button>
+                    </>
+                }
+            }
+            None => {
+                html! {
+                    <button onclick=|_| Msg::BkCmd(BkCommand::LoadHistory(view_id)),>{ "Load the full history of the room" }</button>
+                }
+            }
+        };
+
+        let depth_limit = self
+            .history_depth_limit
+            .map_or(String::new(), |depth| depth.to_string());
+        let events_limit = self
+            .history_events_limit
+            .map_or(String::new(), |events| events.to_string());
+
+        html! {
+            <section class="history",>
+                <label for="history-depth-limit",>{ "Stop at depth (optional):" }</label>
+                <input type="number", id="history-depth-limit", value=depth_limit, onchange=|e| Msg::UI(UIEvent::HistoryDepthLimit(e)),/>
+                <label for="history-events-limit",>{ "Stop after this number of events (optional):" }</label>
+                <input type="number", id="history-events-limit", min=1, value=events_limit, onchange=|e| Msg::UI(UIEvent::HistoryEventsLimit(e)),/>
+
+                { controls }
+            </section>
+        }
+    }
+
+    fn display_gap_filling(&self) -> Html<Model> {
+        let view_id = self.view_idx;
+
+        let (gap_filling, dag) = match &self.views[view_id] {
+            View::CS(view) => match &view.events_dag {
+                Some(dag) => (&view.gap_filling, dag),
+                None => return html! { <></> },
+            },
+            _ => return html! { <></> },
+        };
+
+        let controls = match gap_filling {
+            Some(gap_filling) => {
+                let progress = format!(
+                    "Filling the gaps: generation {} of {}, {} missing events received",
+                    gap_filling.depth, gap_filling.depth_limit, gap_filling.fetched,
+                );
+
+                html! {
+                    <p>{ progress }</p>
+                }
+            }
+            None => {
+                let missing = format!(
+                    "{} events are missing from the DAG",
+                    dag.read().unwrap().missing_prev_events().len(),
+                );
+
+                html! {
+                    <>
+                        <p>{ missing }</p>
+                        <button onclick=|_| Msg::BkCmd(BkCommand::FetchMissingEvents(view_id)),>{ "Fetch the missing events" }</button>
+                    </>
+                }
+            }
+        };
+
+        let depth_limit = self.gap_depth_limit.to_string();
+
+        html! {
+            <section class="gap-filling",>
+                <label for="gap-depth-limit",>{ "Generations of missing ancestors to fetch:" }</label>
+                <input type="number", id="gap-depth-limit", min=1, value=depth_limit, onchange=|e| Msg::UI(UIEvent::GapDepthLimit(e)),/>
+
+                { controls }
+            </section>
+        }
+    }
+
+    fn display_backend_choice(&self) -> Html<Self> {
+        let bk_type = *self.bk_type.read().unwrap();
+
+        let connected = self.views.iter().any(View::is_connected);
+
+        if !connected {
+            html! {
+                <>
+                    <input type="radio", id="cs-bk", name="bk-type", value="cs-bk", checked=(bk_type == BackendChoice::CS), onclick=|_| Msg::UI(UIEvent::ChooseCSBackend),/>
+                    <label for="cs-bk",>{ "CS backend" }</label>
+                    <input type="radio", id="mv-bk", name="bk-type", value="mv-bk", checked=(bk_type == BackendChoice::MV), onclick=|_| Msg::UI(UIEvent::ChooseMVBackend),/>
+                    <label for="mv-bk",>{ "Matrix Visualisations backend" }</label>
+                    <input type="radio", id="file-bk", name="bk-type", value="file-bk", checked=(bk_type == BackendChoice::File), onclick=|_| Msg::UI(UIEvent::ChooseFileBackend),/>
+                    <label for="file-bk",>{ "Local files" }</label>
+                    <input type="radio", id="federation-bk", name="bk-type", value="federation-bk", checked=(bk_type == BackendChoice::Federation), onclick=|_| Msg::UI(UIEvent::ChooseFederationBackend),/>
+                    <label for="federation-bk",>{ "Federation API" }</label>
+                </>
+            }
+        } else {
+            html! {
+                <></>
+            }
+        }
+    }
+
+    fn display_login_methods(&self) -> Html<Self> {
+        let (login_method, login_flows, peek) = match &self.views[self.view_idx] {
+            View::CS(view) => {
+                let session = view.session.read().unwrap();
+
+                (
+                    session.login_method,
+                    session.login_flows.clone(),
+                    session.peeks(),
+                )
+            }
+            _ => return html! { <></> },
+        };
+
+        // Every login method is offered until the login flows of the homeserver are known
+        let supports = |flow: &str| {
+            login_flows
+                .as_ref()
+                .map_or(true, |flows| flows.iter().any(|f| f == flow))
+        };
+        let sso = supports("m.login.sso") || supports("m.login.cas");
+
+        let view_id = self.view_idx;
+        let method_choice = |method, id, label| {
+            html! {
+                <>
+                    <input type="radio", id=id, name="login-method", value=id, checked=(login_method == method), onclick=|_| Msg::UI(UIEvent::ChooseLoginMethod(method)),/>
+                    <label for=id,>{ label }</label>
+                </>
+            }
+        };
+
+        let credentials = match login_method {
+            LoginMethod::Password => html! {
+                <>
+                    <li>{ "Username: " }<input type="text", id="username-input", onchange=|e| Msg::UI(UIEvent::Username(e)),/></li>
+
+                    <li>{ "Password: " }<input type="password", id="password-input", onchange=|e| Msg::UI(UIEvent::Password(e)),/></li>
+                </>
+            },
+            LoginMethod::AccessToken => html! {
+                <li>{ "Access token: " }<input type="password", id="token-input", onchange=|e| Msg::UI(UIEvent::Token(e)),/></li>
+            },
+            LoginMethod::Token => html! {
+                <li>
+                    { "Login token: " }<input type="text", id="token-input", onchange=|e| Msg::UI(UIEvent::Token(e)),/>
+                    { if sso {
+                        html! { <button onclick=|_| Msg::UICmd(UICommand::LoginViaSso),>{ "Log in via SSO" }</button> }
+                    } else {
+                        html! { <></> }
+                    } }
+                </li>
+            },
+            LoginMethod::Guest => html! { <></> },
+        };
+
+        html! {
+            <>
+                <li>
+                    { "Log in with: " }
+                    { if supports("m.login.password") { method_choice(LoginMethod::Password, "login-password", "Password") } else { html! { <></> } } }
+                    { method_choice(LoginMethod::AccessToken, "login-access-token", "Access token") }
+                    { if supports("m.login.token") { method_choice(LoginMethod::Token, "login-token", "Login token") } else { html! { <></> } } }
+                    { method_choice(LoginMethod::Guest, "login-guest", "Guest account") }
+                    <button onclick=|_| Msg::BkCmd(BkCommand::GetLoginFlows(view_id)),>{ "Check the login flows" }</button>
+                </li>
+
+                { credentials }
+
+                <li>
+                    <input type="checkbox", id="peek", name="peek", checked=peek, disabled=(login_method == LoginMethod::Guest), onclick=|_| Msg::UI(UIEvent::TogglePeek),/>
+                    <label for="peek",>{ "Peek at the room without joining it (world-readable rooms only)" }</label>
+                </li>
+            </>
+        }
+    }
+
+    fn display_filter_settings(&self) -> Html<Self> {
+        let lazy_load_members = match &self.views[self.view_idx] {
+            View::CS(view) => view.session.read().unwrap().filter.lazy_load_members,
+            _ => false,
+        };
+
+        html! {
+            <li>
+                { "Filter (lists separated by commas, optional):" }
+                <ul>
+                    <li>{ "Only these event types: " }<input type="text", id="filter-types-input", placeholder="m.room.message, m.room.member", onchange=|e| Msg::UI(UIEvent::FilterTypes(e)),/></li>
+
+                    <li>{ "Not these event types: " }<input type="text", id="filter-not-types-input", onchange=|e| Msg::UI(UIEvent::FilterNotTypes(e)),/></li>
+
+                    <li>{ "Only these senders: " }<input type="text", id="filter-senders-input", onchange=|e| Msg::UI(UIEvent::FilterSenders(e)),/></li>
+
+                    <li>{ "Not these senders: " }<input type="text", id="filter-not-senders-input", onchange=|e| Msg::UI(UIEvent::FilterNotSenders(e)),/></li>
+
+                    <li>{ "Events per sync: " }<input type="number", id="timeline-limit-input", min=1, onchange=|e| Msg::UI(UIEvent::TimelineLimit(e)),/></li>
+
+                    <li>
+                        <input type="checkbox", id="lazy-load-members", name="lazy-load-members", checked=lazy_load_members, onclick=|_| Msg::UI(UIEvent::ToggleLazyLoadMembers),/>
+                        <label for="lazy-load-members",>{ "Lazy-load the members of the room" }</label>
+                    </li>
+                </ul>
+            </li>
+        }
+    }
+
+    fn display_received_login_token(&self) -> Html<Self> {
+        match &self.received_login_token {
+            Some(token) => html! {
+                <li>
+                    { format!("Logged in via SSO, the login token is: {}", token) }
+                    <br/>
+                    { "It has been put in the first view, it can also be pasted in the view it was requested from." }
+                </li>
+            },
+            None => html! { <></> },
+        }
+    }
+
+    fn display_view_choice(&self) -> Html<Self> {
+        let entry = |id: ViewIndex| {
+            let label = match self.views[id].reconnection() {
+                Some(_) => format!("View {} (reconnecting)", id + 1),
+                None => format!("View {}", id + 1),
+            };
+
+            html! {
+                <option value=format!("view-{}", id), onclick=|_| Msg::UI(UIEvent::ViewChoice(id)),>{ label }</option>
+            }
+        };
+
+        html! {
+            <>
+                <select id="view-select",>
+                    { for (0..self.views.len()).map(entry) }
+                </select>
+
+                <button onclick=|_| Msg::UI(UIEvent::AddView),>{ "Add a view" }</button>
+
+                { self.display_reconnection() }
+                { self.display_error() }
+            </>
+        }
+    }
+
+    fn display_reconnection(&self) -> Html<Self> {
+        match self.views[self.view_idx].reconnection() {
+            Some((attempts, delay)) => html! {
+                <p class="reconnecting",>
+                    { format!("Reconnecting to the homeserver: attempt {}, {:.1} s after the previous failure", attempts, delay.as_millis() as f64 / 1000.0) }
+                </p>
+            },
+            None => html! { <></> },
+        }
+    }
+
+    fn display_error(&self) -> Html<Self> {
+        let error = match self.views[self.view_idx].error() {
+            Some(error) => error,
+            None => return html! { <></> },
+        };
+
+        // Tell the user what to do when the failure won't go away by itself
+        let hint = if error.soft_logout {
+            html! { <><br/>{ "The session has expired, disconnect and log in again" }</> }
+        } else if error.is_logged_out() {
+            html! { <><br/>{ "The session has ended, disconnect and log in again" }</> }
+        } else {
+            html! { <></> }
+        };
+
+        html! {
+            <p class="view-error",>
+                { error.to_string() }
+                { hint }
+            </p>
+        }
+    }
+
+    fn display_interaction_list(&self) -> Html<Self> {
+        let view_id = self.view_idx;
+
+        match *self.bk_type.read().unwrap() {
+            BackendChoice::CS => {
+                html! {
+                    <ul>
+                        { self.display_received_login_token() }
+
+                        <li>{ "Server name: " }<input type="text", id="server-name-input", onchange=|e| Msg::UI(UIEvent::ServerName(e)),/></li>
+
+                        <li>{ "Base URL (optional): " }<input type="text", id="base-url-input", placeholder="https://matrix.example.org:8448/prefix", onchange=|e| Msg::UI(UIEvent::BaseUrl(e)),/></li>
+
+                        <li>{ "Room ID or alias: " }<input type="text", id="room-id-input", onchange=|e| Msg::UI(UIEvent::RoomId(e)),/></li>
+
+                        { self.display_login_methods() }
+
+                        { self.display_filter_settings() }
+
+                        <li>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Connect(view_id)),>{ "Connect" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Disconnect(view_id)),>{ "Disconnect" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::LeaveRoom(view_id)),>{ "Leave room and disconnect" }</button>
+                        </li>
+                    </ul>
+                }
+            }
+            BackendChoice::MV => {
+                html! {
+                    <ul>
+                        <li>{ "Server name: " }<input type="text", id="server-name-input", onchange=|e| Msg::UI(UIEvent::ServerName(e)),/></li>
+
+                        <li>{ "Base URL (optional): " }<input type="text", id="base-url-input", placeholder="https://matrix.example.org:8448/prefix", onchange=|e| Msg::UI(UIEvent::BaseUrl(e)),/></li>
+
+                        <li>{ "Room ID or alias: " }<input type="text", id="room-id-input", onchange=|e| Msg::UI(UIEvent::RoomId(e)),/></li>
+
+                        <li>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Connect(view_id)),>{ "Start observation" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Disconnect(view_id)),>{ "Stop observation" }</button>
+                        </li>
+                    </ul>
+                }
+            }
+            BackendChoice::File => {
+                html! {
+                    <ul>
+                        <li>{ "Server name: " }<input type="text", id="server-name-input", onchange=|e| Msg::UI(UIEvent::ServerName(e)),/></li>
+
+                        <li>{ "Room ID: " }<input type="text", id="room-id-input", onchange=|e| Msg::UI(UIEvent::RoomId(e)),/></li>
+
+                        <li>
+                            <label for="events-files",>{ "Events files (JSON array, {\"events\": [...]} or one event per line): " }</label>
+                            <input type="file", id="events-files", multiple="multiple", onchange=|e| Msg::UI(UIEvent::EventsFiles(e)),/>
+                        </li>
+
+                        <li>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Disconnect(view_id)),>{ "Close the files" }</button>
+                        </li>
+                    </ul>
+                }
+            }
+            BackendChoice::Federation => {
+                html! {
+                    <ul>
+                        <li>{ "Server name: " }<input type="text", id="server-name-input", onchange=|e| Msg::UI(UIEvent::ServerName(e)),/></li>
+
+                        <li>{ "Server address (optional, e.g. http://localhost:8080): " }<input type="text", id="address-input", onchange=|e| Msg::UI(UIEvent::FederationAddress(e)),/></li>
+
+                        <li>{ "Origin server name: " }<input type="text", id="origin-input", onchange=|e| Msg::UI(UIEvent::Origin(e)),/></li>
+
+                        <li>{ "Signing key of the origin (ed25519 <version> <seed>): " }<input type="password", id="signing-key-input", onchange=|e| Msg::UI(UIEvent::SigningKey(e)),/></li>
+
+                        <li>{ "Room ID: " }<input type="text", id="room-id-input", onchange=|e| Msg::UI(UIEvent::RoomId(e)),/></li>
+
+                        <li>{ "Event ID: " }<input type="text", id="event-id-input", onchange=|e| Msg::UI(UIEvent::EventId(e)),/></li>
+
+                        <li>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Connect(view_id)),>{ "Start observation" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::FetchMissingEvents(view_id)),>{ "Fetch the missing events" }</button>
+                            <button onclick=|_| Msg::BkCmd(BkCommand::Disconnect(view_id)),>{ "Stop observation" }</button>
+                        </li>
+                    </ul>
+                }
+            }
+        }
+    }
+}
+
+impl Renderable<Model> for Model {
+    fn view(&self) -> Html<Self> {
+        html! {
+            <section class="backend-choice",>
+                { self.display_backend_choice() }
+            </section>
+
+            <section class="view-choice",>
+                { self.display_view_choice() }
+            </section>
+
+            { self.display_interaction_list() }
+
+            { self.display_history() }
+
+            { self.display_gap_filling() }
+
+            <section class="fields-choice",>
+                <p>{ "Event fields to show in the DAG:" }</p>
+
+                <ul>
+                    <li>
+                        <input type="checkbox", id="sender", name="sender", checked=self.fields_choice.sender, onclick=|_| Msg::UI(UIEvent::ToggleSender),/>
+                        <label for="sender",>{ "Sender" }</label>
+                    </li>
+
+                    <li>
+                        <input type="checkbox", id="origin", name="origin", checked=self.fields_choice.origin, onclick=|_| Msg::UI(UIEvent::ToggleOrigin),/>
+                        <label for="origin",>{ "Origin" }</label>
+                    </li>
+
+                    <li>
+                        <input type="checkbox", id="origin-server-ts", name="origin-server-ts", checked=self.fields_choice.origin_server_ts, onclick=|_| Msg::UI(UIEvent::ToggleOriginServerTS),/>
+                        <label for="origin-server-ts",>{ "Origin server time stamp" }</label>
+                    </li>
+
+                    <li>
+                        <input type="checkbox", id="type", name="type", checked=self.fields_choice.etype, onclick=|_| Msg::UI(UIEvent::ToggleType),/>
+                        <label for="type",>{ "Type" }</label>
+                    </li>
+
+                    <li>
+                        <input type="checkbox", id="state-key", name="state-key", checked=self.fields_choice.state_key, onclick=|_| Msg::UI(UIEvent::ToggleStateKey),/>
+                        <label for="state-key",>{ "State key" }</label>
+                    </li>
+
+                    <li>
+                        <input type="checkbox", id="prev-events", name="prev-events", checked=self.fields_choice.prev_events, onclick=|_| Msg::UI(UIEvent::TogglePrevEvents),/>
+                        <label for="prev-events",>{ "Previous events" }</label>
+                    </li>
+
+                    <li>
+                        <input type="checkbox", id="depth", name="depth", checked=self.fields_choice.depth, onclick=|_| Msg::UI(UIEvent::ToggleDepth),/>
+                        <label for="depth",>{ "Depth" }</label>
+                    </li>
+
+                    <li>
+                        <input type="checkbox", id="redacts", name="redacts", checked=self.fields_choice.redacts, onclick=|_| Msg::UI(UIEvent::ToggleRedacts),/>
+                        <label for="redacts",>{ "Redacts" }</label>
+                    </li>
+
+                    <li>
+                        <input type="checkbox", id="event-id", name="event-id", checked=self.fields_choice.event_id, onclick=|_| Msg::UI(UIEvent::ToggleEventID),/>
+                        <label for="event-id",>{ "Event ID" }</label>
+                    </li>
+                </ul>
+            </section>
+
+            <section class="edges-choice",>
+                <p>{ "Edges to show in the DAG:" }</p>
+
+                <input type="radio", id="prev-edges", name="edges", value="prev-edges", checked=(self.edges_choice == EdgesChoice::Prev), onclick=|_| Msg::UI(UIEvent::ChooseEdges(EdgesChoice::Prev)),/>
+                <label for="prev-edges",>{ "Previous events" }</label>
+                <input type="radio", id="auth-edges", name="edges", value="auth-edges", checked=(self.edges_choice == EdgesChoice::Auth), onclick=|_| Msg::UI(UIEvent::ChooseEdges(EdgesChoice::Auth)),/>
+                <label for="auth-edges",>{ "Auth events" }</label>
+                <input type="radio", id="both-edges", name="edges", value="both-edges", checked=(self.edges_choice == EdgesChoice::Both), onclick=|_| Msg::UI(UIEvent::ChooseEdges(EdgesChoice::Both)),/>
+                <label for="both-edges",>{ "Both" }</label>
+            </section>
+
+            <section class="branches-choice",>
+                <input type="checkbox", id="color-branches", name="color-branches", checked=self.branches_colored, onclick=|_| Msg::UI(UIEvent::ToggleBranches),/>
+                <label for="color-branches",>{ "Color each branch of the DAG (fork and merge points have a thicker border)" }</label>
+            </section>
+
+            <section class="signatures",>
+                <p>{ "Events with bad signatures are pink, events missing a signature are yellow" }</p>
+
+                <button onclick=|_| Msg::BkCmd(BkCommand::FetchServerKeys),>{ "Fetch the signing keys of the servers" }</button>
+                <label for="keys-file",>{ "Import signing keys from a file:" }</label>
+                <input type="file", id="keys-file", onchange=|e| Msg::UI(UIEvent::ImportKeys(e)),/>
+            </section>
+
+            <section class="to-hide",>
+                <input type="text", id="targeted-view",/>
+
+                <button id="more-ev-target", onclick=|_| Msg::BkCmd(BkCommand::MoreMsg),>{ "More events" }</button>
+                <input type="text", id="selected-event",/>
+                <button id="display-body-target", onclick=|_| Msg::UICmd(UICommand::DisplayEventBody),>{ "Display body" }</button>
+
+                <input type="text", id="ancestors-id",/>
+                <button id="ancestors-target", onclick=|_| Msg::BkCmd(BkCommand::MoreMsg),>{ "Ancestors" }</button>
+            </section>
+
+            <div class="view",>
+                <section id="dag-vis",>
+                </section>
+
+                <section id="event-body",>
+                { self.display_body() }
+                </section>
+            </div>
+
+            <section class="state",>
+                <button onclick=|_| Msg::BkCmd(BkCommand::FetchState), disabled=self.event_body.is_none(),>
+                    { "Room state at the selected event" }
+                </button>
+
+                <button onclick=|_| Msg::UICmd(UICommand::ResolveState), disabled=self.event_body.is_none(),>
+                    { "Resolve the room state locally at the selected event" }
+                </button>
+
+                <button onclick=|_| Msg::UICmd(UICommand::DisplayRedactedBody), disabled=self.event_body.is_none(),>
+                    { "Show the redacted form of the selected event" }
+                </button>
+
+                <div class="states",>
+                    <section id="room-state",>
+                    { self.display_room_state() }
+                    </section>
+
+                    <section id="local-room-state",>
+                    { self.display_local_room_state() }
+                    </section>
+                </div>
+            </section>
+
+            <section class="export",>
+                <p>{ "Export the DAG of the current view:" }</p>
+                <button onclick=|_| Msg::UICmd(UICommand::Export(ExportFormat::Dot, ExportScope::CurrentView)),>{ "DOT" }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::Export(ExportFormat::GraphML, ExportScope::CurrentView)),>{ "GraphML" }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::Export(ExportFormat::Gexf, ExportScope::CurrentView)),>{ "GEXF" }</button>
+
+                <p>{ "Export the DAGs of every views:" }</p>
+                <button onclick=|_| Msg::UICmd(UICommand::Export(ExportFormat::Dot, ExportScope::AllViews)),>{ "DOT" }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::Export(ExportFormat::GraphML, ExportScope::AllViews)),>{ "GraphML" }</button>
+                <button onclick=|_| Msg::UICmd(UICommand::Export(ExportFormat::Gexf, ExportScope::AllViews)),>{ "GEXF" }</button>
+            </section>
+
+            <section class="snapshot",>
+                <button onclick=|_| Msg::UICmd(UICommand::SaveSnapshot),>{ "Save a snapshot of the DAG of the current view" }</button>
+                <label for="snapshot-file",>{ "Load a snapshot in the current view:" }</label>
+                <input type="file", id="snapshot-file", onchange=|e| Msg::UI(UIEvent::LoadSnapshot(e)),/>
+            </section>
+
+            <section class="replay",>
+                { self.display_replay() }
+            </section>
+
+            <section id="dag-diff",>
+                { self.display_dag_diff() }
+            </section>
+
+            <section id="rejected-events",>
+                { self.display_rejected_events() }
+            </section>
+        }
+    }
+}
+
+// Reads the view and the ID of the event which have been selected in the vis.js network from the
+// hidden inputs filled by its callbacks.
+fn selected_event() -> (ViewIndex, String) {
+    let view_selection_input: web::html_element::InputElement = web::document()
+        .query_selector("#targeted-view")
+        .expect("Couldn't get document element")
+        .expect("Couldn't get document element")
+        .try_into()
+        .unwrap();
+    let view_id: ViewIndex = view_selection_input
+        .raw_value()
+        .parse()
+        .expect("Failed to parse view_id");
+
+    let event_id_input: web::html_element::InputElement = web::document()
+        .query_selector("#selected-event")
+        .expect("Couldn't get document element")
+        .expect("Couldn't get document element")
+        .try_into()
+        .unwrap();
+    let event_id = event_id_input.raw_value();
+
+    (view_id, event_id)
+}
+
+// Makes the browser download a file named `filename` with the content `content`.
+fn download(filename: &str, mime_type: &str, content: &str) {
+    js! {
+        var blob = new Blob([@{content}], { type: @{mime_type} });
+        var url = URL.createObjectURL(blob);
+        var link = document.createElement("a");
+
+        link.href = url;
+        link.download = @{filename};
+        document.body.appendChild(link);
+        link.click();
+        document.body.removeChild(link);
+        URL.revokeObjectURL(url);
+    }
+}
+
+// Empties the input `selector` if it is currently displayed, since some inputs are only displayed
+// for some login methods.
+fn clear_input(selector: &str) {
+    if let Ok(Some(input)) = web::document().query_selector(selector) {
+        let input: web::html_element::InputElement = input.try_into().unwrap();
+        input.set_raw_value("");
+    }
+}
+
+// Get the login token given in the query string of the page when the user is sent back to the
+// application after logging in via SSO.
+fn login_token_from_url() -> Option<String> {
+    js! { return new URLSearchParams(window.location.search).get("loginToken"); }
+        .try_into()
+        .unwrap_or(None)
+}
+
+// Get the URL of the application, without its query string, to which the user is sent back after
+// logging in via SSO.
+fn app_url() -> String {
+    js! { return window.location.origin + window.location.pathname; }
+        .try_into()
+        .unwrap()
+}
+
+// Get the items of a list entered by the user, separated by commas or whitespaces.
+fn parse_list(list: &str) -> Vec<String> {
+    list.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Get a random number between 0 and 1.
+fn random() -> f64 {
+    js! { return Math.random(); }.try_into().unwrap()
+}
+
+// Get the current local time, in milliseconds since the Unix epoch.
+fn now() -> f64 {
+    js! { return Date.now(); }.try_into().unwrap()
+}
+
+// Formats a local time given in milliseconds since the Unix epoch.
+fn format_time(time: f64) -> String {
+    js! { return new Date(@{time}).toLocaleString(); }
+        .try_into()
+        .unwrap()
+}
+
+// Creates a batch of the kind `kind` received now.
+fn new_batch(kind: BatchKind) -> Batch {
+    Batch {
+        kind,
+        received_at: now(),
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::Duration;
+
+use crate::cs_backend::error::MatrixError;
+
+// The delay before the first retry
+const BASE_DELAY_MS: u64 = 1000;
+
+// The longest delay between two retries
+const MAX_DELAY_MS: u64 = 60_000;
+
+/// Counts the consecutive failures of the requests of a view which keep it updated, e.g. `/sync`,
+/// and gives the delay before retrying them.
+///
+/// The delay doubles with each failure, up to a minute, and is randomly shortened by up to a half
+/// so the views of a restarting homeserver don't all retry at once. The delay asked by the
+/// homeserver for the rate-limited requests is used as is.
+#[derive(Debug, Default)]
+pub struct Backoff {
+    attempts: u32,           // The number of retries since the last successful request
+    delay: Option<Duration>, // The delay before the current retry
+}
+
+impl Backoff {
+    pub fn new() -> Backoff {
+        Backoff {
+            attempts: 0,
+            delay: None,
+        }
+    }
+
+    /// Get the delay before retrying the request which failed with `error` and counts the retry,
+    /// or `None` if retrying wouldn't help, e.g. if the access token is invalid. `random` is a
+    /// random number between 0 and 1 used for the jitter.
+    ///
+    /// The requests which didn't get any response, e.g. because of a network error, are retried.
+    pub fn retry(&mut self, error: &MatrixError, random: f64) -> Option<Duration> {
+        if !error.is_transient() {
+            return None;
+        }
+
+        let delay_ms = error.retry_after_ms.unwrap_or_else(|| {
+            let delay_ms = BASE_DELAY_MS
+                .saturating_mul(1 << self.attempts.min(16))
+                .min(MAX_DELAY_MS);
+
+            delay_ms - (delay_ms as f64 * random.clamp(0.0, 1.0) / 2.0) as u64
+        });
+        let delay = Duration::from_millis(delay_ms);
+
+        self.attempts += 1;
+        self.delay = Some(delay);
+
+        Some(delay)
+    }
+
+    /// Starts counting again after a successful request.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+        self.delay = None;
+    }
+
+    /// Get the number of retries since the last successful request.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Get the delay before the current retry, if the requests are being retried.
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use http::StatusCode;
+    use serde_json::Value as JsonValue;
+
+    fn error(status: StatusCode, body: &JsonValue) -> MatrixError {
+        MatrixError::from_response("error syncing", status, &body.to_string())
+    }
+
+    fn server_error() -> MatrixError {
+        error(StatusCode::BAD_GATEWAY, &json!({}))
+    }
+
+    #[test]
+    fn exponential_growth() {
+        let mut backoff = Backoff::new();
+        let delays: Vec<_> = (0..6)
+            .map(|_| backoff.retry(&server_error(), 0.0).unwrap().as_millis())
+            .collect();
+
+        assert_eq!(delays, vec![1000, 2000, 4000, 8000, 16000, 32000]);
+        assert_eq!(backoff.attempts(), 6);
+        assert_eq!(backoff.delay(), Some(Duration::from_millis(32000)));
+
+        backoff.reset();
+
+        assert_eq!(backoff.attempts(), 0);
+        assert_eq!(backoff.delay(), None);
+        assert_eq!(
+            backoff.retry(&server_error(), 0.0),
+            Some(Duration::from_millis(BASE_DELAY_MS))
+        );
+    }
+
+    #[test]
+    fn maximum_delay() {
+        let mut backoff = Backoff::default();
+
+        for _ in 0..100 {
+            assert!(
+                backoff.retry(&server_error(), 0.0).unwrap() <= Duration::from_millis(MAX_DELAY_MS)
+            );
+        }
+
+        assert_eq!(backoff.attempts(), 100);
+        assert_eq!(backoff.delay(), Some(Duration::from_millis(MAX_DELAY_MS)));
+    }
+
+    #[test]
+    fn jitter() {
+        let delay = |random| {
+            Backoff::new()
+                .retry(&server_error(), random)
+                .unwrap()
+                .as_millis()
+        };
+
+        // The delay is shortened by up to a half, even with random numbers out of bounds
+        assert_eq!(delay(0.0), 1000);
+        assert_eq!(delay(0.5), 750);
+        assert_eq!(delay(1.0), 500);
+        assert_eq!(delay(-1.0), 1000);
+        assert_eq!(delay(2.0), 500);
+    }
+
+    #[test]
+    fn retry_after() {
+        let mut backoff = Backoff::new();
+        let body = json!({ "errcode": "M_LIMIT_EXCEEDED", "retry_after_ms": 5000 });
+
+        for _ in 0..3 {
+            backoff.retry(&server_error(), 0.0);
+        }
+
+        // The delay asked by the homeserver is used as is, without any jitter
+        assert_eq!(
+            backoff.retry(&error(StatusCode::TOO_MANY_REQUESTS, &body), 1.0),
+            Some(Duration::from_millis(5000))
+        );
+        assert_eq!(backoff.attempts(), 4);
+    }
+
+    #[test]
+    fn permanent_errors() {
+        let mut backoff = Backoff::new();
+        let forbidden = json!({ "errcode": "M_FORBIDDEN" });
+        let unknown_token = json!({ "errcode": "M_UNKNOWN_TOKEN" });
+
+        assert_eq!(
+            backoff.retry(&error(StatusCode::FORBIDDEN, &forbidden), 0.0),
+            None
+        );
+        assert_eq!(
+            backoff.retry(&error(StatusCode::UNAUTHORIZED, &unknown_token), 0.0),
+            None
+        );
+        assert_eq!(
+            backoff.retry(
+                &MatrixError::invalid_request("foo bar", &"invalid URI"),
+                0.0
+            ),
+            None
+        );
+        assert_eq!(backoff.attempts(), 0);
+        assert_eq!(backoff.delay(), None);
+    }
+}
@@ -0,0 +1,435 @@
+// A command-line tool which retrieves the events DAG of a room from a homeserver via the
+// Client-Server API, the same way the CS backend of the web application does, and writes it as a
+// JSON snapshot, as a DOT document or as a summary report.
+
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use failure::{format_err, Error};
+use serde::de::DeserializeOwned;
+
+use matrix_visualisations::cs_backend::api::{
+    self, ApiRequest, ConnectionResponse, JoinedRooms, MessagesResponse, RegisterResponse,
+    RoomAliasResponse, RoomInitialSync, SyncResponse, WellKnown, WhoAmIResponse,
+};
+use matrix_visualisations::cs_backend::error::MatrixError;
+use matrix_visualisations::cs_backend::session::{LoginMethod, Session};
+use matrix_visualisations::model::dag::{Batch, BatchKind, EdgeKind, RoomEvents};
+use matrix_visualisations::model::event::Field;
+use matrix_visualisations::model::export;
+use matrix_visualisations::model::snapshot::Snapshot;
+use matrix_visualisations::BackendChoice;
+
+const USAGE: &str = "\
+Usage: matrix-visualisations-dump --server <server name> --room <room ID or alias> [options]
+
+Options:
+    --base-url <URL>        The base URL of the homeserver if it can't be discovered via
+                            .well-known, e.g. http://localhost:8008
+    --user <username>       The username to log in with, the password is read from the
+                            MATRIX_PASSWORD environment variable or else from the standard input
+    --token <access token>  An access token to use instead of logging in
+    --login-token <token>   A login token to log in with, e.g. obtained via SSO
+    --guest                 Register a guest account instead of logging in, implies --peek
+    --peek                  Peek at the room, which must be world-readable, without joining it
+    --pages <count>         The maximum number of /messages requests [default: 10]
+    --format <format>       json, dot or summary [default: summary]
+    --output <path>         The file to write to instead of the standard output";
+
+// The environment variable the password can be given in, so it isn't in the arguments of the
+// command where other users could see it.
+const PASSWORD_VAR: &str = "MATRIX_PASSWORD";
+
+// The formats in which the DAG can be written.
+enum OutputFormat {
+    Json,
+    Dot,
+    Summary,
+}
+
+struct Options {
+    server_name: String,
+    base_url: String,
+    room_id: String,
+    username: String,
+    access_token: Option<String>,
+    login_token: Option<String>,
+    guest: bool,
+    peek: bool,
+    pages: usize,
+    format: OutputFormat,
+    output: Option<String>,
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1).collect()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&options) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Options, Error> {
+    let mut options = Options {
+        server_name: String::new(),
+        base_url: String::new(),
+        room_id: String::new(),
+        username: String::new(),
+        access_token: None,
+        login_token: None,
+        guest: false,
+        peek: false,
+        pages: 10,
+        format: OutputFormat::Summary,
+        output: None,
+    };
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format_err!("Missing value for {}", arg))
+        };
+
+        match arg.as_str() {
+            "--server" => options.server_name = value()?,
+            "--base-url" => options.base_url = value()?,
+            "--room" => options.room_id = value()?,
+            "--user" => options.username = value()?,
+            "--token" => options.access_token = Some(value()?),
+            "--login-token" => options.login_token = Some(value()?),
+            "--guest" => options.guest = true,
+            "--peek" => options.peek = true,
+            "--pages" => {
+                options.pages = value()?
+                    .parse()
+                    .map_err(|_| format_err!("The number of pages must be a number"))?
+            }
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "json" => OutputFormat::Json,
+                    "dot" => OutputFormat::Dot,
+                    "summary" => OutputFormat::Summary,
+                    format => return Err(format_err!("Unknown format: {}", format)),
+                }
+            }
+            "--output" => options.output = Some(value()?),
+            _ => return Err(format_err!("Unknown argument: {}", arg)),
+        }
+    }
+
+    if options.server_name.is_empty() || options.room_id.is_empty() {
+        return Err(format_err!("The server name and the room ID are required"));
+    }
+    if !options.guest
+        && options.access_token.is_none()
+        && options.login_token.is_none()
+        && options.username.is_empty()
+    {
+        return Err(format_err!(
+            "Either a username, an access token, a login token or --guest is required"
+        ));
+    }
+
+    Ok(options)
+}
+
+// Get the password to log in with from its environment variable, or else from the first line of
+// the standard input.
+fn read_password() -> Result<String, Error> {
+    if let Ok(password) = env::var(PASSWORD_VAR) {
+        return Ok(password);
+    }
+
+    eprint!("Password: ");
+
+    let mut password = String::new();
+    io::stdin().read_line(&mut password)?;
+
+    Ok(password.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+fn run(options: &Options) -> Result<(), Error> {
+    let client = reqwest::Client::new();
+
+    let mut session = Session::empty();
+    session.server_name = options.server_name.clone();
+    session.base_url = options.base_url.clone();
+    session.room_id = options.room_id.clone();
+    session.username = options.username.clone();
+    session.peek = options.peek;
+
+    if session.needs_discovery() {
+        // Without `.well-known`, the homeserver is reached at the server name itself
+        let discovered: Result<WellKnown, Error> = send(
+            &client,
+            api::well_known(&session.server_name),
+            "error discovering the homeserver",
+        );
+
+        session.discovered_base_url = Some(match discovered {
+            Ok(res) => res.homeserver.base_url,
+            Err(_) => session.server_name.clone(),
+        });
+    }
+
+    if let Some(login_token) = &options.login_token {
+        session.login_method = LoginMethod::Token;
+        session.token = login_token.clone();
+    }
+
+    // Only log out at the end if the access token was obtained by logging in, the guests can't log
+    // out
+    let logged_in = options.access_token.is_none() && !options.guest;
+    if options.guest {
+        session.login_method = LoginMethod::Guest;
+
+        let res: RegisterResponse = send(
+            &client,
+            api::register_guest(&session),
+            "error registering a guest account",
+        )?;
+
+        session.user_id = res.user_id;
+        session.access_token = Some(res.access_token);
+        session.device_id = res.device_id;
+    } else if logged_in {
+        if options.login_token.is_none() {
+            session.password = read_password()?;
+        }
+
+        let res: ConnectionResponse = send(&client, api::login(&session), "error connecting")?;
+
+        session.user_id = res.user_id;
+        session.access_token = Some(res.access_token);
+        session.device_id = Some(res.device_id);
+    } else {
+        session.login_method = LoginMethod::AccessToken;
+        session.access_token = options.access_token.clone();
+
+        let res: WhoAmIResponse = send(
+            &client,
+            api::whoami(&session),
+            "error checking the access token",
+        )?;
+
+        session.user_id = res.user_id;
+        session.device_id = res.device_id;
+    }
+
+    let result = retrieve_dag(&client, &mut session, options.pages);
+
+    if logged_in {
+        send_empty(&client, api::logout(&session), "error disconnecting")?;
+    }
+
+    let events_dag = result?;
+    let output = match options.format {
+        OutputFormat::Json => {
+            let fetched_at = events_dag.last_received_at().unwrap_or_else(now);
+            let snapshot =
+                Snapshot::new(&events_dag, &session.room_id, BackendChoice::CS, fetched_at);
+
+            serde_json::to_string_pretty(&snapshot)?
+        }
+        OutputFormat::Dot => export::to_dot(&[(0, &events_dag)], &[EdgeKind::Prev, EdgeKind::Auth]),
+        OutputFormat::Summary => summary(&events_dag, &session.room_id),
+    };
+
+    match &options.output {
+        Some(path) => fs::write(path, output)?,
+        None => print!("{}", output),
+    }
+
+    Ok(())
+}
+
+// Resolves the alias of the room if it was given by its alias, joins the room if needed, makes the
+// initial sync and then retrieves earlier events with up to `pages` `/messages` requests, or until
+// the beginning of the room is reached. When peeking at the room, it is never joined and the
+// initial sync is replaced by `/initialSync`.
+fn retrieve_dag(
+    client: &reqwest::Client,
+    session: &mut Session,
+    pages: usize,
+) -> Result<RoomEvents, Error> {
+    if api::is_room_alias(&session.room_id) {
+        let res: RoomAliasResponse = send(
+            client,
+            api::resolve_alias(&session.base_url(), &session.room_id),
+            "error resolving the room alias",
+        )?;
+
+        session.room_id = res.room_id;
+        session.via_servers = res.servers;
+    }
+
+    let fields: HashSet<Field> = [Field::EventID].iter().cloned().collect();
+    let mut events_dag = if session.peeks() {
+        let res: RoomInitialSync = send(
+            client,
+            api::room_initial_sync(session),
+            "error peeking at the room",
+        )?;
+        let messages = res
+            .messages
+            .ok_or_else(|| format_err!("The timeline of the room can't be peeked at"))?;
+
+        // Every event comes from `/messages`, from the latest ones of the room
+        session.prev_batch_token = Some(messages.end);
+
+        RoomEvents::from_room_state(&session.server_name, &fields, &res.state)
+    } else {
+        let res: JoinedRooms = send(
+            client,
+            api::joined_rooms(session),
+            "error listing joined rooms",
+        )?;
+        if !res.joined_rooms.contains(&session.room_id) {
+            send_empty(client, api::join_room(session), "error joining the room")?;
+        }
+
+        let res: SyncResponse = send(client, api::sync(session, None), "error syncing")?;
+        if let Some(room) = res.rooms.join.get(&session.room_id) {
+            session.prev_batch_token = room.timeline.prev_batch.clone();
+        }
+
+        RoomEvents::from_sync_response(&session.room_id, &session.server_name, &fields, res, now())
+            .ok_or_else(|| format_err!("The room isn't in the sync response"))?
+    };
+
+    for _ in 0..pages {
+        if session.prev_batch_token.is_none() {
+            break;
+        }
+
+        let res: MessagesResponse = send(
+            client,
+            api::prev_messages(session),
+            "error retrieving previous messages",
+        )?;
+
+        // An empty chunk means the beginning of the room was reached
+        if res.chunk.is_empty() {
+            break;
+        }
+
+        session.prev_batch_token = res.end;
+        events_dag.add_events(
+            res.chunk,
+            Batch {
+                kind: BatchKind::Messages,
+                received_at: now(),
+            },
+        );
+    }
+
+    Ok(events_dag)
+}
+
+// Writes a short report on the events DAG of the room `room_id`.
+fn summary(events_dag: &RoomEvents, room_id: &str) -> String {
+    let events = events_dag.events();
+    let shape = events_dag.shape();
+    let max_depth = events.iter().map(|event| event.depth).max();
+
+    let mut report = String::new();
+
+    report.push_str(&format!("Room: {}\n", room_id));
+    report.push_str(&format!("Server: {}\n", events_dag.server_name()));
+    report.push_str(&format!(
+        "Room version: {}\n",
+        events_dag
+            .room_version()
+            .map_or("unknown", |version| version.id())
+    ));
+    report.push_str(&format!("Events: {}\n", events.len()));
+    if let Some(max_depth) = max_depth {
+        report.push_str(&format!(
+            "Depths: {} to {}\n",
+            events_dag.min_depth(),
+            max_depth
+        ));
+    }
+    report.push_str(&format!(
+        "Latest events: {}\n",
+        events_dag.latest_events().join(", ")
+    ));
+    report.push_str(&format!(
+        "Earliest events: {}\n",
+        events_dag.earliest_events().join(", ")
+    ));
+    report.push_str(&format!(
+        "Orphan events: {}\n",
+        events_dag.orphan_events().len()
+    ));
+    report.push_str(&format!("Fork points: {}\n", shape.fork_points.len()));
+    report.push_str(&format!("Merge points: {}\n", shape.merge_points.len()));
+    report.push_str(&format!("Branches: {}\n", shape.branches.len()));
+    report.push_str(&format!(
+        "Quarantined events: {}\n",
+        events_dag.quarantine().len()
+    ));
+
+    report
+}
+
+// Sends `request` and parses the JSON body of its response. The requests which couldn't be built
+// fail right away.
+fn send<T: DeserializeOwned>(
+    client: &reqwest::Client,
+    request: Result<ApiRequest, MatrixError>,
+    error: &str,
+) -> Result<T, Error> {
+    let mut response = execute(client, request?)?;
+    let body = response.text()?;
+
+    api::parse_response(response.status(), &body, error)
+}
+
+// Sends `request` and only reads the body of its response for the error it gives if the request
+// failed.
+fn send_empty(
+    client: &reqwest::Client,
+    request: Result<ApiRequest, MatrixError>,
+    error: &str,
+) -> Result<(), Error> {
+    let mut response = execute(client, request?)?;
+    let body = response.text()?;
+
+    api::check_response(response.status(), &body, error)
+}
+
+fn execute(client: &reqwest::Client, request: ApiRequest) -> Result<reqwest::Response, Error> {
+    let (parts, body) = request.into_parts();
+
+    let builder = client
+        .request(parts.method, &parts.uri.to_string())
+        .headers(parts.headers);
+    let builder = match body {
+        Some(body) => builder.body(body),
+        None => builder,
+    };
+
+    Ok(builder.send()?)
+}
+
+// Get the current time, in milliseconds since the Unix epoch.
+fn now() -> f64 {
+    let elapsed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    elapsed.as_secs() as f64 * 1000.0 + f64::from(elapsed.subsec_millis())
+}
@@ -0,0 +1,720 @@
+use std::collections::HashMap;
+
+use failure::Error;
+use http::{Request, StatusCode};
+use percent_encoding::{define_encode_set, utf8_percent_encode, USERINFO_ENCODE_SET};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::error::MatrixError;
+use super::session::{FilterSettings, LoginMethod, Session};
+
+// The requests to the Client-Server API are built and their responses are parsed here, apart from
+// the way they are sent, so they can be used both by the web application and natively.
+
+/// A request to the homeserver, with its JSON body if it has one.
+pub type ApiRequest = Request<Option<String>>;
+
+/// Represents the JSON body of a `POST /_matrix/client/r0/login` request, with either an
+/// identifier and a password or a login token.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ConnectionRequest {
+    #[serde(rename = "type")]
+    typo: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    identifier: Option<Identifier>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    initial_device_display_name: String,
+}
+
+/// Represents the `identifier` field in `ConnectionRequest`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Identifier {
+    #[serde(rename = "type")]
+    typo: String,
+    user: String,
+}
+
+/// Represents the JSON body of a response to a `POST /_matrix/client/r0/login` request.
+#[derive(Debug, Deserialize)]
+pub struct ConnectionResponse {
+    pub user_id: String,
+    pub access_token: String,
+    pub device_id: String,
+}
+
+/// Represents the JSON body of a response to a `GET /.well-known/matrix/client` request.
+#[derive(Debug, Deserialize)]
+pub struct WellKnown {
+    #[serde(rename = "m.homeserver")]
+    pub homeserver: HomeserverInformation,
+}
+
+/// Represents the `m.homeserver` field in `WellKnown`.
+#[derive(Debug, Deserialize)]
+pub struct HomeserverInformation {
+    pub base_url: String,
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/client/r0/login` request.
+#[derive(Debug, Deserialize)]
+pub struct LoginFlows {
+    pub flows: Vec<LoginFlow>,
+}
+
+/// Represents a login flow supported by the homeserver in `LoginFlows`.
+#[derive(Debug, Deserialize)]
+pub struct LoginFlow {
+    #[serde(rename = "type")]
+    pub typo: String,
+}
+
+/// Represents the JSON body of a response to a `POST /_matrix/client/r0/register?kind=guest`
+/// request.
+#[derive(Debug, Deserialize)]
+pub struct RegisterResponse {
+    pub user_id: String,
+    pub access_token: String,
+    pub device_id: Option<String>,
+}
+
+/// Represents the JSON body of a response to a `POST /_matrix/client/r0/user/{userId}/filter`
+/// request.
+#[derive(Debug, Deserialize)]
+pub struct FilterResponse {
+    pub filter_id: String,
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/client/r0/account/whoami` request.
+#[derive(Debug, Deserialize)]
+pub struct WhoAmIResponse {
+    pub user_id: String,
+    pub device_id: Option<String>,
+}
+
+/// Represents the JSON body of a response to a
+/// `GET /_matrix/client/r0/directory/room/{roomAlias}` request.
+#[derive(Debug, Deserialize)]
+pub struct RoomAliasResponse {
+    pub room_id: String,
+    pub servers: Vec<String>, // The servers which are aware of the room, to join it through
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/client/r0/joined_rooms` request.
+#[derive(Debug, Deserialize)]
+pub struct JoinedRooms {
+    pub joined_rooms: Vec<String>,
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/client/r0/sync` request.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SyncResponse {
+    pub next_batch: String,
+    #[serde(default)]
+    pub rooms: Rooms,
+    presence: Option<JsonValue>,
+    #[serde(default)]
+    account_data: JsonValue,
+    to_device: Option<JsonValue>,
+    device_lists: Option<JsonValue>,
+    #[serde(default)]
+    device_one_time_keys_count: HashMap<String, u64>,
+}
+
+/// Represents the list of rooms in `SyncResponse`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Rooms {
+    #[serde(default)]
+    leave: HashMap<String, JsonValue>,
+    #[serde(default)]
+    pub join: HashMap<String, JoinedRoom>,
+    #[serde(default)]
+    invite: HashMap<String, JsonValue>,
+}
+
+/// Represents the list of rooms joined by the user in `SyncResponse`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct JoinedRoom {
+    #[serde(default)]
+    pub unread_notifications: JsonValue,
+    #[serde(default)]
+    pub timeline: Timeline,
+    #[serde(default)]
+    pub state: State,
+    #[serde(default)]
+    pub account_data: JsonValue,
+    #[serde(default)]
+    pub ephemeral: JsonValue,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct State {
+    // TODO: Implement StateEvent
+    #[serde(default)]
+    pub events: Vec<JsonValue>,
+}
+
+/// Represents the timeline of a room in `SyncResponse`. These are the events of the DAG the
+/// application must build for the observed room.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Timeline {
+    #[serde(default)]
+    pub limited: bool,
+    pub prev_batch: Option<String>,
+    // TODO: Implement RoomEvent
+    #[serde(default)]
+    pub events: Vec<JsonValue>,
+}
+
+/// Represents the JSON body of a response to a `GET /_matrix/client/r0/rooms/{roomId}/messages`
+/// request.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MessagesResponse {
+    pub start: String,
+    pub end: Option<String>, // Missing once the beginning of the room has been reached
+    pub chunk: Vec<JsonValue>,
+}
+
+/// Represents the JSON body of a response to a
+/// `GET /_matrix/client/r0/rooms/{roomId}/initialSync` request, which gives the current state of a
+/// room and the latest pagination token of its timeline.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RoomInitialSync {
+    pub room_id: String,
+    pub messages: Option<PaginationChunk>,
+    #[serde(default)]
+    pub state: Vec<JsonValue>,
+}
+
+/// Represents the `messages` field in `RoomInitialSync`. Its events are in the client format, so
+/// the events of the DAG are retrieved from its tokens with `/messages` instead.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PaginationChunk {
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub chunk: Vec<JsonValue>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ContextResponse {
+    pub start: String,
+    pub end: String,
+    pub events_before: Vec<JsonValue>,
+    pub event: JsonValue,
+    pub events_after: Vec<JsonValue>,
+    pub state: Vec<JsonValue>,
+}
+
+/// Builds a request for the `.well-known` file of the server `server_name`, which gives the base
+/// URL of its homeserver when it is delegated to another host.
+pub fn well_known(server_name: &str) -> Result<ApiRequest, MatrixError> {
+    let uri = format!("https://{}/.well-known/matrix/client", server_name);
+
+    Request::get(uri.as_str())
+        .body(None)
+        .map_err(|e| MatrixError::invalid_request(&uri, &e))
+}
+
+/// Get the base URL of the homeserver of the server `server_name`. This is `base_url` if it is
+/// given, e.g. `http://localhost:8008/prefix` for a local test server, then the one discovered via
+/// `.well-known` if there is one and `https://{server_name}` otherwise. The base URLs without a
+/// scheme use HTTPS.
+pub fn base_url(server_name: &str, base_url: &str, discovered_base_url: Option<&str>) -> String {
+    let base_url = match base_url.trim() {
+        "" => discovered_base_url.unwrap_or(server_name).trim(),
+        base_url => base_url,
+    };
+    let base_url = base_url.trim_end_matches('/');
+
+    if base_url.contains("://") {
+        base_url.to_string()
+    } else {
+        format!("https://{}", base_url)
+    }
+}
+
+/// Builds a request for the login flows supported by the homeserver.
+pub fn login_flows(session: &Session) -> Result<ApiRequest, MatrixError> {
+    request::<()>(session, "GET", "/_matrix/client/r0/login", None, false)
+}
+
+/// Builds a login request with the login token of `session` if it logs in with
+/// `LoginMethod::Token`, or with its username and its password otherwise.
+pub fn login(session: &Session) -> Result<ApiRequest, MatrixError> {
+    let body = match session.login_method {
+        LoginMethod::Token => ConnectionRequest {
+            typo: String::from("m.login.token"),
+            identifier: None,
+            password: None,
+            token: Some(session.token.clone()),
+            initial_device_display_name: String::from("Matrix visualisations"),
+        },
+        _ => ConnectionRequest {
+            typo: String::from("m.login.password"),
+            identifier: Some(Identifier {
+                typo: String::from("m.id.user"),
+                user: session.username.clone(),
+            }),
+            password: Some(session.password.clone()),
+            token: None,
+            initial_device_display_name: String::from("Matrix visualisations"),
+        },
+    };
+
+    request(
+        session,
+        "POST",
+        "/_matrix/client/r0/login",
+        Some(&body),
+        false,
+    )
+}
+
+/// Builds a request registering a guest account, which can peek at world-readable rooms without
+/// any credentials.
+pub fn register_guest(session: &Session) -> Result<ApiRequest, MatrixError> {
+    request(
+        session,
+        "POST",
+        "/_matrix/client/r0/register?kind=guest",
+        Some(&serde_json::json!({})),
+        false,
+    )
+}
+
+/// Builds a request for the owner of the access token of `session`, which checks it is valid.
+pub fn whoami(session: &Session) -> Result<ApiRequest, MatrixError> {
+    request::<()>(
+        session,
+        "GET",
+        "/_matrix/client/r0/account/whoami",
+        None,
+        true,
+    )
+}
+
+/// Get the URL to which the user must be sent to log in via SSO. Once logged in, the user is
+/// sent back to `redirect_url` with a login token in the `loginToken` parameter of its query
+/// string.
+pub fn sso_redirect_url(session: &Session, redirect_url: &str) -> String {
+    format!(
+        "{}/_matrix/client/r0/login/sso/redirect?redirectUrl={}",
+        session.base_url(),
+        utf8_percent_encode(redirect_url, USERINFO_ENCODE_SET),
+    )
+}
+
+/// Builds a request to the homeserver at `base_url` for the ID of the room `room_alias` and the
+/// servers which are aware of it. This doesn't need to be authenticated.
+pub fn resolve_alias(base_url: &str, room_alias: &str) -> Result<ApiRequest, MatrixError> {
+    let uri = format!(
+        "{}/_matrix/client/r0/directory/room/{}",
+        base_url,
+        encode(room_alias)
+    );
+
+    Request::get(uri.as_str())
+        .body(None)
+        .map_err(|e| MatrixError::invalid_request(&uri, &e))
+}
+
+/// Whether `room` is an alias, e.g. `#room:example.org`, rather than the ID of a room.
+pub fn is_room_alias(room: &str) -> bool {
+    room.starts_with('#')
+}
+
+/// Builds a request for the list of the rooms joined by the user.
+pub fn joined_rooms(session: &Session) -> Result<ApiRequest, MatrixError> {
+    request::<()>(
+        session,
+        "GET",
+        "/_matrix/client/r0/joined_rooms",
+        None,
+        true,
+    )
+}
+
+/// Builds a request to join the room to observe.
+pub fn join_room(session: &Session) -> Result<ApiRequest, MatrixError> {
+    // The room is joined through the servers found when resolving its alias, if any, so it can be
+    // joined even if our server isn't in it yet
+    let mut path = format!("/_matrix/client/r0/join/{}", encode(&session.room_id));
+    for (i, server) in session.via_servers.iter().enumerate() {
+        path.push(if i == 0 { '?' } else { '&' });
+        path.push_str("server_name=");
+        path.push_str(&encode(server));
+    }
+
+    request::<()>(session, "POST", &path, None, true)
+}
+
+/// Builds a request uploading the filter of `session` to the homeserver, so `/sync` can refer to
+/// it by its ID.
+pub fn upload_filter(session: &Session) -> Result<ApiRequest, MatrixError> {
+    let path = format!(
+        "/_matrix/client/r0/user/{}/filter",
+        encode(&session.user_id)
+    );
+
+    request(
+        session,
+        "POST",
+        &path,
+        Some(&build_filter(&session.filter)),
+        true,
+    )
+}
+
+/// Builds a request for making the initial sync, or receiving the events following
+/// `next_batch_token` if it is given. The filter is given by its ID once it has been uploaded.
+pub fn sync(session: &Session, next_batch_token: Option<&str>) -> Result<ApiRequest, MatrixError> {
+    let filter = match &session.filter_id {
+        Some(filter_id) => encode(filter_id),
+        None => encode_filter(&build_filter(&session.filter)),
+    };
+
+    let mut path = format!(
+        "/_matrix/client/r0/sync?filter={}&set_presence=offline&timeout=5000",
+        filter
+    );
+    if let Some(next_batch_token) = next_batch_token {
+        path.push_str("&since=");
+        path.push_str(&encode(next_batch_token));
+    }
+
+    request::<()>(session, "GET", &path, None, true)
+}
+
+/// Builds a request for the events of the room preceding the previous batch token of `session`.
+pub fn prev_messages(session: &Session) -> Result<ApiRequest, MatrixError> {
+    let path = format!(
+        "/_matrix/client/r0/rooms/{}/messages?from={}&dir=b&filter={}",
+        encode(&session.room_id),
+        encode(session.prev_batch_token.as_deref().unwrap_or_default()),
+        encode_filter(&build_room_event_filter(&session.filter)),
+    );
+
+    request::<()>(session, "GET", &path, None, true)
+}
+
+/// Builds a request for the current state of the room and the pagination token of its latest
+/// events, which doesn't need to join the room if it is world-readable.
+pub fn room_initial_sync(session: &Session) -> Result<ApiRequest, MatrixError> {
+    let path = format!(
+        "/_matrix/client/r0/rooms/{}/initialSync",
+        encode(&session.room_id)
+    );
+
+    request::<()>(session, "GET", &path, None, true)
+}
+
+/// Builds a request for the events of the room following the next batch token of `session`, which
+/// is used instead of `/sync` to get the new events of a room which is only peeked at.
+pub fn next_messages(session: &Session) -> Result<ApiRequest, MatrixError> {
+    let path = format!(
+        "/_matrix/client/r0/rooms/{}/messages?from={}&dir=f&filter={}",
+        encode(&session.room_id),
+        encode(session.next_batch_token.as_deref().unwrap_or_default()),
+        encode_filter(&build_room_event_filter(&session.filter)),
+    );
+
+    request::<()>(session, "GET", &path, None, true)
+}
+
+/// Builds a request for the state of the room at the event `event_id`.
+pub fn room_state(session: &Session, event_id: &str) -> Result<ApiRequest, MatrixError> {
+    let path = format!(
+        "/_matrix/client/r0/rooms/{}/context/{}?limit=0",
+        encode(&session.room_id),
+        encode(event_id),
+    );
+
+    request::<()>(session, "GET", &path, None, true)
+}
+
+/// Builds a request for the event `event_id` alone, in the federation format so its `prev_events`
+/// are given. This is used to fetch the missing ancestors of the events of the DAG one by one.
+pub fn missing_event(session: &Session, event_id: &str) -> Result<ApiRequest, MatrixError> {
+    let path = format!(
+        "/_matrix/client/r0/rooms/{}/context/{}?limit=0&filter={}",
+        encode(&session.room_id),
+        encode(event_id),
+        encode_filter(&build_room_event_filter(&FilterSettings::default())),
+    );
+
+    request::<()>(session, "GET", &path, None, true)
+}
+
+/// Builds a request to leave the room which was observed.
+pub fn leave_room(session: &Session) -> Result<ApiRequest, MatrixError> {
+    let path = format!(
+        "/_matrix/client/r0/rooms/{}/leave",
+        encode(&session.room_id)
+    );
+
+    request::<()>(session, "POST", &path, None, true)
+}
+
+/// Builds a logout request.
+pub fn logout(session: &Session) -> Result<ApiRequest, MatrixError> {
+    request::<()>(session, "POST", "/_matrix/client/r0/logout", None, true)
+}
+
+/// Parses the JSON `body` of a response with the status `status`. If the request failed, or if
+/// its response can't be parsed, the error is a `MatrixError` which says it was an `error`, e.g.
+/// "error syncing".
+pub fn parse_response<T: DeserializeOwned>(
+    status: StatusCode,
+    body: &str,
+    error: &str,
+) -> Result<T, Error> {
+    check_response(status, body, error)?;
+
+    serde_json::from_str(body).map_err(|e| {
+        let mut error = MatrixError::from_response(error, status, body);
+        error.error = Some(e.to_string());
+
+        error.into()
+    })
+}
+
+/// Checks the status `status` of a response whose body isn't needed, apart from the error it
+/// gives if the request failed.
+pub fn check_response(status: StatusCode, body: &str, error: &str) -> Result<(), Error> {
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(MatrixError::from_response(error, status, body).into())
+    }
+}
+
+// Builds a request to the homeserver of `session`, authenticated with its access token if
+// `authenticated` is true. This fails if the base URL of the homeserver, which can be given by
+// the user, doesn't make a valid URI.
+fn request<T: serde::Serialize>(
+    session: &Session,
+    method: &str,
+    path: &str,
+    body: Option<&T>,
+    authenticated: bool,
+) -> Result<ApiRequest, MatrixError> {
+    let uri = format!("{}{}", session.base_url(), path);
+    let mut builder = Request::builder();
+
+    builder
+        .method(method)
+        .uri(uri.as_str())
+        .header("Content-Type", "application/json");
+
+    if authenticated {
+        builder.header(
+            "Authorization",
+            format!(
+                "Bearer {}",
+                session.access_token.as_ref().expect("No access token")
+            ),
+        );
+    }
+
+    builder
+        .body(body.map(|body| serde_json::to_string(body).unwrap()))
+        .map_err(|e| MatrixError::invalid_request(&uri, &e))
+}
+
+// The characters to encode in the IDs of the rooms and events put in the URLs. The `+` of the IDs
+// of the events must be encoded so it isn't read as a space in the query strings.
+define_encode_set! {
+    pub ID_ENCODE_SET = [USERINFO_ENCODE_SET] | {'+', '&', '$', '!'}
+}
+
+/// Encodes an ID, an alias or a pagination token so it can be put in a path or in a query string.
+pub fn encode(id: &str) -> String {
+    utf8_percent_encode(id, ID_ENCODE_SET).to_string()
+}
+
+// The fields of the events required to observe the room. Events in the federation format include
+// informations like the depth of the event in the DAG and the ID of the previous events, which
+// allows the application to properly build the events DAG of a room.
+const EVENT_FIELDS: [&str; 15] = [
+    "room_id",
+    "sender",
+    "origin",
+    "origin_server_ts",
+    "type",
+    "state_key",
+    "content",
+    "prev_events",
+    "depth",
+    "auth_events",
+    "redacts",
+    "unsigned",
+    "event_id",
+    "hashes",
+    "signatures",
+];
+
+/// Builds the filter given to `/sync`, which allows the application to get events in the
+/// federation format with only the fields required to observe the room, narrowed down by the
+/// settings of the user.
+pub fn build_filter(settings: &FilterSettings) -> JsonValue {
+    let mut timeline = event_filter_settings(settings);
+    if let Some(limit) = settings.timeline_limit {
+        timeline.insert("limit".to_string(), limit.into());
+    }
+
+    serde_json::json!({
+        "event_fields": EVENT_FIELDS,
+        "event_format": "federation",
+        "room": {
+            "timeline": timeline,
+            "state": {
+                "lazy_load_members": settings.lazy_load_members,
+            },
+        },
+    })
+}
+
+/// Builds the filter given to `/messages` and `/context`, which only take a filter of the events
+/// of a room rather than the ID of an uploaded filter.
+pub fn build_room_event_filter(settings: &FilterSettings) -> JsonValue {
+    let mut filter = event_filter_settings(settings);
+    filter.insert("event_fields".to_string(), EVENT_FIELDS[..].into());
+    filter.insert("event_format".to_string(), "federation".into());
+    if settings.lazy_load_members {
+        filter.insert("lazy_load_members".to_string(), true.into());
+    }
+
+    JsonValue::Object(filter)
+}
+
+// Get the fields of a filter of the events of a room given by `settings`, leaving out the empty
+// lists which would otherwise filter every event out.
+fn event_filter_settings(settings: &FilterSettings) -> serde_json::Map<String, JsonValue> {
+    let lists = [
+        ("types", &settings.types),
+        ("not_types", &settings.not_types),
+        ("senders", &settings.senders),
+        ("not_senders", &settings.not_senders),
+    ];
+
+    lists
+        .iter()
+        .filter(|(_, values)| !values.is_empty())
+        .map(|(key, values)| (key.to_string(), JsonValue::from(values.to_vec())))
+        .collect()
+}
+
+// Encodes `filter` so it can be put in a query string.
+fn encode_filter(filter: &JsonValue) -> String {
+    utf8_percent_encode(&serde_json::to_string(filter).unwrap(), USERINFO_ENCODE_SET).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(server_name: &str, base_url: &str) -> Session {
+        let mut session = Session::empty();
+        session.server_name = server_name.to_string();
+        session.base_url = base_url.to_string();
+        session.room_id = "!room:a.example".to_string();
+        session.access_token = Some("token".to_string());
+
+        session
+    }
+
+    #[test]
+    fn base_url_override() {
+        // An empty override leaves the server name, with HTTPS
+        assert_eq!(base_url("a.example", "", None), "https://a.example");
+        assert_eq!(base_url("a.example", "  ", None), "https://a.example");
+
+        // The path prefix of the override is kept, without its trailing `/`
+        assert_eq!(
+            base_url("a.example", "http://localhost:8008/prefix/", None),
+            "http://localhost:8008/prefix"
+        );
+
+        // The overrides without a scheme use HTTPS
+        assert_eq!(
+            base_url("a.example", "matrix.a.example:8448", None),
+            "https://matrix.a.example:8448"
+        );
+
+        // The override takes priority over the discovered base URL
+        assert_eq!(
+            base_url(
+                "a.example",
+                "http://localhost:8008",
+                Some("https://matrix.a.example")
+            ),
+            "http://localhost:8008"
+        );
+    }
+
+    #[test]
+    fn discovered_base_url() {
+        assert_eq!(
+            base_url("a.example", "", Some("https://matrix.a.example/")),
+            "https://matrix.a.example"
+        );
+        assert_eq!(
+            base_url("a.example", "", Some("matrix.a.example")),
+            "https://matrix.a.example"
+        );
+
+        let mut session = session("a.example", "");
+        session.discovered_base_url = Some("https://matrix.a.example".to_string());
+
+        assert_eq!(session.base_url(), "https://matrix.a.example");
+        assert!(!session.needs_discovery());
+    }
+
+    #[test]
+    fn encoded_ids() {
+        let mut session = session("a.example", "");
+        session.prev_batch_token = Some("t1/2?3".to_string());
+        session.next_batch_token = Some("t4&5#6".to_string());
+
+        let path = |request: Result<ApiRequest, MatrixError>| {
+            request.unwrap().uri().path_and_query().unwrap().to_string()
+        };
+
+        assert!(
+            path(room_state(&session, "$event/1:a.example")).starts_with(
+                "/_matrix/client/r0/rooms/%21room%3Aa.example/context/%24event%2F1%3Aa.example?"
+            )
+        );
+        assert!(path(prev_messages(&session)).contains("?from=t1%2F2%3F3&dir=b&"));
+        assert!(path(next_messages(&session)).contains("?from=t4%265%236&dir=f&"));
+        assert!(path(sync(&session, Some("s7&8"))).ends_with("&since=s7%268"));
+        assert_eq!(
+            path(leave_room(&session)),
+            "/_matrix/client/r0/rooms/%21room%3Aa.example/leave"
+        );
+    }
+
+    #[test]
+    fn invalid_base_url() {
+        let error = login_flows(&session("a.example", "foo bar")).unwrap_err();
+
+        assert_eq!(error.status, None);
+        assert!(!error.sent);
+        assert!(!error.is_transient());
+        assert!(error
+            .to_string()
+            .contains("https://foo bar/_matrix/client/r0/login"));
+
+        assert!(well_known("foo bar").is_err());
+        assert!(resolve_alias("https://foo bar", "#room:a.example").is_err());
+
+        let request = login_flows(&session("a.example", "")).unwrap();
+
+        assert_eq!(request.uri(), "https://a.example/_matrix/client/r0/login");
+    }
+}
@@ -1,13 +1,17 @@
-use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
-use failure::{format_err, Error};
-use serde_derive::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use failure::Error;
+use serde::de::DeserializeOwned;
 use yew::callback::Callback;
-use yew::format::{Json, Nothing};
-use yew::services::fetch::{FetchService, FetchTask, Request, Response, Uri};
-
+use yew::format::{Nothing, Text};
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+use super::api::{
+    self, ApiRequest, ConnectionResponse, ContextResponse, FilterResponse, JoinedRooms, LoginFlows,
+    MessagesResponse, RegisterResponse, RoomAliasResponse, RoomInitialSync, SyncResponse,
+    WellKnown, WhoAmIResponse,
+};
+use super::error::MatrixError;
 use super::session::Session;
 
 /// Represents the backend used to communicate with a homeserver via the Client-Server HTTP REST
@@ -17,117 +21,6 @@ pub struct CSBackend {
     session: Arc<RwLock<Session>>,
 }
 
-/// Represents the JSON body of a `POST /_matrix/client/r0/login` request.
-#[derive(Debug, Deserialize, Serialize)]
-pub struct ConnectionRequest {
-    #[serde(rename = "type")]
-    typo: String,
-    identifier: Identifier,
-    password: String,
-    initial_device_display_name: String,
-}
-
-/// Represents the `identifier` field in `ConnectionRequest`.
-#[derive(Debug, Deserialize, Serialize)]
-pub struct Identifier {
-    #[serde(rename = "type")]
-    typo: String,
-    user: String,
-}
-
-/// Represents the JSON body of a response to a `POST /_matrix/client/r0/login` request.
-#[derive(Debug, Deserialize)]
-pub struct ConnectionResponse {
-    pub user_id: String,
-    pub access_token: String,
-    pub device_id: String,
-}
-
-/// Represents the JSON body of a response to a `GET /_matrix/client/r0/joined_rooms` request.
-#[derive(Debug, Deserialize)]
-pub struct JoinedRooms {
-    pub joined_rooms: Vec<String>,
-}
-
-/// Represents the JSON body of a response to a `GET /_matrix/client/r0/sync` request.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct SyncResponse {
-    pub next_batch: String,
-    #[serde(default)]
-    pub rooms: Rooms,
-    presence: Option<JsonValue>,
-    #[serde(default)]
-    account_data: JsonValue,
-    to_device: Option<JsonValue>,
-    device_lists: Option<JsonValue>,
-    #[serde(default)]
-    device_one_time_keys_count: HashMap<String, u64>,
-}
-
-/// Represents the list of rooms in `SyncResponse`.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct Rooms {
-    #[serde(default)]
-    leave: HashMap<String, JsonValue>,
-    #[serde(default)]
-    pub join: HashMap<String, JoinedRoom>,
-    #[serde(default)]
-    invite: HashMap<String, JsonValue>,
-}
-
-/// Represents the list of rooms joined by the user in `SyncResponse`.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct JoinedRoom {
-    #[serde(default)]
-    pub unread_notifications: JsonValue,
-    #[serde(default)]
-    pub timeline: Timeline,
-    #[serde(default)]
-    pub state: State,
-    #[serde(default)]
-    pub account_data: JsonValue,
-    #[serde(default)]
-    pub ephemeral: JsonValue,
-}
-
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct State {
-    // TODO: Implement StateEvent
-    #[serde(default)]
-    pub events: Vec<JsonValue>,
-}
-
-/// Represents the timeline of a room in `SyncResponse`. These are the events of the DAG the
-/// application must build for the observed room.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct Timeline {
-    #[serde(default)]
-    pub limited: bool,
-    pub prev_batch: Option<String>,
-    // TODO: Implement RoomEvent
-    #[serde(default)]
-    pub events: Vec<JsonValue>,
-}
-
-/// Represents the JSON body of a response to a `GET /_matrix/client/r0/rooms/{roomId}/messages`
-/// request.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct MessagesResponse {
-    pub start: String,
-    pub end: String,
-    pub chunk: Vec<JsonValue>,
-}
-
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
-pub struct ContextResponse {
-    pub start: String,
-    pub end: String,
-    pub events_before: Vec<JsonValue>,
-    pub event: JsonValue,
-    pub events_after: Vec<JsonValue>,
-    pub state: Vec<JsonValue>,
-}
-
 impl CSBackend {
     /// Creates a new CS backend linked to the given `session`.
     pub fn with_session(session: Arc<RwLock<Session>>) -> Self {
@@ -137,122 +30,106 @@ impl CSBackend {
         }
     }
 
-    /// Sends a login request to the homeserver and then calls `callback` when it gets the
-    /// response.
-    pub fn connect(&mut self, callback: Callback<Result<ConnectionResponse, Error>>) -> FetchTask {
-        let (server_name, username, password) = {
-            let session = self.session.read().unwrap();
+    /// Requests the `.well-known` file of the server of the session, which gives the base URL of
+    /// its homeserver, and then calls `callback` when it gets the response.
+    pub fn well_known(
+        &mut self,
+        callback: Callback<Result<WellKnown, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::well_known(&self.session.read().unwrap().server_name);
 
-            (
-                session.server_name.clone(),
-                session.username.clone(),
-                session.password.clone(),
-            )
-        };
+        self.fetch_json(request, callback, "error discovering the homeserver")
+    }
 
-        let body = ConnectionRequest {
-            typo: String::from("m.login.password"),
-            identifier: Identifier {
-                typo: String::from("m.id.user"),
-                user: username,
-            },
-            password,
-            initial_device_display_name: String::from("Matrix visualisations"),
-        };
+    /// Sends a request to the homeserver for the login flows it supports and then calls
+    /// `callback` when it gets the response.
+    pub fn login_flows(
+        &mut self,
+        callback: Callback<Result<LoginFlows, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::login_flows(&self.session.read().unwrap());
 
-        let uri = format!("https://{}/_matrix/client/r0/login", server_name);
+        self.fetch_json(request, callback, "error retrieving the login flows")
+    }
 
-        let request = Request::post(uri)
-            .header("Content-Type", "application/json")
-            .body(Json(&body))
-            .expect("Failed to build request.");
+    /// Sends a login request to the homeserver and then calls `callback` when it gets the
+    /// response.
+    pub fn connect(
+        &mut self,
+        callback: Callback<Result<ConnectionResponse, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::login(&self.session.read().unwrap());
 
-        let handler = move |response: Response<Json<Result<ConnectionResponse, Error>>>| {
-            let (meta, Json(data)) = response.into_parts();
+        self.fetch_json(request, callback, "error connecting")
+    }
 
-            if meta.status.is_success() {
-                callback.emit(data)
-            } else {
-                callback.emit(Err(format_err!("{}: error connecting", meta.status)))
-            }
-        };
+    /// Sends a request to the homeserver to register a guest account and then calls `callback`
+    /// when it gets the response.
+    pub fn register_guest(
+        &mut self,
+        callback: Callback<Result<RegisterResponse, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::register_guest(&self.session.read().unwrap());
 
-        self.fetch.fetch(request, handler.into())
+        self.fetch_json(request, callback, "error registering a guest account")
     }
 
-    /// Sends a request to the homeserver in order to get the list of the rooms currently joined
-    /// by the user and then calls `callback` when it gets the response.
-    pub fn list_rooms(&mut self, callback: Callback<Result<JoinedRooms, Error>>) -> FetchTask {
-        let (server_name, access_token) = {
+    /// Sends a request to the homeserver to check the access token of the session, which was
+    /// given by the user instead of logging in, and then calls `callback` when it gets the
+    /// response.
+    pub fn whoami(
+        &mut self,
+        callback: Callback<Result<WhoAmIResponse, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::whoami(&self.session.read().unwrap());
+
+        self.fetch_json(request, callback, "error checking the access token")
+    }
+
+    /// Sends a request to the homeserver for the ID of the room whose alias is given in the
+    /// session and the servers to join it through, and then calls `callback` when it gets the
+    /// response.
+    pub fn resolve_alias(
+        &mut self,
+        callback: Callback<Result<RoomAliasResponse, Error>>,
+    ) -> Option<FetchTask> {
+        let request = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.access_token.clone())
+            api::resolve_alias(&session.base_url(), &session.room_id)
         };
 
-        let uri = format!("https://{}/_matrix/client/r0/joined_rooms", server_name);
+        self.fetch_json(request, callback, "error resolving the room alias")
+    }
 
-        let request = Request::get(uri)
-            .header("Content-Type", "application/json")
-            .header(
-                "Authorization",
-                format!("Bearer {}", access_token.expect("No access token")),
-            )
-            .body(Nothing)
-            .expect("Failed to build request.");
-
-        let handler = move |response: Response<Json<Result<JoinedRooms, Error>>>| {
-            let (meta, Json(data)) = response.into_parts();
-
-            if meta.status.is_success() {
-                callback.emit(data)
-            } else {
-                callback.emit(Err(format_err!(
-                    "{}: error listing joined rooms",
-                    meta.status
-                )))
-            }
-        };
+    /// Sends a request to the homeserver in order to get the list of the rooms currently joined
+    /// by the user and then calls `callback` when it gets the response.
+    pub fn list_rooms(
+        &mut self,
+        callback: Callback<Result<JoinedRooms, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::joined_rooms(&self.session.read().unwrap());
 
-        self.fetch.fetch(request, handler.into())
+        self.fetch_json(request, callback, "error listing joined rooms")
     }
 
     /// Sends a request to the homeserver to join the room to observe and then calls `callback`
     /// when it gets the response.
-    pub fn join_room(&mut self, callback: Callback<Result<(), Error>>) -> FetchTask {
-        let (server_name, access_token, room_id) = {
-            let session = self.session.read().unwrap();
+    pub fn join_room(&mut self, callback: Callback<Result<(), Error>>) -> Option<FetchTask> {
+        let request = api::join_room(&self.session.read().unwrap());
 
-            (
-                session.server_name.clone(),
-                session.access_token.clone(),
-                session.room_id.clone(),
-            )
-        };
+        self.fetch_empty(request, callback, "error joining the room")
+    }
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(format!("/_matrix/client/r0/rooms/{}/join", room_id).as_str())
-            .build()
-            .expect("Failed to build URI.");
-
-        let request = Request::post(uri)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
-            .body(Nothing)
-            .expect("Failed to build request.");
-
-        let handler = move |response: Response<Nothing>| {
-            let (meta, _) = response.into_parts();
-
-            if meta.status.is_success() {
-                callback.emit(Ok(()))
-            } else {
-                callback.emit(Err(format_err!("{}: error joining the room", meta.status)))
-            }
-        };
+    /// Uploads the filter of the session to the homeserver and then calls `callback` when it gets
+    /// the response, which gives the ID of the filter.
+    pub fn upload_filter(
+        &mut self,
+        callback: Callback<Result<FilterResponse, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::upload_filter(&self.session.read().unwrap());
 
-        self.fetch.fetch(request, handler.into())
+        self.fetch_json(request, callback, "error uploading the filter")
     }
 
     /// Sends a request to the homeserver for making the initial sync or receiving new events and
@@ -261,47 +138,10 @@ impl CSBackend {
         &mut self,
         callback: Callback<Result<SyncResponse, Error>>,
         next_batch_token: Option<String>,
-    ) -> FetchTask {
-        let (server_name, access_token) = {
-            let session = self.session.read().unwrap();
-
-            (session.server_name.clone(), session.access_token.clone())
-        };
-
-        let filter = build_filter();
-        let mut query_params = format!(
-            "/_matrix/client/r0/sync?filter={}&set_presence=offline&timeout=5000",
-            filter
-        );
-        if let Some(next_batch_token) = next_batch_token {
-            query_params.push_str("&since=");
-            query_params.push_str(&next_batch_token);
-        }
+    ) -> Option<FetchTask> {
+        let request = api::sync(&self.session.read().unwrap(), next_batch_token.as_deref());
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(query_params.as_str())
-            .build()
-            .expect("Failed to build URI.");
-
-        let request = Request::get(uri)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
-            .body(Nothing)
-            .expect("Failed to build request.");
-
-        let handler = move |response: Response<Json<Result<SyncResponse, Error>>>| {
-            let (meta, Json(data)) = response.into_parts();
-
-            if meta.status.is_success() {
-                callback.emit(data)
-            } else {
-                callback.emit(Err(format_err!("{}: error syncing", meta.status)))
-            }
-        };
-
-        self.fetch.fetch(request, handler.into())
+        self.fetch_json(request, callback, "error syncing")
     }
 
     /// Sends a request to the homeserver to get earlier events from the room to observe and then
@@ -309,209 +149,145 @@ impl CSBackend {
     pub fn get_prev_messages(
         &mut self,
         callback: Callback<Result<MessagesResponse, Error>>,
-    ) -> FetchTask {
-        let (server_name, access_token, room_id, prev_batch_token) = {
-            let session = self.session.read().unwrap();
+    ) -> Option<FetchTask> {
+        let request = api::prev_messages(&self.session.read().unwrap());
 
-            (
-                session.server_name.clone(),
-                session.access_token.clone(),
-                session.room_id.clone(),
-                session.prev_batch_token.clone(),
-            )
-        };
+        self.fetch_json(request, callback, "error retrieving previous messages")
+    }
 
-        let filter = build_filter();
-
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(
-                format!(
-                    "/_matrix/client/r0/rooms/{}/messages?from={}&dir=b&filter={}",
-                    room_id,
-                    prev_batch_token.clone().unwrap_or_default(),
-                    filter,
-                )
-                .as_str(),
-            )
-            .build()
-            .expect("Failed to build URI.");
-
-        let request = Request::get(uri)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
-            .body(Nothing)
-            .expect("Failed to build request.");
-
-        let handler = move |response: Response<Json<Result<MessagesResponse, Error>>>| {
-            let (meta, Json(data)) = response.into_parts();
-
-            if meta.status.is_success() {
-                callback.emit(data)
-            } else {
-                callback.emit(Err(format_err!(
-                    "{}: error retrieving previous messages",
-                    meta.status
-                )))
-            }
-        };
+    /// Sends a request to the homeserver for the current state of the room to peek at and then
+    /// calls `callback` when it gets the response.
+    pub fn room_initial_sync(
+        &mut self,
+        callback: Callback<Result<RoomInitialSync, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::room_initial_sync(&self.session.read().unwrap());
 
-        self.fetch.fetch(request, handler.into())
+        self.fetch_json(request, callback, "error peeking at the room")
+    }
+
+    /// Sends a request to the homeserver to get the new events of the room which is peeked at and
+    /// then calls `callback` when it gets the response.
+    pub fn get_next_messages(
+        &mut self,
+        callback: Callback<Result<MessagesResponse, Error>>,
+    ) -> Option<FetchTask> {
+        let request = api::next_messages(&self.session.read().unwrap());
+
+        self.fetch_json(request, callback, "error retrieving new messages")
     }
 
     pub fn room_state(
         &mut self,
         callback: Callback<Result<ContextResponse, Error>>,
         event_id: &str,
-    ) -> FetchTask {
-        let (server_name, access_token, room_id) = {
-            let session = self.session.read().unwrap();
+    ) -> Option<FetchTask> {
+        let request = api::room_state(&self.session.read().unwrap(), event_id);
 
-            (
-                session.server_name.clone(),
-                session.access_token.clone(),
-                session.room_id.clone(),
-            )
-        };
+        self.fetch_json(request, callback, "error retrieving the state of the room")
+    }
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(
-                format!(
-                    "/_matrix/client/r0/rooms/{}/context/{}?limit=0",
-                    room_id, event_id,
-                )
-                .as_str(),
-            )
-            .build()
-            .expect("Failed to build URI.");
-
-        let request = Request::get(uri)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
-            .body(Nothing)
-            .expect("Failed to build request.");
-
-        let handler = move |response: Response<Json<Result<ContextResponse, Error>>>| {
-            let (meta, Json(data)) = response.into_parts();
-
-            if meta.status.is_success() {
-                callback.emit(data)
-            } else {
-                callback.emit(Err(format_err!(
-                    "{}: error retrieving previous messages",
-                    meta.status
-                )))
-            }
-        };
+    /// Sends a request to the homeserver for the event `event_id`, which is missing from the DAG,
+    /// and then calls `callback` when it gets the response.
+    pub fn missing_event(
+        &mut self,
+        callback: Callback<Result<ContextResponse, Error>>,
+        event_id: &str,
+    ) -> Option<FetchTask> {
+        let request = api::missing_event(&self.session.read().unwrap(), event_id);
 
-        self.fetch.fetch(request, handler.into())
+        self.fetch_json(request, callback, "error retrieving a missing event")
     }
 
     /// Sends a request to the homeserver to leave the room which was observed and then calls
     /// `callback` when it gets the response.
-    pub fn leave_room(&mut self, callback: Callback<Result<(), Error>>) -> FetchTask {
-        let (server_name, access_token, room_id) = {
-            let session = self.session.read().unwrap();
+    pub fn leave_room(&mut self, callback: Callback<Result<(), Error>>) -> Option<FetchTask> {
+        let request = api::leave_room(&self.session.read().unwrap());
 
-            (
-                session.server_name.clone(),
-                session.access_token.clone(),
-                session.room_id.clone(),
-            )
-        };
-
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(format!("/_matrix/client/r0/rooms/{}/leave", room_id).as_str())
-            .build()
-            .expect("Failed to build URI.");
-
-        let request = Request::post(uri)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", access_token.unwrap()))
-            .body(Nothing)
-            .expect("Failed to build request.");
-
-        let handler = move |response: Response<Nothing>| {
-            let (meta, _) = response.into_parts();
-
-            if meta.status.is_success() {
-                callback.emit(Ok(()))
-            } else {
-                callback.emit(Err(format_err!("{}: error leaving the room", meta.status)))
-            }
-        };
-
-        self.fetch.fetch(request, handler.into())
+        self.fetch_empty(request, callback, "error leaving the room")
     }
 
     /// Sends a request to the homeserver to logout and then calls `callback` when it gets the
     /// response.
-    pub fn disconnect(&mut self, callback: Callback<Result<(), Error>>) -> FetchTask {
-        let (server_name, access_token) = {
-            let session = self.session.read().unwrap();
+    pub fn disconnect(&mut self, callback: Callback<Result<(), Error>>) -> Option<FetchTask> {
+        let request = api::logout(&self.session.read().unwrap());
 
-            (session.server_name.clone(), session.access_token.clone())
+        self.fetch_empty(request, callback, "error disconnecting")
+    }
+
+    // Sends `request` and then calls `callback` with its parsed JSON response. If the request
+    // couldn't be built, it isn't sent and `callback` is called with the error right away.
+    fn fetch_json<T: DeserializeOwned + 'static>(
+        &mut self,
+        request: Result<ApiRequest, MatrixError>,
+        callback: Callback<Result<T, Error>>,
+        error: &'static str,
+    ) -> Option<FetchTask> {
+        let request = match request {
+            Ok(request) => request,
+            Err(e) => {
+                callback.emit(Err(e.into()));
+                return None;
+            }
         };
 
-        let uri = format!("https://{}/_matrix/client/r0/logout", server_name);
+        let handler = move |response: Response<Text>| {
+            let (meta, body) = response.into_parts();
 
-        let request = Request::post(uri)
-            .header("Content-Type", "application/json")
-            .header(
-                "Authorization",
-                format!("Bearer {}", access_token.expect("No access token")),
+            callback.emit(
+                body.map_err(|e| MatrixError::without_response(error, &e).into())
+                    .and_then(|body| api::parse_response(meta.status, &body, error)),
             )
-            .body(Nothing)
-            .expect("Failed to build request.");
+        };
 
-        let handler = move |response: Response<Nothing>| {
-            let (meta, _) = response.into_parts();
+        Some(self.send(request, handler.into()))
+    }
 
-            if meta.status.is_success() {
-                callback.emit(Ok(()))
-            } else {
-                callback.emit(Err(format_err!("{}: error disconnecting", meta.status)))
+    // Sends `request` and then calls `callback` once it gets a response, whose body is only read
+    // for the error it gives if the request failed. Like with `fetch_json`, the requests which
+    // couldn't be built aren't sent.
+    fn fetch_empty(
+        &mut self,
+        request: Result<ApiRequest, MatrixError>,
+        callback: Callback<Result<(), Error>>,
+        error: &'static str,
+    ) -> Option<FetchTask> {
+        let request = match request {
+            Ok(request) => request,
+            Err(e) => {
+                callback.emit(Err(e.into()));
+                return None;
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        let handler = move |response: Response<Text>| {
+            let (meta, body) = response.into_parts();
+
+            callback.emit(
+                body.map_err(|e| MatrixError::without_response(error, &e).into())
+                    .and_then(|body| api::check_response(meta.status, &body, error)),
+            )
+        };
+
+        Some(self.send(request, handler.into()))
     }
-}
 
-// Builds a filter which allows the application to get events in the federation format with only
-// the fields required to observe the room. Events in the federation format includes informations
-// like the depth of the event in the DAG and the ID of the previous events, it allows the
-// application to properly build the events DAG of a room.
-fn build_filter() -> String {
-    let filter = serde_json::json!({
-        "event_fields": [
-            "room_id",
-            "sender",
-            "origin",
-            "origin_server_ts",
-            "type",
-            "state_key",
-            "content",
-            "prev_events",
-            "depth",
-            "auth_events",
-            "redacts",
-            "unsigned",
-            "event_id",
-            "hashes",
-            "signatures",
-        ],
-        "event_format": "federation",
-    });
-
-    percent_encoding::utf8_percent_encode(
-        &serde_json::to_string(&filter).unwrap(),
-        percent_encoding::USERINFO_ENCODE_SET,
-    )
-    .to_string()
+    // Sends `request` with the `FetchService`, which needs a body only for the requests which
+    // have one.
+    fn send<OUT: From<Text> + 'static>(
+        &mut self,
+        request: ApiRequest,
+        callback: Callback<Response<OUT>>,
+    ) -> FetchTask {
+        let (parts, body) = request.into_parts();
+
+        match body {
+            Some(body) => self
+                .fetch
+                .fetch(Request::from_parts(parts, Ok(body)), callback),
+            None => self
+                .fetch
+                .fetch(Request::from_parts(parts, Nothing), callback),
+        }
+    }
 }
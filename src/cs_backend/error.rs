@@ -0,0 +1,262 @@
+use std::fmt;
+
+use failure::{Error, Fail};
+use http::StatusCode;
+use serde_derive::Deserialize;
+
+/// An error which occurred while communicating with a homeserver. When the homeserver answered
+/// with an error status, the standard error of the Matrix APIs given in the JSON body of its
+/// response is parsed, if there is one.
+#[derive(Clone, Debug)]
+pub struct MatrixError {
+    pub action: String,              // What failed, e.g. "error syncing"
+    pub status: Option<StatusCode>,  // The status of the response, if the request got one
+    pub errcode: Option<String>,     // The code of the error, e.g. `M_FORBIDDEN`
+    pub error: Option<String>,       // Its description, or why the request didn't get a response
+    pub retry_after_ms: Option<u64>, // How long to wait before retrying a rate-limited request
+    pub soft_logout: bool,           // Whether the device can be kept by logging in again
+    pub sent: bool,                  // Whether the request was sent, invalid ones aren't
+}
+
+// The standard error in the body of the responses of the homeserver.
+#[derive(Deserialize)]
+struct ErrorBody {
+    errcode: String,
+    error: Option<String>,
+    retry_after_ms: Option<u64>,
+    #[serde(default)]
+    soft_logout: bool,
+}
+
+impl MatrixError {
+    /// Creates the error of a response with the status `status` and the body `body` to a request
+    /// whose failure is an `action`, e.g. "error syncing".
+    pub fn from_response(action: &str, status: StatusCode, body: &str) -> MatrixError {
+        let mut error = MatrixError {
+            action: action.to_string(),
+            status: Some(status),
+            errcode: None,
+            error: None,
+            retry_after_ms: None,
+            soft_logout: false,
+            sent: true,
+        };
+
+        if let Ok(body) = serde_json::from_str::<ErrorBody>(body) {
+            error.errcode = Some(body.errcode);
+            error.error = body.error;
+            error.retry_after_ms = body.retry_after_ms;
+            error.soft_logout = body.soft_logout;
+        }
+
+        error
+    }
+
+    /// Creates the error of a request whose failure is an `action` and which didn't get any
+    /// response because of `cause`, e.g. a network error.
+    pub fn without_response(action: &str, cause: &Error) -> MatrixError {
+        MatrixError {
+            action: action.to_string(),
+            status: None,
+            errcode: None,
+            error: Some(cause.to_string()),
+            retry_after_ms: None,
+            soft_logout: false,
+            sent: true,
+        }
+    }
+
+    /// Creates the error of a request to `uri` which couldn't be built because of `cause`, e.g.
+    /// because the base URL given by the user isn't valid. Such a request is never sent.
+    pub fn invalid_request(uri: &str, cause: &dyn fmt::Display) -> MatrixError {
+        MatrixError {
+            action: "invalid request".to_string(),
+            status: None,
+            errcode: None,
+            error: Some(format!("{}: {}", uri, cause)),
+            retry_after_ms: None,
+            soft_logout: false,
+            sent: false,
+        }
+    }
+
+    /// Whether the request may succeed if it is sent again later, i.e. if it didn't get any
+    /// response, if it failed because of the homeserver or if it was rate-limited rather than
+    /// because it was wrong.
+    pub fn is_transient(&self) -> bool {
+        if !self.sent {
+            return false;
+        }
+
+        match self.status {
+            Some(status) => {
+                status.is_server_error()
+                    || status == StatusCode::TOO_MANY_REQUESTS
+                    || self.errcode.as_deref() == Some("M_LIMIT_EXCEEDED")
+            }
+            None => true,
+        }
+    }
+
+    /// Whether the access token isn't valid anymore, so the user must log in again.
+    pub fn is_logged_out(&self) -> bool {
+        self.errcode.as_deref() == Some("M_UNKNOWN_TOKEN")
+    }
+}
+
+impl From<Error> for MatrixError {
+    /// Get the `MatrixError` in `error`, or wraps it if it didn't come from a request.
+    fn from(error: Error) -> MatrixError {
+        error
+            .downcast::<MatrixError>()
+            .unwrap_or_else(|e| MatrixError::without_response("error", &e))
+    }
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(status) = self.status {
+            write!(f, "{}: ", status)?;
+        }
+
+        write!(f, "{}", self.action)?;
+
+        match (&self.errcode, &self.error) {
+            (Some(errcode), Some(error)) => write!(f, " ({}: {})", errcode, error),
+            (Some(errcode), None) => write!(f, " ({})", errcode),
+            (None, Some(error)) => write!(f, " ({})", error),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+impl Fail for MatrixError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use failure::format_err;
+
+    #[test]
+    fn rate_limited() {
+        let body = json!({
+            "errcode": "M_LIMIT_EXCEEDED",
+            "error": "Too many requests",
+            "retry_after_ms": 2000,
+        });
+        let error = MatrixError::from_response(
+            "error syncing",
+            StatusCode::TOO_MANY_REQUESTS,
+            &body.to_string(),
+        );
+
+        assert_eq!(error.errcode.as_deref(), Some("M_LIMIT_EXCEEDED"));
+        assert_eq!(error.error.as_deref(), Some("Too many requests"));
+        assert_eq!(error.retry_after_ms, Some(2000));
+        assert!(error.is_transient());
+        assert!(!error.is_logged_out());
+        assert_eq!(
+            error.to_string(),
+            "429 Too Many Requests: error syncing (M_LIMIT_EXCEEDED: Too many requests)"
+        );
+    }
+
+    #[test]
+    fn soft_logout() {
+        let body = json!({
+            "errcode": "M_UNKNOWN_TOKEN",
+            "error": "Access token has expired",
+            "soft_logout": true,
+        });
+        let error = MatrixError::from_response(
+            "error syncing",
+            StatusCode::UNAUTHORIZED,
+            &body.to_string(),
+        );
+
+        assert!(error.soft_logout);
+        assert!(error.is_logged_out());
+        assert!(!error.is_transient());
+
+        let body = json!({ "errcode": "M_UNKNOWN_TOKEN" });
+        let error = MatrixError::from_response(
+            "error syncing",
+            StatusCode::UNAUTHORIZED,
+            &body.to_string(),
+        );
+
+        assert!(!error.soft_logout);
+        assert!(error.is_logged_out());
+    }
+
+    #[test]
+    fn non_json_body() {
+        let error = MatrixError::from_response(
+            "error joining the room",
+            StatusCode::BAD_GATEWAY,
+            "<html>Bad Gateway</html>",
+        );
+
+        assert_eq!(error.errcode, None);
+        assert_eq!(error.error, None);
+        assert_eq!(error.retry_after_ms, None);
+        assert_eq!(error.to_string(), "502 Bad Gateway: error joining the room");
+    }
+
+    #[test]
+    fn server_error() {
+        let body = json!({ "errcode": "M_UNKNOWN", "error": "Internal server error" });
+        let error = MatrixError::from_response(
+            "error syncing",
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &body.to_string(),
+        );
+
+        assert!(error.is_transient());
+        assert_eq!(error.retry_after_ms, None);
+
+        let body = json!({ "errcode": "M_FORBIDDEN", "error": "You are not invited" });
+        let error = MatrixError::from_response(
+            "error joining the room",
+            StatusCode::FORBIDDEN,
+            &body.to_string(),
+        );
+
+        assert!(!error.is_transient());
+    }
+
+    #[test]
+    fn without_response() {
+        let error = MatrixError::without_response("error syncing", &format_err!("Network error"));
+
+        assert_eq!(error.status, None);
+        assert_eq!(error.error.as_deref(), Some("Network error"));
+        assert!(error.is_transient());
+        assert_eq!(error.to_string(), "error syncing (Network error)");
+    }
+
+    #[test]
+    fn from_error() {
+        // The `MatrixError`s are taken back out of the `Error`s they were wrapped in
+        let body = json!({ "errcode": "M_FORBIDDEN" });
+        let error: Error = MatrixError::from_response(
+            "error joining the room",
+            StatusCode::FORBIDDEN,
+            &body.to_string(),
+        )
+        .into();
+        let error = MatrixError::from(error);
+
+        assert_eq!(error.status, Some(StatusCode::FORBIDDEN));
+        assert_eq!(error.errcode.as_deref(), Some("M_FORBIDDEN"));
+        assert_eq!(error.action, "error joining the room");
+
+        // The other errors are taken as errors of requests which didn't get any response
+        let error = MatrixError::from(format_err!("Invalid JSON"));
+
+        assert_eq!(error.status, None);
+        assert_eq!(error.error.as_deref(), Some("Invalid JSON"));
+        assert_eq!(error.to_string(), "error (Invalid JSON)");
+    }
+}
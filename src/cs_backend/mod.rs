@@ -1,2 +1,5 @@
+pub mod api;
+#[cfg(feature = "web")]
 pub mod backend;
+pub mod error;
 pub mod session;
@@ -1,37 +1,97 @@
+use super::api;
+
 /// Holds every informations allowing the application to communicate with the homeserver and
 /// retrieve the events of the room to observe.
 #[derive(Clone, Debug)]
 pub struct Session {
     pub server_name: String,
-    pub room_id: String,
+    pub base_url: String, // The base URL of the homeserver given by the user, if it can't be discovered
+    pub discovered_base_url: Option<String>, // The base URL of the homeserver found via `.well-known`
+    pub room_id: String, // The ID of the room, or its alias until it is resolved
+    pub via_servers: Vec<String>, // The servers to join the room through, found with its alias
+    pub peek: bool,      // Whether the room is only peeked at, without joining it
 
+    pub login_method: LoginMethod,
+    pub login_flows: Option<Vec<String>>, // The login flows supported by the homeserver, once retrieved
     pub username: String,
     pub user_id: String,
     pub password: String,
+    pub token: String, // The access token or the login token given by the user, depending on `login_method`
     pub access_token: Option<String>,
 
     pub device_id: Option<String>,
-    pub filter_id: Option<String>,
+    pub filter: FilterSettings, // The filter of the events, editable by the user
+    pub filter_id: Option<String>, // The ID of `filter` once it has been uploaded to the homeserver
     pub next_batch_token: Option<String>,
     pub prev_batch_token: Option<String>,
 }
 
+/// The ways of logging in to the homeserver.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LoginMethod {
+    Password,    // `m.login.password`, with the username and the password
+    AccessToken, // An existing access token, checked with `/account/whoami`
+    Token,       // `m.login.token`, with a login token obtained e.g. by logging in via SSO
+    Guest,       // A guest account registered for the observation, which can only peek at rooms
+}
+
+/// The settings of the filter of the events received from the homeserver, which allow to narrow
+/// the observation of noisy rooms. The lists are ignored when they are empty.
+#[derive(Clone, Debug, Default)]
+pub struct FilterSettings {
+    pub types: Vec<String>,          // The only types of events to receive
+    pub not_types: Vec<String>,      // The types of events to leave out
+    pub senders: Vec<String>,        // The only senders whose events are received
+    pub not_senders: Vec<String>,    // The senders whose events are left out
+    pub lazy_load_members: bool, // Whether the state only gives the members who sent the received events
+    pub timeline_limit: Option<u64>, // The maximal number of events of the timeline in each `/sync` response
+}
+
 impl Session {
     pub fn empty() -> Self {
         Session {
             server_name: String::new(),
+            base_url: String::new(),
+            discovered_base_url: None,
             room_id: String::new(),
+            via_servers: Vec::new(),
+            peek: false,
 
+            login_method: LoginMethod::Password,
+            login_flows: None,
             username: String::new(),
             user_id: String::new(),
             password: String::new(),
+            token: String::new(),
 
             access_token: None,
 
             device_id: None,
+            filter: FilterSettings::default(),
             filter_id: None,
             next_batch_token: None,
             prev_batch_token: None,
         }
     }
+
+    /// Get the base URL of the homeserver, to which the requests are sent.
+    pub fn base_url(&self) -> String {
+        api::base_url(
+            &self.server_name,
+            &self.base_url,
+            self.discovered_base_url.as_deref(),
+        )
+    }
+
+    /// Whether the room is peeked at rather than joined, which is always the case for the guests.
+    /// The room must then be world-readable.
+    pub fn peeks(&self) -> bool {
+        self.peek || self.login_method == LoginMethod::Guest
+    }
+
+    /// Whether the base URL of the homeserver must be discovered via `.well-known` before sending
+    /// any request to it.
+    pub fn needs_discovery(&self) -> bool {
+        self.base_url.trim().is_empty() && self.discovered_base_url.is_none()
+    }
 }
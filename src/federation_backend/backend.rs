@@ -0,0 +1,247 @@
+use std::sync::{Arc, RwLock};
+
+use failure::{format_err, Error};
+use percent_encoding::utf8_percent_encode;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use yew::callback::Callback;
+use yew::format::{Json, Nothing};
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+use super::api::x_matrix_authorization;
+use super::session::Session;
+use crate::cs_backend::api::ID_ENCODE_SET;
+
+// The maximum number of events requested with `/backfill` and `/get_missing_events`
+const EVENTS_LIMIT: u32 = 10;
+
+/// The default port of the federation API.
+pub const FEDERATION_PORT: u16 = 8448;
+
+/// Represents the backend used to retrieve the events of a room from a homeserver via the
+/// Server-Server (federation) API. The requests are signed with the signing key of the server
+/// given in the session, on whose behalf they are made.
+pub struct FederationBackend {
+    fetch: FetchService,
+    session: Arc<RwLock<Session>>,
+}
+
+/// Represents the transaction in the response to a `GET /_matrix/federation/v1/event` or a
+/// `GET /_matrix/federation/v1/backfill` request.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Transaction {
+    pub origin: String,
+    pub origin_server_ts: i64,
+    pub pdus: Vec<JsonValue>,
+}
+
+/// Represents the JSON body of a `POST /_matrix/federation/v1/get_missing_events` request.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MissingEventsRequest {
+    limit: u32,
+    min_depth: i64,
+    earliest_events: Vec<String>,
+    latest_events: Vec<String>,
+}
+
+/// Represents the response to a `POST /_matrix/federation/v1/get_missing_events` request.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MissingEventsResponse {
+    pub events: Vec<JsonValue>,
+}
+
+/// Represents the response to a `GET /_matrix/federation/v1/state_ids` request.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StateIdsResponse {
+    pub pdu_ids: Vec<String>,
+    pub auth_chain_ids: Vec<String>,
+}
+
+impl FederationBackend {
+    /// Creates a new federation backend linked to the given `session`.
+    pub fn with_session(session: Arc<RwLock<Session>>) -> Self {
+        FederationBackend {
+            fetch: FetchService::new(),
+            session,
+        }
+    }
+
+    /// Requests the event `event_id` and then calls `callback` when it gets the response.
+    pub fn event(
+        &mut self,
+        callback: Callback<Result<Transaction, Error>>,
+        event_id: &str,
+    ) -> Option<FetchTask> {
+        let path = format!("/_matrix/federation/v1/event/{}", encode_id(event_id));
+
+        self.request(callback, path, None, "retrieving the event")
+    }
+
+    /// Requests the events preceding the events `from` and then calls `callback` when it gets the
+    /// response.
+    pub fn backfill(
+        &mut self,
+        callback: Callback<Result<Transaction, Error>>,
+        from: &[String],
+    ) -> Option<FetchTask> {
+        let room_id = self.session.read().unwrap().room_id.clone();
+
+        let from: Vec<String> = from
+            .iter()
+            .map(|id| format!("v={}", encode_id(id)))
+            .collect();
+        let path = format!(
+            "/_matrix/federation/v1/backfill/{}?{}&limit={}",
+            encode_id(&room_id),
+            from.join("&"),
+            EVENTS_LIMIT
+        );
+
+        self.request(callback, path, None, "backfilling the room")
+    }
+
+    /// Requests the events missing between the events `earliest_events`, which are already known,
+    /// and the events `latest_events` and then calls `callback` when it gets the response. The
+    /// events whose depth is lower than `min_depth` are left out.
+    pub fn missing_events(
+        &mut self,
+        callback: Callback<Result<MissingEventsResponse, Error>>,
+        earliest_events: Vec<String>,
+        latest_events: Vec<String>,
+        min_depth: i64,
+    ) -> Option<FetchTask> {
+        let room_id = self.session.read().unwrap().room_id.clone();
+
+        let body = MissingEventsRequest {
+            limit: EVENTS_LIMIT,
+            min_depth,
+            earliest_events,
+            latest_events,
+        };
+        let path = format!(
+            "/_matrix/federation/v1/get_missing_events/{}",
+            encode_id(&room_id)
+        );
+
+        self.request(
+            callback,
+            path,
+            Some(serde_json::to_value(&body).unwrap()),
+            "retrieving the missing events",
+        )
+    }
+
+    /// Requests the IDs of the events of the state of the room at the event `event_id` and of
+    /// their auth chain and then calls `callback` when it gets the response.
+    pub fn state_ids(
+        &mut self,
+        callback: Callback<Result<StateIdsResponse, Error>>,
+        event_id: &str,
+    ) -> Option<FetchTask> {
+        let room_id = self.session.read().unwrap().room_id.clone();
+
+        let path = format!(
+            "/_matrix/federation/v1/state_ids/{}?event_id={}",
+            encode_id(&room_id),
+            encode_id(event_id)
+        );
+
+        self.request(callback, path, None, "retrieving the state of the room")
+    }
+
+    // Sends a request signed with the signing key of the session to `path`, with `content` as
+    // its JSON body if it is a `POST` request, and then calls `callback` when it gets the
+    // response. Without a signing key, no request is sent and `callback` is called with the
+    // error right away.
+    fn request<T>(
+        &mut self,
+        callback: Callback<Result<T, Error>>,
+        path: String,
+        content: Option<JsonValue>,
+        action: &'static str,
+    ) -> Option<FetchTask>
+    where
+        T: DeserializeOwned + 'static,
+    {
+        let (base_url, authorization) = {
+            let session = self.session.read().unwrap();
+            let method = if content.is_some() { "POST" } else { "GET" };
+
+            let signing_key = match &session.signing_key {
+                Some(signing_key) => signing_key,
+                None => {
+                    callback.emit(Err(format_err!("No signing key: error {}", action)));
+                    return None;
+                }
+            };
+
+            (
+                base_url(&session),
+                x_matrix_authorization(
+                    signing_key,
+                    &session.origin,
+                    &session.server_name,
+                    method,
+                    &path,
+                    content.as_ref(),
+                ),
+            )
+        };
+
+        let uri = base_url + &path;
+
+        let handler = move |response: Response<Json<Result<T, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                callback.emit(Err(format_err!("{}: error {}", meta.status, action)))
+            }
+        };
+
+        match content {
+            Some(content) => {
+                let request = Request::post(uri)
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", authorization)
+                    .body(Json(&content))
+                    .expect("Failed to build request.");
+
+                Some(self.fetch.fetch(request, handler.into()))
+            }
+            None => {
+                let request = Request::get(uri)
+                    .header("Content-Type", "application/json")
+                    .header("Authorization", authorization)
+                    .body(Nothing)
+                    .expect("Failed to build request.");
+
+                Some(self.fetch.fetch(request, handler.into()))
+            }
+        }
+    }
+}
+
+// Get the base URL of the federation API of the server of the session. The address of the server
+// is used if it is given, with HTTPS unless it has its own scheme so a local stand-in server can
+// be reached over HTTP. Otherwise the server is reached at its name, on the default port if it
+// doesn't have one.
+fn base_url(session: &Session) -> String {
+    let address = session.address.trim().trim_end_matches('/');
+
+    if address.contains("://") {
+        address.to_string()
+    } else if !address.is_empty() {
+        format!("https://{}", address)
+    } else if session.server_name.contains(':') {
+        format!("https://{}", session.server_name)
+    } else {
+        format!("https://{}:{}", session.server_name, FEDERATION_PORT)
+    }
+}
+
+fn encode_id(id: &str) -> String {
+    utf8_percent_encode(id, ID_ENCODE_SET).to_string()
+}
@@ -0,0 +1,4 @@
+pub mod api;
+#[cfg(feature = "web")]
+pub mod backend;
+pub mod session;
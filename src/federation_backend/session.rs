@@ -0,0 +1,26 @@
+use crate::model::crypto::SigningKey;
+
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub server_name: String, // The name of the server the room is observed from
+    pub address: String, // The address of the server if it isn't reachable at its name, e.g. a local stand-in server
+    pub origin: String,  // The name of the server on whose behalf the requests are made
+    pub signing_key: Option<SigningKey>, // The signing key of `origin`
+    pub room_id: String,
+    pub event_id: String, // The ID of the event from which the DAG is retrieved
+    pub connected: bool,
+}
+
+impl Session {
+    pub fn empty() -> Self {
+        Session {
+            server_name: String::new(),
+            address: String::new(),
+            origin: String::new(),
+            signing_key: None,
+            room_id: String::new(),
+            event_id: String::new(),
+            connected: false,
+        }
+    }
+}
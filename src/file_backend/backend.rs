@@ -0,0 +1,78 @@
+use std::sync::{Arc, RwLock};
+
+use failure::{format_err, Error};
+use stdweb::web::File;
+use yew::callback::Callback;
+use yew::services::reader::{FileData, ReaderService, ReaderTask};
+
+use super::parser::parse_events_file;
+use super::session::Session;
+use crate::mv_backend::api::EventsResponse;
+
+/// The number of events added to the DAG each time more events are loaded.
+const CHUNK_SIZE: usize = 100;
+
+/// Reads the events of a room from local files rather than from a homeserver, so captured room
+/// histories can be observed offline.
+pub struct FileBackend {
+    reader: ReaderService,
+    session: Arc<RwLock<Session>>,
+}
+
+impl FileBackend {
+    pub fn with_session(session: Arc<RwLock<Session>>) -> Self {
+        FileBackend {
+            reader: ReaderService::new(),
+            session,
+        }
+    }
+
+    /// Reads the events in `file` and emits their number. They are kept in the session until they
+    /// are taken by `next_chunk`.
+    pub fn read_events(
+        &mut self,
+        callback: Callback<Result<usize, Error>>,
+        file: File,
+    ) -> ReaderTask {
+        let session = self.session.clone();
+
+        let handler = move |data: FileData| match parse_events_file(&data.content) {
+            Ok(events) => {
+                let count = events.len();
+
+                {
+                    let mut session = session.write().unwrap();
+
+                    session.files.push(data.name);
+                    session.pending_events.extend(events);
+
+                    // The events of every files are loaded from the latest to the earliest, like
+                    // the events retrieved from a homeserver
+                    session
+                        .pending_events
+                        .sort_by_key(|ev| -ev["depth"].as_i64().unwrap_or(0));
+                }
+
+                callback.emit(Ok(count))
+            }
+            Err(e) => callback.emit(Err(format_err!("{}: {}", data.name, e))),
+        };
+
+        self.reader.read_file(file, handler.into())
+    }
+
+    /// Takes the next chunk of the events which have been read, or `None` if every events have
+    /// already been taken.
+    pub fn next_chunk(&mut self) -> Option<EventsResponse> {
+        let mut session = self.session.write().unwrap();
+
+        if session.pending_events.is_empty() {
+            return None;
+        }
+
+        let chunk_size = CHUNK_SIZE.min(session.pending_events.len());
+        let events = session.pending_events.drain(..chunk_size).collect();
+
+        Some(EventsResponse { events })
+    }
+}
@@ -0,0 +1,4 @@
+#[cfg(feature = "web")]
+pub mod backend;
+pub mod parser;
+pub mod session;
@@ -0,0 +1,118 @@
+use failure::{format_err, Error};
+use serde_json::Value as JsonValue;
+
+/// Parses the events in the content of a file. The file can either contain a JSON array of
+/// events, a JSON object with the events in its `events` field like the responses of the Matrix
+/// Visualisations backend, a single event, or one event per line (newline-delimited JSON).
+///
+/// Any other JSON object is rejected rather than taken as an event, so a file of the wrong kind
+/// doesn't end up in quarantine as a single malformed event.
+pub fn parse_events_file(content: &[u8]) -> Result<Vec<JsonValue>, Error> {
+    match serde_json::from_slice(content) {
+        Ok(JsonValue::Array(events)) => Ok(events),
+        Ok(JsonValue::Object(mut object)) => match object.remove("events") {
+            Some(JsonValue::Array(events)) => Ok(events),
+            Some(_) => Err(format_err!("The `events` field is not an array")),
+            None => {
+                let object = JsonValue::Object(object);
+
+                if is_pdu(&object) {
+                    Ok(vec![object]) // A file with a single event
+                } else {
+                    Err(format_err!("Unknown format of events file"))
+                }
+            }
+        },
+        Ok(_) => Err(format_err!("Unknown format of events file")),
+        Err(_) => {
+            let content = std::str::from_utf8(content)?;
+
+            content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| {
+                    serde_json::from_str(line)
+                        .map_err(|e| format_err!("Invalid event on line {}: {}", i + 1, e))
+                })
+                .collect()
+        }
+    }
+}
+
+// Whether `object` looks like an event as it is exchanged between homeservers, i.e. it has the
+// fields which place it in the DAG of a room.
+fn is_pdu(object: &JsonValue) -> bool {
+    object["room_id"].is_string()
+        && object["type"].is_string()
+        && object["prev_events"].is_array()
+        && object["depth"].is_number()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::testing::Pdu;
+
+    fn event(id: &str, depth: i64) -> JsonValue {
+        Pdu::new("m.room.message")
+            .id(id)
+            .content(json!({ "body": "Hello" }))
+            .depth(depth)
+            .build()
+    }
+
+    #[test]
+    fn json_array() {
+        let content = json!([event("$a", 1), event("$b", 2)]).to_string();
+
+        assert_eq!(
+            parse_events_file(content.as_bytes()).unwrap(),
+            vec![event("$a", 1), event("$b", 2)]
+        );
+        assert_eq!(parse_events_file(b"[]").unwrap(), Vec::<JsonValue>::new());
+    }
+
+    #[test]
+    fn events_object() {
+        let content = json!({ "events": [event("$a", 1), event("$b", 2)] }).to_string();
+
+        assert_eq!(
+            parse_events_file(content.as_bytes()).unwrap(),
+            vec![event("$a", 1), event("$b", 2)]
+        );
+        assert!(parse_events_file(br#"{ "events": {} }"#).is_err());
+    }
+
+    #[test]
+    fn single_event() {
+        let content = event("$a", 1).to_string();
+
+        assert_eq!(
+            parse_events_file(content.as_bytes()).unwrap(),
+            vec![event("$a", 1)]
+        );
+
+        // Objects which aren't events, e.g. the response of a `/messages` request, are rejected
+        let messages = json!({ "chunk": [event("$a", 1)], "start": "s1", "end": "s2" });
+
+        assert!(parse_events_file(messages.to_string().as_bytes()).is_err());
+        assert!(parse_events_file(b"{}").is_err());
+        assert!(parse_events_file(b"42").is_err());
+    }
+
+    #[test]
+    fn newline_delimited_json() {
+        let content = format!("{}\n\n{}\n", event("$a", 1), event("$b", 2));
+
+        assert_eq!(
+            parse_events_file(content.as_bytes()).unwrap(),
+            vec![event("$a", 1), event("$b", 2)]
+        );
+
+        let content = format!("{}\nnot an event\n", event("$a", 1));
+        let error = parse_events_file(content.as_bytes()).unwrap_err();
+
+        assert!(error.to_string().starts_with("Invalid event on line 2"));
+    }
+}
@@ -0,0 +1,24 @@
+use serde_json::Value as JsonValue;
+
+/// Holds the events read from local files which haven't been added to the DAG yet, as well as
+/// the informations about the room they come from.
+#[derive(Clone, Debug)]
+pub struct Session {
+    pub server_name: String, // The server the events were captured from, for coloring the nodes
+    pub room_id: String,
+
+    pub files: Vec<String>, // The names of the files which have been read
+    pub pending_events: Vec<JsonValue>, // The events read from the files, from the latest to the earliest
+}
+
+impl Session {
+    pub fn empty() -> Self {
+        Session {
+            server_name: String::new(),
+            room_id: String::new(),
+
+            files: Vec::new(),
+            pending_events: Vec::new(),
+        }
+    }
+}
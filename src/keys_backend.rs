@@ -0,0 +1,146 @@
+use std::net::Ipv4Addr;
+
+use failure::{format_err, Error};
+use serde_json::Value as JsonValue;
+use yew::callback::Callback;
+use yew::format::{Json, Nothing};
+use yew::services::fetch::{FetchService, FetchTask, Request, Response, Uri};
+
+use crate::federation_backend::backend::FEDERATION_PORT;
+
+/// Retrieves the public signing keys of the servers, which are needed for checking the signatures
+/// of the events.
+pub struct KeysBackend {
+    fetch: FetchService,
+}
+
+impl KeysBackend {
+    pub fn new() -> Self {
+        KeysBackend {
+            fetch: FetchService::new(),
+        }
+    }
+
+    /// Finds the address of the federation API of `server_name` and then calls `callback` with
+    /// it.
+    ///
+    /// The server names with an explicit port and the IP literals are used as they are, on the
+    /// default port of the federation API if they don't have one, so `callback` is called right
+    /// away. For the others, the `/.well-known/matrix/server` file of the server is requested
+    /// since the server may delegate its federation API to another one. Without a valid
+    /// delegation, the server is reached at its name on the default port.
+    ///
+    /// The server names come from the signatures of the events, which anyone can forge, so no
+    /// request is sent if `server_name` isn't a valid authority: `callback` is called with the
+    /// error right away.
+    pub fn resolve_server(
+        &mut self,
+        callback: Callback<Result<String, Error>>,
+        server_name: &str,
+    ) -> Option<FetchTask> {
+        // Building the URI of the `.well-known` file also checks that the server name is valid
+        let uri = match Uri::builder()
+            .scheme("https")
+            .authority(server_name)
+            .path_and_query("/.well-known/matrix/server")
+            .build()
+        {
+            Ok(uri) => uri,
+            Err(e) => {
+                callback.emit(Err(format_err!("Invalid server name: {}", e)));
+                return None;
+            }
+        };
+
+        if has_port(server_name) || is_ip_literal(server_name) {
+            callback.emit(Ok(with_default_port(server_name)));
+            return None;
+        }
+
+        let request = Request::get(uri)
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let fallback = with_default_port(server_name);
+        let handler = move |response: Response<Json<Result<JsonValue, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            let delegated = data
+                .ok()
+                .filter(|_| meta.status.is_success())
+                .and_then(|data| data["m.server"].as_str().map(String::from))
+                .filter(|address| address.parse::<Uri>().is_ok());
+
+            match delegated {
+                Some(address) => callback.emit(Ok(with_default_port(&address))),
+                None => callback.emit(Ok(fallback.clone())),
+            }
+        };
+
+        Some(self.fetch.fetch(request, handler.into()))
+    }
+
+    /// Requests the keys published by a server via the `/_matrix/key/v2/server` endpoint of its
+    /// federation API, which is at `address` as given by `resolve_server`.
+    pub fn server_keys(
+        &mut self,
+        callback: Callback<Result<JsonValue, Error>>,
+        address: &str,
+    ) -> Option<FetchTask> {
+        let uri = match Uri::builder()
+            .scheme("https")
+            .authority(address)
+            .path_and_query("/_matrix/key/v2/server")
+            .build()
+        {
+            Ok(uri) => uri,
+            Err(e) => {
+                callback.emit(Err(format_err!("Invalid server address: {}", e)));
+                return None;
+            }
+        };
+
+        let request = Request::get(uri)
+            .header("Content-Type", "application/json")
+            .body(Nothing)
+            .expect("Failed to build request.");
+
+        let handler = move |response: Response<Json<Result<JsonValue, Error>>>| {
+            let (meta, Json(data)) = response.into_parts();
+
+            if meta.status.is_success() {
+                callback.emit(data)
+            } else {
+                callback.emit(Err(format_err!(
+                    "{}: error fetching the server keys",
+                    meta.status
+                )))
+            }
+        };
+
+        Some(self.fetch.fetch(request, handler.into()))
+    }
+}
+
+// Whether the server name or address `name` has an explicit port.
+fn has_port(name: &str) -> bool {
+    if name.starts_with('[') {
+        name.contains("]:")
+    } else {
+        name.contains(':')
+    }
+}
+
+// Whether the server name `name` is an IPv4 or IPv6 literal rather than a hostname.
+fn is_ip_literal(name: &str) -> bool {
+    name.starts_with('[') || name.parse::<Ipv4Addr>().is_ok()
+}
+
+// Adds the default port of the federation API to the address `address` if it has no port.
+fn with_default_port(address: &str) -> String {
+    if has_port(address) {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, FEDERATION_PORT)
+    }
+}
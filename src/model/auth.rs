@@ -0,0 +1,704 @@
+use std::collections::HashMap;
+
+use serde_json::Value as JsonValue;
+
+use super::event::Event;
+use super::room_version::RoomVersion;
+
+/// The key of an entry of the state of a room: the type of a state event and its state key.
+pub type StateKey = (String, String);
+
+/// The state events against which an event is authorized, indexed by their `StateKey`.
+pub type AuthEvents<'a> = HashMap<StateKey, &'a Event>;
+
+const CREATE: &str = "m.room.create";
+const POWER_LEVELS: &str = "m.room.power_levels";
+const JOIN_RULES: &str = "m.room.join_rules";
+const MEMBER: &str = "m.room.member";
+const THIRD_PARTY_INVITE: &str = "m.room.third_party_invite";
+
+/// Returns the `StateKey` of `event` if it is a state event.
+pub fn state_key(event: &Event) -> Option<StateKey> {
+    event
+        .state_key
+        .as_ref()
+        .map(|state_key| (event.etype.clone(), state_key.clone()))
+}
+
+/// Returns the `StateKey`s of the state events which are needed to authorize `event`.
+pub fn auth_types_for_event(event: &Event, version: RoomVersion) -> Vec<StateKey> {
+    if event.etype == CREATE {
+        return Vec::new();
+    }
+
+    let mut auth_types = vec![
+        (CREATE.to_string(), String::new()),
+        (POWER_LEVELS.to_string(), String::new()),
+        (MEMBER.to_string(), event.sender.clone()),
+    ];
+
+    if event.etype == MEMBER {
+        if let Some(target) = &event.state_key {
+            let membership = membership(event);
+
+            auth_types.push((MEMBER.to_string(), target.clone()));
+
+            if membership == "join" || membership == "invite" || membership == "knock" {
+                auth_types.push((JOIN_RULES.to_string(), String::new()));
+            }
+
+            if membership == "invite" {
+                if let Some(token) = event
+                    .content
+                    .pointer("/third_party_invite/signed/token")
+                    .and_then(JsonValue::as_str)
+                {
+                    auth_types.push((THIRD_PARTY_INVITE.to_string(), token.to_string()));
+                }
+            }
+
+            if membership == "join" && version.restricted_join() {
+                if let Some(authoriser) = event
+                    .content
+                    .get("join_authorised_via_users_server")
+                    .and_then(JsonValue::as_str)
+                {
+                    auth_types.push((MEMBER.to_string(), authoriser.to_string()));
+                }
+            }
+        }
+    }
+
+    auth_types
+}
+
+/// Checks whether `event` is allowed by the authorization rules of the room version `version`,
+/// given the state events in `auth_events`.
+///
+/// The signatures of the events aren't verified here.
+pub fn check(event: &Event, auth_events: &AuthEvents, version: RoomVersion) -> bool {
+    if event.etype == CREATE {
+        return event.get_prev_events().is_empty()
+            && domain(&event.sender) == domain(&event.room_id);
+    }
+
+    let create_event = match auth_events.get(&(CREATE.to_string(), String::new())) {
+        Some(create_event) => create_event,
+        None => return false,
+    };
+
+    if create_event.content.get("m.federate") == Some(&JsonValue::Bool(false))
+        && domain(&event.sender) != domain(&create_event.sender)
+    {
+        return false;
+    }
+
+    if event.etype == "m.room.aliases" && version.special_cased_aliases() {
+        return match &event.state_key {
+            Some(state_key) => domain(&event.sender) == state_key,
+            None => false,
+        };
+    }
+
+    if event.etype == MEMBER {
+        return check_membership(event, create_event, auth_events, version);
+    }
+
+    if get_membership(&event.sender, auth_events) != "join" {
+        return false;
+    }
+
+    let sender_level = get_user_power_level(&event.sender, auth_events, version);
+
+    if event.etype == THIRD_PARTY_INVITE {
+        return sender_level >= get_named_level(auth_events, "invite", 0);
+    }
+
+    if sender_level < get_send_level(event, auth_events) {
+        return false;
+    }
+
+    if let Some(state_key) = &event.state_key {
+        if state_key.starts_with('@') && *state_key != event.sender {
+            return false;
+        }
+    }
+
+    if event.etype == POWER_LEVELS {
+        return check_power_levels(event, sender_level, auth_events, version);
+    }
+
+    if event.etype == "m.room.redaction" && version.special_cased_redactions() {
+        if sender_level >= get_named_level(auth_events, "redact", 50) {
+            return true;
+        }
+
+        return match &event.redacts {
+            Some(redacts) => domain(redacts) == domain(&event.sender),
+            None => false,
+        };
+    }
+
+    true
+}
+
+// Applies the authorization rules specific to `m.room.member` events.
+fn check_membership(
+    event: &Event,
+    create_event: &Event,
+    auth_events: &AuthEvents,
+    version: RoomVersion,
+) -> bool {
+    let target = match &event.state_key {
+        Some(target) => target,
+        None => return false,
+    };
+    let membership = match event.content.get("membership").and_then(JsonValue::as_str) {
+        Some(membership) => membership,
+        None => return false,
+    };
+
+    let sender_membership = get_membership(&event.sender, auth_events);
+    let target_membership = get_membership(target, auth_events);
+    let join_rule = auth_events
+        .get(&(JOIN_RULES.to_string(), String::new()))
+        .and_then(|ev| ev.content.get("join_rule"))
+        .and_then(JsonValue::as_str)
+        .unwrap_or("invite");
+
+    let sender_level = get_user_power_level(&event.sender, auth_events, version);
+    let target_level = get_user_power_level(target, auth_events, version);
+
+    match membership {
+        "join" => {
+            // The creator of the room joins it right after its creation
+            let prev_events = event.get_prev_events();
+            if prev_events.len() == 1
+                && prev_events[0] == create_event.event_id
+                && *target == get_creator(create_event, version)
+            {
+                return true;
+            }
+
+            if event.sender != *target || target_membership == "ban" {
+                return false;
+            }
+
+            match join_rule {
+                "public" => true,
+                "invite" => target_membership == "join" || target_membership == "invite",
+                "knock" if version.knocking() => {
+                    target_membership == "join" || target_membership == "invite"
+                }
+                "restricted" if version.restricted_join() => {
+                    check_restricted_join(event, target_membership, auth_events, version)
+                }
+                "knock_restricted" if version.knock_restricted_join() => {
+                    check_restricted_join(event, target_membership, auth_events, version)
+                }
+                _ => false,
+            }
+        }
+        "invite" => {
+            if let Some(third_party_invite) = event.content.get("third_party_invite") {
+                return check_third_party_invite(event, target, third_party_invite, auth_events);
+            }
+
+            if sender_membership != "join"
+                || target_membership == "join"
+                || target_membership == "ban"
+            {
+                return false;
+            }
+
+            sender_level >= get_named_level(auth_events, "invite", 0)
+        }
+        "leave" => {
+            if event.sender == *target {
+                return sender_membership == "join"
+                    || sender_membership == "invite"
+                    || (version.knocking() && sender_membership == "knock");
+            }
+
+            if sender_membership != "join" {
+                return false;
+            }
+
+            if target_membership == "ban" && sender_level < get_named_level(auth_events, "ban", 50)
+            {
+                return false;
+            }
+
+            sender_level >= get_named_level(auth_events, "kick", 50) && target_level < sender_level
+        }
+        "ban" => {
+            sender_membership == "join"
+                && sender_level >= get_named_level(auth_events, "ban", 50)
+                && target_level < sender_level
+        }
+        "knock" if version.knocking() => {
+            let knock_allowed = join_rule == "knock"
+                || (version.knock_restricted_join() && join_rule == "knock_restricted");
+
+            knock_allowed
+                && event.sender == *target
+                && target_membership != "ban"
+                && target_membership != "join"
+                && target_membership != "invite"
+        }
+        _ => false,
+    }
+}
+
+// A user can join a restricted room if it was already in it or invited to it, or if a member of
+// the room with the power to invite authorised the join.
+fn check_restricted_join(
+    event: &Event,
+    target_membership: &str,
+    auth_events: &AuthEvents,
+    version: RoomVersion,
+) -> bool {
+    if target_membership == "join" || target_membership == "invite" {
+        return true;
+    }
+
+    match event
+        .content
+        .get("join_authorised_via_users_server")
+        .and_then(JsonValue::as_str)
+    {
+        Some(authoriser) => {
+            get_membership(authoriser, auth_events) == "join"
+                && get_user_power_level(authoriser, auth_events, version)
+                    >= get_named_level(auth_events, "invite", 0)
+        }
+        None => false,
+    }
+}
+
+// The signature of the third party invite isn't verified, only its consistency with the
+// `m.room.third_party_invite` event it refers to.
+fn check_third_party_invite(
+    event: &Event,
+    target: &str,
+    third_party_invite: &JsonValue,
+    auth_events: &AuthEvents,
+) -> bool {
+    if get_membership(target, auth_events) == "ban" {
+        return false;
+    }
+
+    let signed = match third_party_invite.get("signed") {
+        Some(signed) => signed,
+        None => return false,
+    };
+
+    let mxid = signed.get("mxid").and_then(JsonValue::as_str);
+    let token = signed.get("token").and_then(JsonValue::as_str);
+
+    match (mxid, token) {
+        (Some(mxid), Some(token)) if mxid == target => auth_events
+            .get(&(THIRD_PARTY_INVITE.to_string(), token.to_string()))
+            .is_some_and(|invite| invite.sender == event.sender),
+        _ => false,
+    }
+}
+
+// Applies the authorization rules specific to `m.room.power_levels` events.
+fn check_power_levels(
+    event: &Event,
+    sender_level: i64,
+    auth_events: &AuthEvents,
+    version: RoomVersion,
+) -> bool {
+    if version.integer_power_levels() && !has_integer_levels(&event.content) {
+        return false;
+    }
+
+    let current = match auth_events.get(&(POWER_LEVELS.to_string(), String::new())) {
+        Some(current) => &current.content,
+        None => return true,
+    };
+    let new = &event.content;
+
+    let named_levels = [
+        "users_default",
+        "events_default",
+        "state_default",
+        "ban",
+        "redact",
+        "kick",
+        "invite",
+    ];
+
+    for name in named_levels.iter() {
+        let old_level = current.get(*name).and_then(as_power_level);
+        let new_level = new.get(*name).and_then(as_power_level);
+
+        if !level_change_allowed(old_level, new_level, sender_level) {
+            return false;
+        }
+    }
+
+    for field in ["events", "notifications"].iter() {
+        for key in object_keys(current.get(*field), new.get(*field)) {
+            let old_level = current
+                .get(*field)
+                .and_then(|levels| levels.get(&key))
+                .and_then(as_power_level);
+            let new_level = new
+                .get(*field)
+                .and_then(|levels| levels.get(&key))
+                .and_then(as_power_level);
+
+            if !level_change_allowed(old_level, new_level, sender_level) {
+                return false;
+            }
+        }
+    }
+
+    for user in object_keys(current.get("users"), new.get("users")) {
+        let old_level = current
+            .get("users")
+            .and_then(|users| users.get(&user))
+            .and_then(as_power_level);
+        let new_level = new
+            .get("users")
+            .and_then(|users| users.get(&user))
+            .and_then(as_power_level);
+
+        if old_level == new_level {
+            continue;
+        }
+
+        // A user can't change the power level of another user with the same power level or a
+        // higher one
+        if user != event.sender && old_level.is_some_and(|level| level >= sender_level) {
+            return false;
+        }
+
+        if new_level.is_some_and(|level| level > sender_level) {
+            return false;
+        }
+    }
+
+    true
+}
+
+// A power level can only be added, removed or changed by a user whose power level is at least as
+// high as the old and the new values.
+fn level_change_allowed(old_level: Option<i64>, new_level: Option<i64>, sender_level: i64) -> bool {
+    old_level == new_level
+        || (old_level.is_none_or(|level| level <= sender_level)
+            && new_level.is_none_or(|level| level <= sender_level))
+}
+
+fn has_integer_levels(content: &JsonValue) -> bool {
+    let is_integer = |value: &JsonValue| value.is_i64();
+
+    let named_levels_ok = [
+        "users_default",
+        "events_default",
+        "state_default",
+        "ban",
+        "redact",
+        "kick",
+        "invite",
+    ]
+    .iter()
+    .filter_map(|name| content.get(*name))
+    .all(is_integer);
+
+    let maps_ok = ["events", "notifications", "users"]
+        .iter()
+        .filter_map(|field| content.get(*field))
+        .all(|levels| match levels.as_object() {
+            Some(levels) => levels.values().all(is_integer),
+            None => false,
+        });
+
+    named_levels_ok && maps_ok
+}
+
+fn object_keys(old: Option<&JsonValue>, new: Option<&JsonValue>) -> Vec<String> {
+    let mut keys: Vec<String> = old
+        .and_then(JsonValue::as_object)
+        .into_iter()
+        .chain(new.and_then(JsonValue::as_object))
+        .flat_map(|object| object.keys().cloned())
+        .collect();
+
+    keys.sort();
+    keys.dedup();
+
+    keys
+}
+
+/// Returns the power level of `user_id` according to the `m.room.power_levels` event in
+/// `auth_events`. Without such an event, the creator of the room has the power level 100 and the
+/// other users have the power level 0.
+pub fn get_user_power_level(user_id: &str, auth_events: &AuthEvents, version: RoomVersion) -> i64 {
+    match auth_events.get(&(POWER_LEVELS.to_string(), String::new())) {
+        Some(power_levels) => power_levels
+            .content
+            .get("users")
+            .and_then(|users| users.get(user_id))
+            .and_then(as_power_level)
+            .or_else(|| {
+                power_levels
+                    .content
+                    .get("users_default")
+                    .and_then(as_power_level)
+            })
+            .unwrap_or(0),
+        None => match auth_events.get(&(CREATE.to_string(), String::new())) {
+            Some(create_event) if get_creator(create_event, version) == user_id => 100,
+            _ => 0,
+        },
+    }
+}
+
+// Returns the power level required to send `event`.
+fn get_send_level(event: &Event, auth_events: &AuthEvents) -> i64 {
+    let power_levels = match auth_events.get(&(POWER_LEVELS.to_string(), String::new())) {
+        Some(power_levels) => &power_levels.content,
+        None => return 0,
+    };
+
+    power_levels
+        .get("events")
+        .and_then(|events| events.get(&event.etype))
+        .and_then(as_power_level)
+        .unwrap_or_else(|| {
+            if event.state_key.is_some() {
+                get_named_level(auth_events, "state_default", 50)
+            } else {
+                get_named_level(auth_events, "events_default", 0)
+            }
+        })
+}
+
+fn get_named_level(auth_events: &AuthEvents, name: &str, default: i64) -> i64 {
+    auth_events
+        .get(&(POWER_LEVELS.to_string(), String::new()))
+        .and_then(|power_levels| power_levels.content.get(name))
+        .and_then(as_power_level)
+        .unwrap_or(default)
+}
+
+fn get_membership<'a>(user_id: &str, auth_events: &AuthEvents<'a>) -> &'a str {
+    auth_events
+        .get(&(MEMBER.to_string(), user_id.to_string()))
+        .map(|member| membership(member))
+        .unwrap_or("leave")
+}
+
+fn get_creator(create_event: &Event, version: RoomVersion) -> &str {
+    if version.creator_is_sender() {
+        &create_event.sender
+    } else {
+        create_event
+            .content
+            .get("creator")
+            .and_then(JsonValue::as_str)
+            .unwrap_or(&create_event.sender)
+    }
+}
+
+fn membership(event: &Event) -> &str {
+    event
+        .content
+        .get("membership")
+        .and_then(JsonValue::as_str)
+        .unwrap_or("")
+}
+
+// Power levels used to be strings in some old rooms, so both forms are accepted.
+fn as_power_level(value: &JsonValue) -> Option<i64> {
+    match value {
+        JsonValue::Number(n) => n.as_i64(),
+        JsonValue::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn domain(id: &str) -> &str {
+    match id.find(':') {
+        Some(idx) => &id[idx + 1..],
+        None => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::testing::Pdu;
+
+    const VERSION: RoomVersion = RoomVersion::V6;
+
+    fn event(id: &str, etype: &str, sender: &str, state_key: &str, content: JsonValue) -> Event {
+        let json = Pdu::new(etype)
+            .id(id)
+            .sender(sender)
+            .state_key(state_key)
+            .content(content)
+            .build();
+
+        Event::from_json(&json, Some(VERSION)).unwrap()
+    }
+
+    fn member(id: &str, user_id: &str, membership: &str) -> Event {
+        event(
+            id,
+            MEMBER,
+            user_id,
+            user_id,
+            json!({ "membership": membership }),
+        )
+    }
+
+    // A room created by Alice, where Bob is a moderator and Carol a simple member.
+    fn room_events() -> Vec<Event> {
+        vec![
+            event(
+                "$create",
+                CREATE,
+                "@alice:a.example",
+                "",
+                json!({ "room_version": "6" }),
+            ),
+            event(
+                "$power_levels",
+                POWER_LEVELS,
+                "@alice:a.example",
+                "",
+                json!({
+                    "users": { "@alice:a.example": 100, "@bob:b.example": 50 },
+                    "users_default": 0,
+                    "ban": 50,
+                    "kick": 50,
+                    "state_default": 50,
+                }),
+            ),
+            event(
+                "$join_rules",
+                JOIN_RULES,
+                "@alice:a.example",
+                "",
+                json!({ "join_rule": "public" }),
+            ),
+            member("$alice", "@alice:a.example", "join"),
+            member("$bob", "@bob:b.example", "join"),
+            member("$carol", "@carol:c.example", "join"),
+        ]
+    }
+
+    fn auth_map(events: &[Event]) -> AuthEvents<'_> {
+        events
+            .iter()
+            .map(|ev| (state_key(ev).unwrap(), ev))
+            .collect()
+    }
+
+    fn power_levels(sender: &str, users: JsonValue) -> Event {
+        event(
+            "$new_power_levels",
+            POWER_LEVELS,
+            sender,
+            "",
+            json!({ "users": users, "users_default": 0, "ban": 50, "kick": 50, "state_default": 50 }),
+        )
+    }
+
+    fn membership_change(sender: &str, target: &str, membership: &str) -> Event {
+        event(
+            "$membership",
+            MEMBER,
+            sender,
+            target,
+            json!({ "membership": membership }),
+        )
+    }
+
+    #[test]
+    fn power_levels_changes() {
+        let events = room_events();
+        let auth_events = auth_map(&events);
+
+        // The creator can promote a moderator to its own level
+        let promotion = power_levels(
+            "@alice:a.example",
+            json!({ "@alice:a.example": 100, "@bob:b.example": 100 }),
+        );
+        assert!(check(&promotion, &auth_events, VERSION));
+
+        // A moderator can't raise its own level above its current one
+        let self_promotion = power_levels(
+            "@bob:b.example",
+            json!({ "@alice:a.example": 100, "@bob:b.example": 100 }),
+        );
+        assert!(!check(&self_promotion, &auth_events, VERSION));
+
+        // Nor demote a user with a higher level
+        let demotion = power_levels(
+            "@bob:b.example",
+            json!({ "@alice:a.example": 0, "@bob:b.example": 50 }),
+        );
+        assert!(!check(&demotion, &auth_events, VERSION));
+
+        // But it can give its own level to a member
+        let moderator = power_levels(
+            "@bob:b.example",
+            json!({ "@alice:a.example": 100, "@bob:b.example": 50, "@carol:c.example": 50 }),
+        );
+        assert!(check(&moderator, &auth_events, VERSION));
+
+        // A member can't change the power levels, whose `state_default` is 50
+        let by_member = power_levels(
+            "@carol:c.example",
+            json!({ "@alice:a.example": 100, "@bob:b.example": 50 }),
+        );
+        assert!(!check(&by_member, &auth_events, VERSION));
+
+        // Power levels can be strings until rooms v10, where they must be integers
+        let string_level = power_levels(
+            "@alice:a.example",
+            json!({ "@alice:a.example": "100", "@bob:b.example": 50 }),
+        );
+        assert!(check(&string_level, &auth_events, VERSION));
+        assert!(!check(&string_level, &auth_events, RoomVersion::V10));
+    }
+
+    #[test]
+    fn bans_and_kicks() {
+        let events = room_events();
+        let auth_events = auth_map(&events);
+
+        // A moderator can ban and kick a member
+        let ban = membership_change("@bob:b.example", "@carol:c.example", "ban");
+        assert!(check(&ban, &auth_events, VERSION));
+        let kick = membership_change("@bob:b.example", "@carol:c.example", "leave");
+        assert!(check(&kick, &auth_events, VERSION));
+
+        // But not a user with a higher level
+        let ban = membership_change("@bob:b.example", "@alice:a.example", "ban");
+        assert!(!check(&ban, &auth_events, VERSION));
+
+        // A member can't ban nor kick anyone
+        let ban = membership_change("@carol:c.example", "@bob:b.example", "ban");
+        assert!(!check(&ban, &auth_events, VERSION));
+        let kick = membership_change("@carol:c.example", "@bob:b.example", "leave");
+        assert!(!check(&kick, &auth_events, VERSION));
+
+        // A banned user can't join again, even in a public room
+        let mut banned_events = events.clone();
+        banned_events[5] = membership_change("@bob:b.example", "@carol:c.example", "ban");
+        let banned_auth_events = auth_map(&banned_events);
+        let join = member("$join", "@carol:c.example", "join");
+        assert!(!check(&join, &banned_auth_events, VERSION));
+
+        // The moderator can lift the ban
+        let unban = membership_change("@bob:b.example", "@carol:c.example", "leave");
+        assert!(check(&unban, &banned_auth_events, VERSION));
+    }
+}
@@ -0,0 +1,869 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signature, Signer, Verifier};
+use failure::{format_err, Error};
+use serde_derive::Serialize;
+use serde_json::{Map, Value as JsonValue};
+use sha2::{Digest, Sha256};
+
+use super::room_version::{EventFormat, RoomVersion};
+
+/// The result of the check of the content hash of an event.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashCheck {
+    Verified, // The SHA-256 content hash of the event matches the event
+    Mismatch, // The content hash doesn't match, the event has been redacted or tampered with
+    #[default]
+    Unverifiable, // The event has no SHA-256 content hash or the version of the room is unknown
+}
+
+/// The result of the check of the signatures of an event.
+///
+/// The variants are declared from the best to the worst result, so the result of the check of
+/// several signatures is the maximum of their results.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureCheck {
+    Verified, // The event is signed by every server which had to sign it
+    #[default]
+    Unverifiable, // The keys of a server or the version of the room are unknown
+    Missing,  // A server which had to sign the event didn't
+    Bad,      // A signature doesn't match the event
+}
+
+/// The public signing keys of the servers, used to check the signatures of the events.
+#[derive(Clone, Default)]
+pub struct ServerKeys {
+    keys: HashMap<String, HashMap<String, (PublicKey, KeyValidity)>>, // The keys of each server, by key ID
+}
+
+// Until when a key of a server can be used to check the signatures of the events.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum KeyValidity {
+    Current(Option<i64>), // A current key, valid until the `valid_until_ts` of the keys, if any
+    Expired(i64),         // An old key, only valid for the events sent before its `expired_ts`
+}
+
+/// The private signing key of a server, used to sign the requests made on its behalf over
+/// federation.
+#[derive(Clone)]
+pub struct SigningKey {
+    pub key_id: String, // The ID of the key, e.g. `ed25519:a_abcd`
+    seed: [u8; 32],     // The seed of the Ed25519 secret key
+}
+
+impl fmt::Display for HashCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HashCheck::Verified => write!(f, "verified"),
+            HashCheck::Mismatch => write!(f, "mismatch"),
+            HashCheck::Unverifiable => write!(f, "unverifiable"),
+        }
+    }
+}
+
+impl fmt::Display for SignatureCheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureCheck::Verified => write!(f, "verified"),
+            SignatureCheck::Unverifiable => write!(f, "unverifiable"),
+            SignatureCheck::Missing => write!(f, "missing"),
+            SignatureCheck::Bad => write!(f, "bad"),
+        }
+    }
+}
+
+/// Encodes `value` in the canonical JSON format of Matrix: objects have their keys sorted and
+/// there is no insignificant whitespace.
+pub fn canonical_json(value: &JsonValue) -> String {
+    let mut json = String::new();
+
+    write_canonical_json(value, &mut json);
+
+    json
+}
+
+fn write_canonical_json(value: &JsonValue, json: &mut String) {
+    match value {
+        JsonValue::Object(object) => {
+            let mut entries: Vec<(&String, &JsonValue)> = object.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+
+            json.push('{');
+
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+
+                json.push_str(&JsonValue::String(key.clone()).to_string());
+                json.push(':');
+                write_canonical_json(value, json);
+            }
+
+            json.push('}');
+        }
+        JsonValue::Array(values) => {
+            json.push('[');
+
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+
+                write_canonical_json(value, json);
+            }
+
+            json.push(']');
+        }
+        _ => json.push_str(&value.to_string()),
+    }
+}
+
+/// Returns the redacted form of the JSON body of an event, following the redaction algorithm of
+/// the room version `version`.
+pub fn redact(event: &JsonValue, version: RoomVersion) -> JsonValue {
+    let mut kept_keys = vec![
+        "event_id",
+        "type",
+        "room_id",
+        "sender",
+        "state_key",
+        "content",
+        "hashes",
+        "signatures",
+        "depth",
+        "prev_events",
+        "auth_events",
+        "origin_server_ts",
+    ];
+
+    if !version.updated_redaction_rules() {
+        kept_keys.extend(&["origin", "membership", "prev_state"]);
+    }
+
+    let mut redacted = Map::new();
+
+    if let Some(object) = event.as_object() {
+        for key in kept_keys {
+            if let Some(value) = object.get(key) {
+                redacted.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+
+    let etype = event.get("type").and_then(JsonValue::as_str).unwrap_or("");
+    let content = event.get("content").and_then(JsonValue::as_object);
+
+    let kept_content_keys: Vec<&str> = match etype {
+        "m.room.member" if version.redaction_keeps_join_authoriser() => {
+            vec!["membership", "join_authorised_via_users_server"]
+        }
+        "m.room.member" => vec!["membership"],
+        "m.room.create" if version.updated_redaction_rules() => content
+            .map(|content| content.keys().map(String::as_str).collect())
+            .unwrap_or_default(),
+        "m.room.create" => vec!["creator"],
+        "m.room.join_rules" if version.restricted_join() => vec!["join_rule", "allow"],
+        "m.room.join_rules" => vec!["join_rule"],
+        "m.room.power_levels" => {
+            let mut keys = vec![
+                "ban",
+                "events",
+                "events_default",
+                "kick",
+                "redact",
+                "state_default",
+                "users",
+                "users_default",
+            ];
+
+            if version.updated_redaction_rules() {
+                keys.push("invite");
+            }
+
+            keys
+        }
+        "m.room.aliases" if version.special_cased_aliases() => vec!["aliases"],
+        "m.room.history_visibility" => vec!["history_visibility"],
+        "m.room.redaction" if version.updated_redaction_rules() => vec!["redacts"],
+        _ => Vec::new(),
+    };
+
+    let mut redacted_content = Map::new();
+
+    if let Some(content) = content {
+        for key in kept_content_keys {
+            if let Some(value) = content.get(key) {
+                redacted_content.insert(key.to_string(), value.clone());
+            }
+        }
+
+        // Only the `signed` part of a third party invite is kept since rooms v11
+        if etype == "m.room.member" && version.updated_redaction_rules() {
+            if let Some(signed) = content
+                .get("third_party_invite")
+                .and_then(|i| i.get("signed"))
+            {
+                redacted_content.insert(
+                    "third_party_invite".to_string(),
+                    json!({ "signed": signed.clone() }),
+                );
+            }
+        }
+    }
+
+    redacted.insert("content".to_string(), JsonValue::Object(redacted_content));
+
+    JsonValue::Object(redacted)
+}
+
+impl ServerKeys {
+    pub fn new() -> ServerKeys {
+        ServerKeys::default()
+    }
+
+    /// Adds the keys from the body of a response to a `GET /_matrix/key/v2/server` request. Key
+    /// files can also contain an array of such bodies or the body of a response to a
+    /// `/_matrix/key/v2/query` request, which lists them in its `server_keys` field.
+    ///
+    /// Returns the names of the servers whose keys have been added.
+    pub fn add_keys_json(&mut self, json: &JsonValue) -> Result<Vec<String>, Error> {
+        match json {
+            JsonValue::Array(responses) => responses
+                .iter()
+                .map(|res| self.add_server_keys(res))
+                .collect(),
+            JsonValue::Object(object) => match object.get("server_keys") {
+                Some(server_keys) => self.add_keys_json(server_keys),
+                None => self.add_server_keys(json).map(|server| vec![server]),
+            },
+            _ => Err(format_err!("Unknown format of server keys")),
+        }
+    }
+
+    /// Get the key `key_id` of the server `server_name` if it can be used to check the signatures
+    /// of an event sent at `origin_server_ts` in a room of version `version`.
+    ///
+    /// Old keys are only valid for the events sent before they expired. The `valid_until_ts` of
+    /// the current keys is only enforced from rooms v5 on, as the specification requires.
+    pub fn get(
+        &self,
+        server_name: &str,
+        key_id: &str,
+        origin_server_ts: Option<i64>,
+        version: RoomVersion,
+    ) -> Option<&PublicKey> {
+        let (key, validity) = self.keys.get(server_name)?.get(key_id)?;
+
+        let valid = match *validity {
+            KeyValidity::Current(Some(valid_until_ts)) if version.enforce_key_validity() => {
+                origin_server_ts.is_some_and(|ts| ts <= valid_until_ts)
+            }
+            KeyValidity::Current(_) => true,
+            KeyValidity::Expired(expired_ts) => origin_server_ts.is_some_and(|ts| ts < expired_ts),
+        };
+
+        if valid {
+            Some(key)
+        } else {
+            None
+        }
+    }
+
+    // Adds the current and old keys of a server, once it has been checked that they are signed
+    // by one of its current keys.
+    fn add_server_keys(&mut self, res: &JsonValue) -> Result<String, Error> {
+        let server_name = res
+            .get("server_name")
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| format_err!("Missing server_name in server keys"))?;
+
+        let verify_keys = parse_verify_keys(res.get("verify_keys"))?;
+        let old_verify_keys = parse_verify_keys(res.get("old_verify_keys"))?;
+        let valid_until_ts = res.get("valid_until_ts").and_then(JsonValue::as_i64);
+
+        let mut signed = res.clone();
+
+        if let Some(object) = signed.as_object_mut() {
+            object.remove("signatures");
+            object.remove("unsigned");
+        }
+
+        let self_signed = res
+            .get("signatures")
+            .and_then(|signatures| signatures.get(server_name))
+            .and_then(JsonValue::as_object)
+            .is_some_and(|signatures| {
+                signatures.iter().any(|(key_id, signature)| {
+                    match (verify_keys.get(key_id), signature.as_str()) {
+                        (Some(key), Some(signature)) => verify_signature(&signed, signature, key),
+                        _ => false,
+                    }
+                })
+            });
+
+        if !self_signed {
+            return Err(format_err!(
+                "The keys of {} are not signed by the server itself",
+                server_name
+            ));
+        }
+
+        let keys = self.keys.entry(server_name.to_string()).or_default();
+
+        for (key_id, key) in old_verify_keys {
+            let expired_ts = res["old_verify_keys"][&key_id]["expired_ts"]
+                .as_i64()
+                .ok_or_else(|| format_err!("Missing expired_ts of the old key {}", key_id))?;
+
+            keys.insert(key_id, (key, KeyValidity::Expired(expired_ts)));
+        }
+
+        // A key which has expired stays expired, even if older copies of the keys list it
+        for (key_id, key) in verify_keys {
+            let is_expired = keys
+                .get(&key_id)
+                .is_some_and(|(_, validity)| matches!(validity, KeyValidity::Expired(_)));
+
+            if !is_expired {
+                keys.insert(key_id, (key, KeyValidity::Current(valid_until_ts)));
+            }
+        }
+
+        Ok(server_name.to_string())
+    }
+}
+
+impl SigningKey {
+    /// Parses a signing key in the format of the signing key files of Synapse: the algorithm, the
+    /// version of the key and its seed encoded in base64, separated by spaces.
+    pub fn from_key_file(content: &str) -> Result<SigningKey, Error> {
+        let line = content
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .ok_or_else(|| format_err!("The signing key is empty"))?;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        if parts.len() != 3 {
+            return Err(format_err!(
+                "The signing key must be of the form `ed25519 <version> <seed>`"
+            ));
+        }
+
+        if parts[0] != "ed25519" {
+            return Err(format_err!(
+                "Unsupported signing key algorithm: {}",
+                parts[0]
+            ));
+        }
+
+        let bytes = decode_seed(parts[2])?;
+        SecretKey::from_bytes(&bytes).map_err(|_| format_err!("Invalid signing key"))?;
+
+        let mut seed = [0; 32];
+        seed.copy_from_slice(&bytes);
+
+        Ok(SigningKey {
+            key_id: format!("ed25519:{}", parts[1]),
+            seed,
+        })
+    }
+
+    /// Signs the canonical JSON of `value` and returns the signature encoded in unpadded base64.
+    pub fn sign_json(&self, value: &JsonValue) -> String {
+        let secret = SecretKey::from_bytes(&self.seed).expect("Invalid signing key");
+        let public = PublicKey::from(&secret);
+        let keypair = Keypair { secret, public };
+
+        let signature = keypair.sign(canonical_json(value).as_bytes());
+
+        base64::encode_config(&signature.to_bytes()[..], base64::STANDARD_NO_PAD)
+    }
+}
+
+// The seed of the key is left out so it doesn't end up in the logs.
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SigningKey({})", self.key_id)
+    }
+}
+
+// Decodes the seed of a signing key from unpadded base64. The unused bits of the last symbol are
+// ignored because the seeds aren't always encoded canonically, e.g. the one of the examples of the
+// specification.
+fn decode_seed(seed: &str) -> Result<Vec<u8>, Error> {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut symbols = seed.trim_end_matches('=').as_bytes().to_vec();
+    let unused_bits = symbols.len() * 6 % 8;
+
+    if let Some(last) = symbols.last_mut() {
+        if let Some(value) = ALPHABET.iter().position(|symbol| symbol == last) {
+            *last = ALPHABET[value & !((1 << unused_bits) - 1)];
+        }
+    }
+
+    Ok(base64::decode_config(&symbols, base64::STANDARD_NO_PAD)?)
+}
+
+// Parses the `verify_keys` or `old_verify_keys` field of the keys of a server, which maps the ID
+// of the keys to objects with the key encoded in base64 in their `key` field.
+fn parse_verify_keys(keys: Option<&JsonValue>) -> Result<HashMap<String, PublicKey>, Error> {
+    let keys = match keys.and_then(JsonValue::as_object) {
+        Some(keys) => keys,
+        None => return Ok(HashMap::new()),
+    };
+
+    keys.iter()
+        .filter(|(key_id, _)| key_id.starts_with("ed25519:"))
+        .map(|(key_id, key)| {
+            let key = key
+                .get("key")
+                .and_then(JsonValue::as_str)
+                .ok_or_else(|| format_err!("Missing key {}", key_id))?;
+            let bytes = base64::decode(key)?;
+            let key =
+                PublicKey::from_bytes(&bytes).map_err(|_| format_err!("Invalid key {}", key_id))?;
+
+            Ok((key_id.clone(), key))
+        })
+        .collect()
+}
+
+// Checks `signature`, encoded in base64, of the canonical JSON of `signed` with `key`.
+fn verify_signature(signed: &JsonValue, signature: &str, key: &PublicKey) -> bool {
+    base64::decode(signature)
+        .ok()
+        .and_then(|bytes| Signature::from_bytes(&bytes).ok())
+        .is_some_and(|signature| {
+            key.verify(canonical_json(signed).as_bytes(), &signature)
+                .is_ok()
+        })
+}
+
+/// Computes the content hash of an event: the SHA-256 hash of the canonical JSON of the event
+/// without its unsigned data, its signatures and its hashes.
+pub fn content_hash(event: &JsonValue, version: RoomVersion) -> Vec<u8> {
+    let mut event = event.clone();
+
+    if let Some(object) = event.as_object_mut() {
+        object.remove("unsigned");
+        object.remove("signatures");
+        object.remove("hashes");
+
+        if version.event_format() != EventFormat::V1 {
+            object.remove("event_id");
+        }
+    }
+
+    Sha256::digest(canonical_json(&event).as_bytes()).to_vec()
+}
+
+/// Checks the SHA-256 content hash in the `hashes` field of an event from a room of version
+/// `version`. The check can't be done if the version of the room is unknown, because it tells
+/// whether the `event_id` field is covered by the hash.
+pub fn check_content_hash(event: &JsonValue, version: Option<RoomVersion>) -> HashCheck {
+    let expected = event
+        .get("hashes")
+        .and_then(|hashes| hashes.get("sha256"))
+        .and_then(JsonValue::as_str);
+
+    match (expected, version) {
+        (Some(expected), Some(version)) => {
+            let hash =
+                base64::encode_config(&content_hash(event, version), base64::STANDARD_NO_PAD);
+
+            // The hashes are supposed to be unpadded but some implementations may pad them
+            if hash == expected.trim_end_matches('=') {
+                HashCheck::Verified
+            } else {
+                HashCheck::Mismatch
+            }
+        }
+        _ => HashCheck::Unverifiable,
+    }
+}
+
+/// Computes the reference hash of an event: the SHA-256 hash of the canonical JSON of its
+/// redacted form, without its signatures and its unsigned data.
+pub fn reference_hash(event: &JsonValue, version: RoomVersion) -> Vec<u8> {
+    let mut redacted = redact(event, version);
+
+    if let Some(object) = redacted.as_object_mut() {
+        object.remove("signatures");
+        object.remove("unsigned");
+
+        // The events given by the Client-Server API have an `event_id` field even in the rooms
+        // where it is not a part of the PDUs
+        if version.event_format() != EventFormat::V1 {
+            object.remove("event_id");
+        }
+    }
+
+    Sha256::digest(canonical_json(&redacted).as_bytes()).to_vec()
+}
+
+/// Computes the ID of an event from its reference hash in the rooms where the events don't have
+/// an `event_id` field. Returns `None` for the other rooms.
+pub fn compute_event_id(event: &JsonValue, version: RoomVersion) -> Option<String> {
+    let config = match version.event_format() {
+        EventFormat::V1 => return None,
+        EventFormat::V2 => base64::STANDARD_NO_PAD,
+        EventFormat::V3 => base64::URL_SAFE_NO_PAD,
+    };
+
+    let hash = reference_hash(event, version);
+
+    Some(format!("${}", base64::encode_config(&hash, config)))
+}
+
+/// Get the names of the servers which must have signed an event: the server of its sender, the
+/// server which created its ID in the rooms where the events have an `event_id` field and, for
+/// the joins authorised by another server in the rooms with restricted join rules, this server.
+pub fn signing_servers(event: &JsonValue, version: RoomVersion) -> Vec<String> {
+    let server_of = |id: &JsonValue| {
+        id.as_str()
+            .and_then(|id| id.split_once(':'))
+            .map(|(_, server_name)| server_name)
+            .map(String::from)
+    };
+
+    let mut servers: Vec<String> = Vec::new();
+
+    servers.extend(event.get("sender").and_then(server_of));
+
+    if version.event_format() == EventFormat::V1 {
+        servers.extend(event.get("event_id").and_then(server_of));
+    }
+
+    let is_join = event.get("type").and_then(JsonValue::as_str) == Some("m.room.member")
+        && event["content"]["membership"] == "join";
+
+    if version.restricted_join() && is_join {
+        servers.extend(server_of(
+            &event["content"]["join_authorised_via_users_server"],
+        ));
+    }
+
+    servers.sort();
+    servers.dedup();
+
+    servers
+}
+
+/// Checks the signatures of an event from a room of version `version` with the keys in
+/// `server_keys`. The signed JSON is the redacted form of the event, without its signatures and
+/// its unsigned data.
+pub fn check_signatures(
+    event: &JsonValue,
+    version: Option<RoomVersion>,
+    server_keys: &ServerKeys,
+) -> SignatureCheck {
+    let version = match version {
+        Some(version) => version,
+        None => return SignatureCheck::Unverifiable,
+    };
+
+    let signatures = &event["signatures"];
+    let origin_server_ts = event["origin_server_ts"].as_i64();
+
+    // The redacted form of the event is only computed if a signature can be verified
+    let mut signed: Option<JsonValue> = None;
+
+    signing_servers(event, version)
+        .iter()
+        .map(|server| {
+            let server_signatures = match signatures.get(server).and_then(JsonValue::as_object) {
+                Some(server_signatures) => server_signatures,
+                None => return SignatureCheck::Missing,
+            };
+
+            // The server must have signed the event with at least one of its known keys
+            let checks: Vec<bool> = server_signatures
+                .iter()
+                .filter(|(key_id, _)| key_id.starts_with("ed25519:"))
+                .filter_map(|(key_id, signature)| {
+                    let key = server_keys.get(server, key_id, origin_server_ts, version)?;
+                    let signature = signature.as_str()?;
+
+                    let signed = signed.get_or_insert_with(|| {
+                        let mut signed = redact(event, version);
+
+                        if let Some(object) = signed.as_object_mut() {
+                            object.remove("signatures");
+                            object.remove("unsigned");
+
+                            if version.event_format() != EventFormat::V1 {
+                                object.remove("event_id");
+                            }
+                        }
+
+                        signed
+                    });
+
+                    Some(verify_signature(signed, signature, key))
+                })
+                .collect();
+
+            if checks.iter().any(|valid| *valid) {
+                SignatureCheck::Verified
+            } else if !checks.is_empty() {
+                SignatureCheck::Bad
+            } else if server_signatures
+                .keys()
+                .any(|id| id.starts_with("ed25519:"))
+            {
+                SignatureCheck::Unverifiable
+            } else {
+                SignatureCheck::Missing
+            }
+        })
+        .max()
+        .unwrap_or(SignatureCheck::Unverifiable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The signing key of the examples of the appendices of the specification
+    const SPEC_SIGNING_KEY: &str = "ed25519 1 YJDBA9Xnr2sVqXD9Vj7XVUnmFZcZrlw8Md7kMW+3XA1";
+    const SPEC_VERIFY_KEY: &str = "XGX0JRS2Af3be3knz2fBiRbApjm2Dh61gXDJA8kcJNI";
+
+    // The event of the example of the signing of events in the specification
+    fn spec_event() -> JsonValue {
+        json!({
+            "room_id": "!x:domain",
+            "sender": "@a:domain",
+            "origin": "domain",
+            "origin_server_ts": 1000000,
+            "signatures": {
+                "domain": {
+                    "ed25519:1": "KxwGjPSDEtvnFgU00fwFz+l6d2pJM6XBIaMEn81SXPTRl16AqLAYqfIReFGZlHi5KLjAWbOoMszkwsQma+lYAg"
+                }
+            },
+            "hashes": { "sha256": "5jM4wQpv6lnBo7CLIghJuHdW+s2CMBJPUOGOC89ncos" },
+            "type": "X",
+            "content": {},
+            "prev_events": [],
+            "auth_events": [],
+            "depth": 3,
+            "unsigned": { "age_ts": 1000000 }
+        })
+    }
+
+    // The keys of the server `domain`, signed by itself
+    fn spec_server_keys() -> ServerKeys {
+        let signing_key = SigningKey::from_key_file(SPEC_SIGNING_KEY).unwrap();
+        let mut keys = json!({
+            "server_name": "domain",
+            "valid_until_ts": 2000000,
+            "verify_keys": { "ed25519:1": { "key": SPEC_VERIFY_KEY } },
+            "old_verify_keys": {}
+        });
+        let signature = signing_key.sign_json(&keys);
+        keys["signatures"] = json!({ "domain": { "ed25519:1": signature } });
+
+        let mut server_keys = ServerKeys::new();
+        assert_eq!(server_keys.add_keys_json(&keys).unwrap(), vec!["domain"]);
+
+        server_keys
+    }
+
+    #[test]
+    fn canonical_json_examples() {
+        assert_eq!(canonical_json(&json!({})), "{}");
+        assert_eq!(
+            canonical_json(&json!({ "one": 1, "two": "Two" })),
+            r#"{"one":1,"two":"Two"}"#
+        );
+        assert_eq!(
+            canonical_json(&json!({ "b": "2", "a": "1" })),
+            r#"{"a":"1","b":"2"}"#
+        );
+        assert_eq!(
+            canonical_json(&json!({
+                "auth": {
+                    "success": true,
+                    "mxid": "@john.doe:example.com",
+                    "profile": {
+                        "display_name": "John Doe",
+                        "three_pids": [
+                            { "medium": "email", "address": "john.doe@example.org" },
+                            { "medium": "msisdn", "address": "123456789" }
+                        ]
+                    }
+                }
+            })),
+            r#"{"auth":{"mxid":"@john.doe:example.com","profile":{"display_name":"John Doe","three_pids":[{"address":"john.doe@example.org","medium":"email"},{"address":"123456789","medium":"msisdn"}]},"success":true}}"#
+        );
+        assert_eq!(
+            canonical_json(&json!({ "a": "日本語" })),
+            r#"{"a":"日本語"}"#
+        );
+        assert_eq!(
+            canonical_json(&json!({ "本": 2, "日": 1 })),
+            r#"{"日":1,"本":2}"#
+        );
+        assert_eq!(canonical_json(&json!({ "a": "\u{65e5}" })), r#"{"a":"日"}"#);
+        assert_eq!(canonical_json(&json!({ "a": null })), r#"{"a":null}"#);
+    }
+
+    #[test]
+    fn signed_json_examples() {
+        let signing_key = SigningKey::from_key_file(SPEC_SIGNING_KEY).unwrap();
+
+        assert_eq!(signing_key.key_id, "ed25519:1");
+        assert_eq!(
+            signing_key.sign_json(&json!({})),
+            "K8280/U9SSy9IVtjBuVeLr+HpOB4BQFWbg+UZaADMtTdGYI7Geitb76LTrr5QV/7Xg4ahLwYGYZzuHGZKM5ZAQ"
+        );
+        assert_eq!(
+            signing_key.sign_json(&json!({ "one": 1, "two": "Two" })),
+            "KqmLSbO39/Bzb0QIYE82zqLwsA+PDzYIpIRA2sRQ4sL53+sN6/fpNSoqE7BP7vBZhG6kYdD13EIMJpvhJI+6Bw"
+        );
+    }
+
+    #[test]
+    fn signed_event_example() {
+        let event = spec_event();
+        let server_keys = spec_server_keys();
+
+        assert_eq!(
+            check_content_hash(&event, Some(RoomVersion::V1)),
+            HashCheck::Verified
+        );
+        assert_eq!(
+            check_signatures(&event, Some(RoomVersion::V1), &server_keys),
+            SignatureCheck::Verified
+        );
+
+        let mut tampered = event.clone();
+        tampered["depth"] = json!(4);
+
+        assert_eq!(
+            check_content_hash(&tampered, Some(RoomVersion::V1)),
+            HashCheck::Mismatch
+        );
+        assert_eq!(
+            check_signatures(&tampered, Some(RoomVersion::V1), &server_keys),
+            SignatureCheck::Bad
+        );
+
+        // The content of the events isn't signed, only hashed
+        let mut edited = event.clone();
+        edited["content"] = json!({ "body": "Edited" });
+
+        assert_eq!(
+            check_content_hash(&edited, Some(RoomVersion::V1)),
+            HashCheck::Mismatch
+        );
+        assert_eq!(
+            check_signatures(&edited, Some(RoomVersion::V1), &server_keys),
+            SignatureCheck::Verified
+        );
+    }
+
+    #[test]
+    fn key_validity() {
+        let event = spec_event();
+
+        // The key of the example is only valid until 2000000 in the keys of `spec_server_keys`
+        let mut late = event.clone();
+        late["origin_server_ts"] = json!(3000000);
+
+        let server_keys = spec_server_keys();
+
+        assert_eq!(
+            check_signatures(&late, Some(RoomVersion::V4), &server_keys),
+            SignatureCheck::Bad
+        );
+        assert_eq!(
+            check_signatures(&late, Some(RoomVersion::V5), &server_keys),
+            SignatureCheck::Unverifiable
+        );
+
+        // The key of the example has been replaced by another one and expired at 1500000
+        let new_seed = [1; 32];
+        let new_key = PublicKey::from(&SecretKey::from_bytes(&new_seed).unwrap());
+        let signing_key = SigningKey::from_key_file(&format!(
+            "ed25519 2 {}",
+            base64::encode_config(&new_seed, base64::STANDARD_NO_PAD)
+        ))
+        .unwrap();
+        let mut keys = json!({
+            "server_name": "domain",
+            "valid_until_ts": 4000000,
+            "verify_keys": { "ed25519:2": { "key": base64::encode(new_key.as_bytes()) } },
+            "old_verify_keys": {
+                "ed25519:1": { "key": SPEC_VERIFY_KEY, "expired_ts": 1500000 }
+            }
+        });
+        let signature = signing_key.sign_json(&keys);
+        keys["signatures"] = json!({ "domain": { "ed25519:2": signature } });
+
+        let mut server_keys = spec_server_keys();
+        assert_eq!(server_keys.add_keys_json(&keys).unwrap(), vec!["domain"]);
+
+        assert_eq!(
+            check_signatures(&event, Some(RoomVersion::V5), &server_keys),
+            SignatureCheck::Verified
+        );
+        assert_eq!(
+            check_signatures(&late, Some(RoomVersion::V1), &server_keys),
+            SignatureCheck::Unverifiable
+        );
+
+        // Old keys without `expired_ts` are rejected
+        let mut keys = json!({
+            "server_name": "domain",
+            "verify_keys": { "ed25519:2": { "key": base64::encode(new_key.as_bytes()) } },
+            "old_verify_keys": { "ed25519:1": { "key": SPEC_VERIFY_KEY } }
+        });
+        let signature = signing_key.sign_json(&keys);
+        keys["signatures"] = json!({ "domain": { "ed25519:2": signature } });
+
+        assert!(ServerKeys::new().add_keys_json(&keys).is_err());
+    }
+
+    #[test]
+    fn event_ids() {
+        let event = spec_event();
+
+        // The reference hash of the event is the hash of its redacted form without its
+        // signatures and its unsigned data
+        let redacted = r#"{"auth_events":[],"content":{},"depth":3,"hashes":{"sha256":"5jM4wQpv6lnBo7CLIghJuHdW+s2CMBJPUOGOC89ncos"},"origin":"domain","origin_server_ts":1000000,"prev_events":[],"room_id":"!x:domain","sender":"@a:domain","type":"X"}"#;
+        let hash = Sha256::digest(redacted.as_bytes()).to_vec();
+
+        assert_eq!(reference_hash(&event, RoomVersion::V3), hash);
+        assert_eq!(compute_event_id(&event, RoomVersion::V1), None);
+        assert_eq!(
+            compute_event_id(&event, RoomVersion::V3),
+            Some(format!(
+                "${}",
+                base64::encode_config(&hash, base64::STANDARD_NO_PAD)
+            ))
+        );
+        assert_eq!(
+            compute_event_id(&event, RoomVersion::V4),
+            Some(format!(
+                "${}",
+                base64::encode_config(&hash, base64::URL_SAFE_NO_PAD)
+            ))
+        );
+
+        // Neither the unsigned data nor the content of the event change its ID
+        let mut edited = event.clone();
+        edited["unsigned"] = json!({ "age": 42 });
+        edited["content"] = json!({ "body": "Edited" });
+
+        assert_eq!(
+            compute_event_id(&edited, RoomVersion::V4),
+            compute_event_id(&event, RoomVersion::V4)
+        );
+    }
+}
@@ -1,44 +1,121 @@
-use std::collections::{HashMap, HashSet};
-use std::iter::FromIterator;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
 
 use petgraph::graph::{Graph, NodeIndex};
-use petgraph::visit::{Bfs, EdgeRef};
+use petgraph::visit::EdgeRef;
 use petgraph::{Directed, Direction};
 use serde_derive::Serialize;
 use serde_json::Value as JsonValue;
 
-use crate::cs_backend::backend::SyncResponse;
-use crate::mv_backend::backend::EventsResponse;
+use crate::cs_backend::api::SyncResponse;
+use crate::mv_backend::api::EventsResponse;
 
-use super::event::{Event, Field};
+use super::crypto::{self, ServerKeys};
+use super::event::{Event, Field, ParseError};
+use super::room_version::RoomVersion;
+use super::snapshot::Snapshot;
 
 /// The internal representation of the events DAG of the room being observed as well as various
 /// informations and `HashMap`s which makes easier to locate the events.
 pub struct RoomEvents {
     server_name: String,    // The name of the server this DAG was retrieved from
     fields: HashSet<Field>, // Events fields which will be included in the labels on the nodes of the vis.js network
+    room_version: Option<RoomVersion>, // The version of the room, known once its `m.room.create` event has been seen
+    server_keys: ServerKeys, // The signing keys of the servers, for checking the signatures of the events
 
-    dag: Graph<Event, (), Directed>,         // The DAG of the events
+    dag: Graph<Event, EdgeKind, Directed>, // The DAG of the events
     events_map: HashMap<String, NodeIndex>, // Allows to quickly locate an event in the DAG with its ID
     depth_map: HashMap<i64, Vec<NodeIndex>>, // Allows to quickly locate events at a given depth in the DAG
-    pub latest_events: Vec<String>,          // The ID of the latest events in the DAG
-    pub earliest_events: Vec<String>,        // The ID of the earliest events in the DAG
-    pub orphan_events: Vec<OrphanInfo>, // The ID and depth of events with missing ancestors in the DAG
-    max_depth: i64,                     // Minimal depth of the events in the DAG
-    min_depth: i64,                     // Maximal depth of the events in the DAG
+    pending_events: HashMap<String, Vec<(NodeIndex, EdgeKind)>>, // The events referencing events which are not in the DAG yet, indexed by the ID of the missing events
+    latest_events: HashSet<NodeIndex>,                           // The latest events in the DAG
+    earliest_events: HashSet<NodeIndex>,                         // The earliest events in the DAG
+    orphan_events: HashSet<NodeIndex>, // The events with missing ancestors in the DAG
+    quarantine: Vec<ParseError>,       // The events which have been rejected when parsing them
+    max_depth: i64,                    // Minimal depth of the events in the DAG
+    min_depth: i64,                    // Maximal depth of the events in the DAG
+
+    batches: Vec<Batch>, // The batches which have brought events to the DAG, in arrival order
+    arrival_batches: Vec<usize>, // The index in `batches` of the batch which brought each event, indexed by node
 }
 
+/// The kind of request which brought a batch of events to the DAG.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchKind {
+    Sync,          // A `/sync` request of the CS API
+    Messages,      // A `/messages` request of the CS API
+    Deepest,       // The deepest events from the Matrix Visualisations backend
+    Ancestors,     // Ancestors from the Matrix Visualisations backend
+    Descendants,   // Descendants from the Matrix Visualisations backend
+    File,          // Events read from local files
+    Snapshot,      // Events loaded from a snapshot
+    Event,         // A `/event` request of the federation API
+    Backfill,      // A `/backfill` request of the federation API
+    MissingEvents, // A `/get_missing_events` request of the federation API
+    Context,       // A `/context` request of the CS API, for a missing event
+}
+
+/// A batch of events added to the DAG, and when it has been received.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Batch {
+    pub kind: BatchKind,
+    pub received_at: f64, // The local time of the reception, in milliseconds since the Unix epoch
+}
+
+/// The order in which the construction of the DAG is replayed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReplayOrder {
+    Arrival,        // The order in which the events have been received
+    OriginServerTS, // The order of the `origin_server_ts` of the events
+}
+
+/// A step of the replay of the construction of the DAG: an event, the edges between this event
+/// and the events of the previous steps, and the batch which brought the event.
+#[derive(Debug)]
+pub struct ReplayStep {
+    pub event_id: String,
+    pub data_set: DataSet,
+    pub batch: Batch,
+}
+
+/// The kind of an edge of the DAG, i.e. the field of the source event which references the
+/// target event.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    Prev, // The target is one of the `prev_events` of the source
+    Auth, // The target is one of the `auth_events` of the source
+}
+
+/// The shape of the DAG: where it forks, where its branches merge back together and the branches
+/// themselves.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DagShape {
+    pub fork_points: Vec<String>, // The ID of the events with more than one child in the DAG
+    pub merge_points: Vec<String>, // The ID of the events with more than one `prev_events`
+    pub branches: Vec<Branch>,    // The branches between the fork and merge points
+}
+
+/// A linear sequence of events of the DAG, between fork and merge points.
 #[derive(Clone, Debug, Serialize)]
+pub struct Branch {
+    pub id: usize,
+    pub events: Vec<String>, // The ID of the events of the branch, from the earliest to the latest
+    pub parents: Vec<usize>, // The ID of the branches this branch comes from
+    pub children: Vec<usize>, // The ID of the branches coming from this branch
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub struct OrphanInfo {
-    id: String,
-    depth: i64,
+    pub id: String,
+    pub depth: i64,
 }
 
 /// The data set containing events which will be added to the vis.js network.
-#[derive(Debug, Default, Serialize)]
+#[derive(Clone, Debug, Default, Serialize)]
 pub struct DataSet {
-    nodes: Vec<DataSetNode>,
-    edges: Vec<DataSetEdge>,
+    pub nodes: Vec<DataSetNode>,
+    pub edges: Vec<DataSetEdge>,
 }
 
 impl DataSet {
@@ -55,60 +132,118 @@ impl DataSet {
             e.to.insert_str(0, pref);
         }
     }
+
+    /// Adds the nodes and the edges of `other` after the ones of this `DataSet`, so several
+    /// updates of the DAG can be displayed at once.
+    pub fn append(&mut self, mut other: DataSet) {
+        self.nodes.append(&mut other.nodes);
+        self.edges.append(&mut other.edges);
+    }
 }
 
 /// A node of the vis.js data set.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct DataSetNode {
     pub id: String,
     pub label: String,
     pub level: i64,
     pub color: NodeColor,
+    #[serde(rename = "borderWidth")]
+    pub border_width: u32,
 }
 
 /// The colors of the data set's node.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct NodeColor {
     pub border: String,
     pub background: String,
 }
 
 /// An edge of the vis.js data set.
-#[derive(Debug, Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct DataSetEdge {
-    id: String,
-    from: String,
-    to: String,
+    pub id: String,
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+impl DataSetEdge {
+    fn new(from: String, to: String, kind: EdgeKind) -> DataSetEdge {
+        let id = match kind {
+            EdgeKind::Prev => from.clone() + &to,
+            EdgeKind::Auth => from.clone() + &to + "_auth",
+        };
+
+        DataSetEdge { id, from, to, kind }
+    }
+}
+
+impl fmt::Display for BatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchKind::Sync => write!(f, "/sync"),
+            BatchKind::Messages => write!(f, "/messages"),
+            BatchKind::Deepest => write!(f, "deepest events"),
+            BatchKind::Ancestors => write!(f, "ancestors"),
+            BatchKind::Descendants => write!(f, "descendants"),
+            BatchKind::File => write!(f, "local file"),
+            BatchKind::Snapshot => write!(f, "snapshot"),
+            BatchKind::Event => write!(f, "/event"),
+            BatchKind::Backfill => write!(f, "/backfill"),
+            BatchKind::MissingEvents => write!(f, "/get_missing_events"),
+            BatchKind::Context => write!(f, "/context"),
+        }
+    }
 }
 
 impl RoomEvents {
-    /// Creates an event DAG from the initial `SyncResponse`.
+    /// Creates an empty event DAG for the events retrieved from `server_name`.
+    pub fn new(server_name: &str, fields: &HashSet<Field>) -> RoomEvents {
+        RoomEvents {
+            server_name: server_name.to_string(),
+            fields: fields.clone(),
+            room_version: None,
+            server_keys: ServerKeys::new(),
+
+            dag: Graph::new(),
+            events_map: HashMap::new(),
+            depth_map: HashMap::new(),
+            pending_events: HashMap::new(),
+            latest_events: HashSet::new(),
+            earliest_events: HashSet::new(),
+            orphan_events: HashSet::new(),
+            quarantine: Vec::new(),
+            max_depth: -1,
+            min_depth: -1,
+
+            batches: Vec::new(),
+            arrival_batches: Vec::new(),
+        }
+    }
+
+    /// Creates an event DAG from the initial `SyncResponse`, received at `received_at`.
     pub fn from_sync_response(
         room_id: &str,
         server_name: &str,
         fields: &HashSet<Field>,
-        res: SyncResponse,
+        mut res: SyncResponse,
+        received_at: f64,
     ) -> Option<RoomEvents> {
-        match res.rooms.join.get(room_id) {
+        match res.rooms.join.remove(room_id) {
             Some(room) => {
-                let timeline = parse_events(&room.timeline.events);
-
-                let mut dag = RoomEvents {
-                    server_name: server_name.to_string(),
-                    fields: fields.clone(),
-
-                    dag: Graph::new(),
-                    events_map: HashMap::with_capacity(timeline.len()),
-                    depth_map: HashMap::with_capacity(timeline.len()),
-                    latest_events: Vec::new(),
-                    earliest_events: Vec::new(),
-                    orphan_events: Vec::new(),
-                    max_depth: -1,
-                    min_depth: -1,
-                };
-
-                dag.add_event_nodes(timeline);
-                dag.update_event_edges();
+                let mut dag = RoomEvents::new(server_name, fields);
+
+                // The `m.room.create` event is usually in the state of the room rather than in
+                // its timeline
+                dag.room_version = find_room_version(&room.state.events);
+                dag.add_events(
+                    room.timeline.events,
+                    Batch {
+                        kind: BatchKind::Sync,
+                        received_at,
+                    },
+                );
 
                 Some(dag)
             }
@@ -116,120 +251,511 @@ impl RoomEvents {
         }
     }
 
-    pub fn from_deepest_events(
+    /// Creates an empty event DAG for the room whose current state is `state`, e.g. a room which is
+    /// peeked at, so the version of the room is known before its events are retrieved.
+    pub fn from_room_state(
         server_name: &str,
         fields: &HashSet<Field>,
-        res: EventsResponse,
+        state: &[JsonValue],
     ) -> RoomEvents {
-        let events = parse_events(&res.events);
+        let mut dag = RoomEvents::new(server_name, fields);
 
-        let mut dag = RoomEvents {
-            server_name: server_name.to_string(),
-            fields: fields.clone(),
+        dag.room_version = find_room_version(state);
 
-            dag: Graph::new(),
-            events_map: HashMap::with_capacity(events.len()),
-            depth_map: HashMap::with_capacity(events.len()),
-            latest_events: Vec::new(),
-            earliest_events: Vec::new(),
-            orphan_events: Vec::new(),
-            max_depth: -1,
-            min_depth: -1,
-        };
+        dag
+    }
+
+    pub fn from_deepest_events(
+        server_name: &str,
+        fields: &HashSet<Field>,
+        res: EventsResponse,
+        batch: Batch,
+    ) -> RoomEvents {
+        let mut dag = RoomEvents::new(server_name, fields);
 
-        dag.add_event_nodes(events);
-        dag.update_event_edges();
+        dag.add_events(res.events, batch);
 
         dag
     }
 
-    /// Adds `events` to the DAG.
-    pub fn add_events(&mut self, events: Vec<JsonValue>) {
-        let events = parse_events(&events);
+    /// Rebuilds the event DAG saved in `snapshot`, loaded at `loaded_at`.
+    pub fn from_snapshot(
+        fields: &HashSet<Field>,
+        snapshot: Snapshot,
+        loaded_at: f64,
+    ) -> RoomEvents {
+        let mut dag = RoomEvents::new(&snapshot.server_name, fields);
+
+        // The `m.room.create` event may not be among the saved events
+        dag.room_version = snapshot
+            .room_version
+            .as_ref()
+            .and_then(|id| RoomVersion::from_id(id));
+        dag.add_events(
+            snapshot.events,
+            Batch {
+                kind: BatchKind::Snapshot,
+                received_at: loaded_at,
+            },
+        );
 
-        self.add_event_nodes(events);
-        self.update_event_edges();
+        dag
     }
 
-    fn add_event_nodes(&mut self, events: Vec<Event>) {
-        for event in events.iter() {
-            let id = &event.event_id;
-            let depth = event.depth;
-            let index = self.dag.add_node(event.clone()); // Add each event as a node in the DAG
+    /// Adds `events`, brought by `batch`, to the DAG and returns a data set containing only the
+    /// nodes which have been added or changed and the edges which have been added, so the vis.js
+    /// network can be updated with the difference.
+    ///
+    /// The DAG is updated incrementally: the references to events which are not in the DAG yet
+    /// are kept in an index so the corresponding edges are added as soon as these events arrive,
+    /// and the extremities and orphans of the DAG are only updated around the new events.
+    pub fn add_events(&mut self, mut events: Vec<JsonValue>, batch: Batch) -> DataSet {
+        let mut new_node_indices = Vec::with_capacity(events.len());
+        let mut new_edges = Vec::new();
+
+        if self.room_version.is_none() {
+            self.room_version = find_room_version(&events);
+
+            // The content hashes and the signatures of the events already in the DAG couldn't
+            // be checked without the version of the room
+            if let Some(version) = self.room_version {
+                for event in self.dag.node_weights_mut() {
+                    event.check_content_hash(version);
+                    event.check_signatures(Some(version), &self.server_keys);
+                }
 
-            self.events_map.insert(id.clone(), index); // Update the events map
+                new_node_indices.extend(self.dag.node_indices());
 
-            match self.depth_map.get_mut(&depth) {
-                None => {
-                    self.depth_map.insert(depth, vec![index]);
-                }
-                Some(v) => {
-                    v.push(index);
-                }
+                // The events in quarantine may only have been rejected because the version of the
+                // room was unknown, e.g. the events without `event_id` since rooms v3, so they
+                // are parsed again along with the new ones
+                let quarantine = std::mem::take(&mut self.quarantine);
+                events.splice(0..0, quarantine.into_iter().map(|err| err.json));
             }
+        }
 
-            if self.max_depth == -1 || depth > self.max_depth {
-                self.max_depth = depth;
+        let (events, rejected) = parse_events(&events, self.room_version);
+
+        // The same events can be received several times, they are only kept once in quarantine
+        for err in rejected {
+            if self.quarantine.iter().all(|e| e.json != err.json) {
+                self.quarantine.push(err);
             }
+        }
 
-            if self.min_depth == -1 || depth < self.min_depth {
-                self.min_depth = depth;
+        let batch_idx = self.batches.len();
+        let mut added_events = false;
+
+        for event in events {
+            if let Some(idx) = self.add_event(event, &mut new_edges) {
+                // The nodes are never removed, so their indices follow the arrival order
+                self.arrival_batches.push(batch_idx);
+                new_node_indices.push(idx);
+                added_events = true;
             }
         }
+
+        // Only the batches which brought new events are recorded
+        if added_events {
+            self.batches.push(batch);
+        }
+
+        DataSet {
+            nodes: new_node_indices
+                .into_iter()
+                .map(|idx| self.to_data_set_node(idx))
+                .collect(),
+            edges: new_edges
+                .into_iter()
+                .map(|edge| self.to_data_set_edge(edge).unwrap())
+                .collect(),
+        }
     }
 
-    fn update_event_edges(&mut self) {
-        // Update the edges in the DAG
-        for src_idx in self.dag.node_indices() {
-            let prev_indices: Vec<NodeIndex> = self
-                .dag
-                .node_weight(src_idx)
-                .unwrap()
-                .get_prev_events()
-                .iter()
-                .filter(|id| self.events_map.get(**id).is_some()) // Only take into account events which are really in the DAG
-                .map(|id| self.events_map[*id])
-                .collect();
+    /// Get the batch which brought the event `id` to the DAG.
+    pub fn arrival(&self, id: &str) -> Option<Batch> {
+        let idx = self.events_map.get(id)?;
+
+        Some(self.batches[self.arrival_batches[idx.index()]])
+    }
+
+    /// Get when the latest batch of events has been received, if the DAG has any event.
+    pub fn last_received_at(&self) -> Option<f64> {
+        self.batches.last().map(|batch| batch.received_at)
+    }
+
+    /// Splits the construction of the DAG in steps of one event, ordered according to `order`,
+    /// so it can be replayed. Each step contains the edges between its event and the events of
+    /// the previous steps.
+    pub fn replay(&self, order: ReplayOrder) -> Vec<ReplayStep> {
+        let mut sequence: Vec<NodeIndex> = self.dag.node_indices().collect();
+
+        if order == ReplayOrder::OriginServerTS {
+            // The sort is stable, so the events with the same timestamp stay in arrival order
+            sequence.sort_by_key(|idx| self.dag[*idx].origin_server_ts);
+        }
+
+        let mut positions = vec![0; sequence.len()];
 
-            for dst_idx in prev_indices {
-                self.dag.update_edge(src_idx, dst_idx, ());
+        for (pos, idx) in sequence.iter().enumerate() {
+            positions[idx.index()] = pos;
+        }
+
+        let mut steps: Vec<ReplayStep> = sequence
+            .iter()
+            .map(|idx| ReplayStep {
+                event_id: self.dag[*idx].event_id.clone(),
+                data_set: DataSet {
+                    nodes: vec![self.to_data_set_node(*idx)],
+                    edges: Vec::new(),
+                },
+                batch: self.batches[self.arrival_batches[idx.index()]],
+            })
+            .collect();
+
+        // An edge appears along with the latest of its two events
+        for edge in self.dag.edge_references() {
+            let step = positions[edge.source().index()].max(positions[edge.target().index()]);
+
+            if let Some(data_set_edge) =
+                self.to_data_set_edge((edge.source(), edge.target(), *edge.weight()))
+            {
+                steps[step].data_set.edges.push(data_set_edge);
             }
         }
 
-        self.latest_events.clear();
-        self.earliest_events.clear();
-        self.orphan_events.clear();
+        steps
+    }
+
+    /// Replaces the signing keys of the servers with `server_keys` and checks the signatures of
+    /// the events in the DAG again. Returns a data set containing only the nodes whose signature
+    /// check has changed.
+    pub fn set_server_keys(&mut self, server_keys: &ServerKeys) -> DataSet {
+        self.server_keys = server_keys.clone();
+
+        let mut changed_node_indices = Vec::new();
 
-        // Update the earliest and latest events of the DAG
         for idx in self.dag.node_indices() {
-            if self.dag.edges_directed(idx, Direction::Outgoing).count() == 0 {
-                let id = self.dag.node_weight(idx).unwrap().event_id.clone();
+            let event = &mut self.dag[idx];
+            let previous_check = event.signature_check;
+
+            event.check_signatures(self.room_version, &self.server_keys);
 
-                self.earliest_events.push(id);
+            if event.signature_check != previous_check {
+                changed_node_indices.push(idx);
             }
+        }
+
+        DataSet {
+            nodes: changed_node_indices
+                .into_iter()
+                .map(|idx| self.to_data_set_node(idx))
+                .collect(),
+            edges: Vec::new(),
+        }
+    }
+
+    /// Get the names of the servers which had to sign the events in the DAG, whose signing keys
+    /// are needed for checking the signatures of the events. They are only known once the
+    /// version of the room is known.
+    pub fn signing_servers(&self) -> Vec<String> {
+        let version = match self.room_version {
+            Some(version) => version,
+            None => return Vec::new(),
+        };
 
-            if self.dag.edges_directed(idx, Direction::Incoming).count() == 0 {
-                let id = self.dag.node_weight(idx).unwrap().event_id.clone();
+        let mut servers: Vec<String> = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .flat_map(|node| crypto::signing_servers(node.weight.json(), version))
+            .collect();
+        servers.sort();
+        servers.dedup();
+
+        servers
+    }
+
+    /// Get every events of the DAG, sorted by depth and then by ID.
+    pub fn events(&self) -> Vec<&Event> {
+        let mut events: Vec<&Event> = self
+            .dag
+            .raw_nodes()
+            .iter()
+            .map(|node| &node.weight)
+            .collect();
+        events.sort_by(|a, b| (a.depth, &a.event_id).cmp(&(b.depth, &b.event_id)));
 
-                self.latest_events.push(id);
+        events
+    }
+
+    /// Whether the `m.room.create` event of the room is in the DAG, i.e. whether the DAG goes back
+    /// to the beginning of the room.
+    pub fn has_create_event(&self) -> bool {
+        self.dag.raw_nodes().iter().any(|node| {
+            node.weight.etype == "m.room.create" && node.weight.state_key == Some(String::new())
+        })
+    }
+
+    /// Get the JSON bodies of every events of the DAG as they have been received, sorted by depth,
+    /// followed by the events in quarantine.
+    pub fn raw_events(&self) -> Vec<JsonValue> {
+        self.events()
+            .into_iter()
+            .map(|event| event.json().clone())
+            .chain(self.quarantine.iter().map(|err| err.json.clone()))
+            .collect()
+    }
+
+    /// Get the events which have been rejected when parsing them, along with their raw JSON body.
+    pub fn quarantine(&self) -> &[ParseError] {
+        &self.quarantine
+    }
+
+    // Adds `event` to the DAG, links it to the events it references and to the events which are
+    // referencing it, and pushes the new edges in `new_edges`. Returns `None` if the event was
+    // already in the DAG.
+    fn add_event(
+        &mut self,
+        mut event: Event,
+        new_edges: &mut Vec<(NodeIndex, NodeIndex, EdgeKind)>,
+    ) -> Option<NodeIndex> {
+        if self.events_map.contains_key(&event.event_id) {
+            return None;
+        }
+
+        let id = event.event_id.clone();
+        let depth = event.depth;
+        let refs: Vec<(String, EdgeKind)> = event
+            .get_prev_events()
+            .into_iter()
+            .map(|ref_id| (ref_id.to_string(), EdgeKind::Prev))
+            .chain(
+                event
+                    .get_auth_events()
+                    .into_iter()
+                    .map(|ref_id| (ref_id.to_string(), EdgeKind::Auth)),
+            )
+            .collect();
+
+        event.check_signatures(self.room_version, &self.server_keys);
+
+        let idx = self.dag.add_node(event);
+
+        self.events_map.insert(id.clone(), idx);
+        self.depth_map.entry(depth).or_default().push(idx);
+
+        if self.max_depth == -1 || depth > self.max_depth {
+            self.max_depth = depth;
+        }
+
+        if self.min_depth == -1 || depth < self.min_depth {
+            self.min_depth = depth;
+        }
+
+        // Link the event to the events it references, or wait for them to be added to the DAG
+        for (ref_id, kind) in refs {
+            match self.events_map.get(&ref_id) {
+                Some(&ref_idx) => {
+                    if self.add_edge_once(idx, ref_idx, kind) {
+                        new_edges.push((idx, ref_idx, kind));
+                    }
+                }
+                None => self
+                    .pending_events
+                    .entry(ref_id)
+                    .or_default()
+                    .push((idx, kind)),
             }
+        }
 
-            if self.dag.edges_directed(idx, Direction::Outgoing).count()
-                < self
-                    .dag
-                    .node_weight(idx)
-                    .unwrap()
-                    .get_prev_events()
-                    .iter()
-                    .count()
-            {
-                let id = self.dag.node_weight(idx).unwrap().event_id.clone();
-                let depth = self.dag.node_weight(idx).unwrap().depth;
-                let info = OrphanInfo { id, depth };
+        // Link the events which were waiting for this one
+        for (src_idx, kind) in self.pending_events.remove(&id).unwrap_or_default() {
+            if self.add_edge_once(src_idx, idx, kind) {
+                new_edges.push((src_idx, idx, kind));
+            }
 
-                self.orphan_events.push(info);
+            if kind == EdgeKind::Prev {
+                self.earliest_events.remove(&src_idx);
+                self.update_orphan(src_idx);
             }
         }
+
+        // Update the extremities of the DAG around the new event, only the `prev_events` edges
+        // are taken into account here
+        for parent_idx in self.prev_neighbors(idx, Direction::Outgoing) {
+            self.latest_events.remove(&parent_idx);
+        }
+
+        if self.count_prev_edges(idx, Direction::Outgoing) == 0 {
+            self.earliest_events.insert(idx);
+        }
+
+        if self.count_prev_edges(idx, Direction::Incoming) == 0 {
+            self.latest_events.insert(idx);
+        }
+
+        self.update_orphan(idx);
+
+        Some(idx)
+    }
+
+    // Marks the event `idx` as an orphan if some of its `prev_events` are not in the DAG.
+    fn update_orphan(&mut self, idx: NodeIndex) {
+        let is_orphan = self.dag[idx]
+            .get_prev_events()
+            .iter()
+            .any(|id| !self.events_map.contains_key(*id));
+
+        if is_orphan {
+            self.orphan_events.insert(idx);
+        } else {
+            self.orphan_events.remove(&idx);
+        }
+    }
+
+    // Adds an edge of the kind `kind` from `src` to `dst` unless there is already one, and
+    // returns whether the edge has been added. Unlike `Graph::update_edge`, this allows both a
+    // `Prev` and an `Auth` edge between the same events.
+    fn add_edge_once(&mut self, src: NodeIndex, dst: NodeIndex, kind: EdgeKind) -> bool {
+        let exists = self
+            .dag
+            .edges_directed(src, Direction::Outgoing)
+            .any(|e| e.target() == dst && *e.weight() == kind);
+
+        if !exists {
+            self.dag.add_edge(src, dst, kind);
+        }
+
+        !exists
+    }
+
+    fn count_prev_edges(&self, idx: NodeIndex, dir: Direction) -> usize {
+        self.dag
+            .edges_directed(idx, dir)
+            .filter(|e| *e.weight() == EdgeKind::Prev)
+            .count()
+    }
+
+    // Get the events linked to `idx` by a `prev_events` edge in the direction `dir`, i.e. its
+    // parents for `Outgoing` and its children for `Incoming`.
+    fn prev_neighbors(&self, idx: NodeIndex, dir: Direction) -> Vec<NodeIndex> {
+        self.dag
+            .edges_directed(idx, dir)
+            .filter(|e| *e.weight() == EdgeKind::Prev)
+            .map(|e| match dir {
+                Direction::Outgoing => e.target(),
+                Direction::Incoming => e.source(),
+            })
+            .collect()
+    }
+
+    /// Analyses the shape of the DAG by finding its fork points, its merge points and the
+    /// branches between them. Only the `prev_events` edges are taken into account.
+    ///
+    /// Every event belongs to exactly one branch. A new branch starts at each event which
+    /// doesn't have exactly one parent in the DAG, whose parent is a fork point or which is a
+    /// merge point. The branches are numbered by the depth of their first event.
+    pub fn shape(&self) -> DagShape {
+        let parents = |idx| self.prev_neighbors(idx, Direction::Outgoing);
+        let children = |idx| self.prev_neighbors(idx, Direction::Incoming);
+        let is_merge_point = |idx: NodeIndex| self.dag[idx].get_prev_events().len() > 1;
+        let continues_parent = |idx: NodeIndex| {
+            !is_merge_point(idx)
+                && match parents(idx).as_slice() {
+                    [parent] => children(*parent).len() == 1,
+                    _ => false,
+                }
+        };
+        let by_depth = |a: &NodeIndex, b: &NodeIndex| {
+            let (a, b) = (&self.dag[*a], &self.dag[*b]);
+
+            (a.depth, &a.event_id).cmp(&(b.depth, &b.event_id))
+        };
+        let to_ids = |indices: Vec<NodeIndex>| {
+            indices
+                .into_iter()
+                .map(|idx| self.dag[idx].event_id.clone())
+                .collect::<Vec<String>>()
+        };
+
+        let mut fork_points: Vec<NodeIndex> = self
+            .events_map
+            .values()
+            .cloned()
+            .filter(|idx| children(*idx).len() > 1)
+            .collect();
+        let mut merge_points: Vec<NodeIndex> = self
+            .events_map
+            .values()
+            .cloned()
+            .filter(|idx| is_merge_point(*idx))
+            .collect();
+        let mut starts: Vec<NodeIndex> = self
+            .events_map
+            .values()
+            .cloned()
+            .filter(|idx| !continues_parent(*idx))
+            .collect();
+
+        fork_points.sort_by(by_depth);
+        merge_points.sort_by(by_depth);
+        starts.sort_by(by_depth);
+
+        // Walk down each branch from its first event
+        let mut branch_of: HashMap<NodeIndex, usize> = HashMap::new();
+        let mut branch_events: Vec<Vec<NodeIndex>> = Vec::with_capacity(starts.len());
+
+        for (id, start) in starts.into_iter().enumerate() {
+            let mut events = vec![start];
+            let mut idx = start;
+
+            branch_of.insert(start, id);
+
+            while let [child] = children(idx).as_slice() {
+                if !continues_parent(*child) {
+                    break;
+                }
+
+                idx = *child;
+                events.push(idx);
+                branch_of.insert(idx, id);
+            }
+
+            branch_events.push(events);
+        }
+
+        let mut branches: Vec<Branch> = branch_events
+            .iter()
+            .enumerate()
+            .map(|(id, events)| {
+                let mut parents: Vec<usize> = parents(events[0])
+                    .into_iter()
+                    .filter_map(|idx| branch_of.get(&idx).cloned())
+                    .collect();
+                parents.sort();
+                parents.dedup();
+
+                Branch {
+                    id,
+                    events: to_ids(events.clone()),
+                    parents,
+                    children: Vec::new(),
+                }
+            })
+            .collect();
+
+        for id in 0..branches.len() {
+            for parent in branches[id].parents.clone() {
+                branches[parent].children.push(id);
+            }
+        }
+
+        DagShape {
+            fork_points: to_ids(fork_points),
+            merge_points: to_ids(merge_points),
+            branches,
+        }
     }
 
     /// Get an `Event` from its ID.
@@ -239,103 +765,139 @@ impl RoomEvents {
             .map(|idx| self.dag.node_weight(*idx).unwrap())
     }
 
-    /// Creates a data set for creating a vis.js network.
-    pub fn create_data_set(&self) -> DataSet {
-        let server_name = self.server_name.clone();
-        let fields = self.fields.clone();
+    /// Get the IDs of every events in the DAG, sorted so they can be compared with the events of
+    /// another DAG.
+    pub fn event_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.events_map.keys().cloned().collect();
+        ids.sort();
 
-        let nodes: Vec<DataSetNode> = self
-            .events_map
-            .values()
-            .map(|idx| {
-                self.dag
-                    .node_weight(*idx)
-                    .unwrap()
-                    .to_data_set_node(&server_name, &fields)
+        ids
+    }
+
+    /// Get the name of the server this DAG was retrieved from.
+    pub fn server_name(&self) -> &str {
+        &self.server_name
+    }
+
+    /// Get the version of the room, if its `m.room.create` event has been seen.
+    pub fn room_version(&self) -> Option<RoomVersion> {
+        self.room_version
+    }
+
+    /// Get the ID of the latest events in the DAG, i.e. the events without any children.
+    pub fn latest_events(&self) -> Vec<String> {
+        self.sorted_ids(&self.latest_events)
+    }
+
+    /// Get the ID of the earliest events in the DAG, i.e. the events without any parents in the
+    /// DAG.
+    pub fn earliest_events(&self) -> Vec<String> {
+        self.sorted_ids(&self.earliest_events)
+    }
+
+    /// Get the ID and depth of the events with missing ancestors in the DAG.
+    pub fn orphan_events(&self) -> Vec<OrphanInfo> {
+        self.sorted_ids(&self.orphan_events)
+            .into_iter()
+            .map(|id| {
+                let depth = self.dag[self.events_map[&id]].depth;
+
+                OrphanInfo { id, depth }
             })
+            .collect()
+    }
+
+    /// Get the IDs of the `prev_events` of the orphan events which are missing from the DAG, i.e.
+    /// the events which would fill its gaps.
+    pub fn missing_prev_events(&self) -> Vec<String> {
+        let mut missing: Vec<String> = self
+            .orphan_events
+            .iter()
+            .flat_map(|idx| self.dag[*idx].get_prev_events())
+            .filter(|id| !self.events_map.contains_key(*id))
+            .map(str::to_string)
+            .collect();
+
+        missing.sort();
+        missing.dedup();
+
+        missing
+    }
+
+    /// Get the minimal depth of the events in the DAG, or -1 if it is empty.
+    pub fn min_depth(&self) -> i64 {
+        self.min_depth
+    }
+
+    // Get the ID of the events in `indices`, sorted so they can be compared across updates.
+    fn sorted_ids(&self, indices: &HashSet<NodeIndex>) -> Vec<String> {
+        let mut ids: Vec<String> = indices
+            .iter()
+            .map(|idx| self.dag[*idx].event_id.clone())
             .collect();
+        ids.sort();
+
+        ids
+    }
+
+    /// Creates a data set for creating a vis.js network.
+    pub fn create_data_set(&self) -> DataSet {
+        let nodes = self.data_set_nodes();
 
         let edges: Vec<DataSetEdge> = self
             .dag
             .edge_references()
             .map(|edge| {
-                let from = self
-                    .dag
-                    .node_weight(edge.source())
-                    .unwrap()
-                    .event_id
-                    .clone();
-                let to = self
-                    .dag
-                    .node_weight(edge.target())
+                self.to_data_set_edge((edge.source(), edge.target(), *edge.weight()))
                     .unwrap()
-                    .event_id
-                    .clone();
-
-                DataSetEdge {
-                    id: from.clone() + &to,
-                    from,
-                    to,
-                }
             })
             .collect();
 
         DataSet { nodes, edges }
     }
 
+    /// Creates the nodes of a data set for every events in the DAG, without the edges between
+    /// them. This is enough for updating the labels or the colors of the nodes.
+    pub fn data_set_nodes(&self) -> Vec<DataSetNode> {
+        self.events_map
+            .values()
+            .map(|idx| self.to_data_set_node(*idx))
+            .collect()
+    }
+
     /// Adds to `data_set` every events in the DAG which are earlier than the events which IDs are
     /// in `from`.
     pub fn add_earlier_events_to_data_set(&self, data_set: &mut DataSet, from: Vec<String>) {
-        let from_indices: HashSet<NodeIndex> = from.iter().map(|id| self.events_map[id]).collect();
-
-        let (new_node_indices, new_edges) = new_nodes_edges(&self.dag, from_indices);
-
-        new_node_indices
-            .iter()
-            .map(|idx| {
-                self.dag
-                    .node_weight(*idx)
-                    .unwrap()
-                    .to_data_set_node(&self.server_name, &self.fields)
-            })
-            .for_each(|node| data_set.nodes.push(node));
-
-        new_edges
-            .iter()
-            .map(|(src, dst)| self.to_data_set_edge((*src, *dst)).unwrap())
-            .for_each(|edge| data_set.edges.push(edge));
+        self.add_reachable_events_to_data_set(data_set, from, Direction::Outgoing);
     }
 
     /// Adds to `data_set` every events in the DAG which are newer than the events which IDs are
     /// in `from`.
     pub fn add_new_events_to_data_set(&self, data_set: &mut DataSet, from: Vec<String>) {
-        // TODO: Make a shadow copy instead of a real one
-        let mut rev_dag = self.dag.clone();
-        rev_dag.reverse();
-
-        let from_indices: HashSet<NodeIndex> = from.iter().map(|id| self.events_map[id]).collect();
-
-        let (new_node_indices, rev_new_edges) = new_nodes_edges(&rev_dag, from_indices);
+        self.add_reachable_events_to_data_set(data_set, from, Direction::Incoming);
+    }
 
-        // We have to reverse the edges again
-        let new_edges: HashSet<(NodeIndex, NodeIndex)> = rev_new_edges
-            .into_iter()
-            .map(|(src, dst)| (dst, src))
+    fn add_reachable_events_to_data_set(
+        &self,
+        data_set: &mut DataSet,
+        from: Vec<String>,
+        dir: Direction,
+    ) {
+        let from_indices: HashSet<NodeIndex> = from
+            .iter()
+            .filter_map(|id| self.events_map.get(id).cloned())
             .collect();
 
+        let (new_node_indices, new_edges) = new_nodes_edges(&self.dag, from_indices, dir);
+
         new_node_indices
             .iter()
-            .map(|idx| {
-                self.dag
-                    .node_weight(*idx)
-                    .unwrap()
-                    .to_data_set_node(&self.server_name, &self.fields)
-            })
+            .map(|idx| self.to_data_set_node(*idx))
             .for_each(|node| data_set.nodes.push(node));
 
         new_edges
             .iter()
-            .map(|(src, dst)| self.to_data_set_edge((*src, *dst)).unwrap())
+            .map(|(src, dst, kind)| self.to_data_set_edge((*src, *dst, *kind)).unwrap())
             .for_each(|edge| data_set.edges.push(edge));
     }
 
@@ -344,60 +906,196 @@ impl RoomEvents {
         self.fields = fields.clone();
     }
 
-    fn to_data_set_edge(&self, (src, dst): (NodeIndex, NodeIndex)) -> Option<DataSetEdge> {
+    fn to_data_set_node(&self, idx: NodeIndex) -> DataSetNode {
+        self.dag[idx].to_data_set_node(&self.server_name, &self.fields)
+    }
+
+    fn to_data_set_edge(
+        &self,
+        (src, dst, kind): (NodeIndex, NodeIndex, EdgeKind),
+    ) -> Option<DataSetEdge> {
         let from = self.dag.node_weight(src)?.event_id.clone();
         let to = self.dag.node_weight(dst)?.event_id.clone();
 
-        Some(DataSetEdge {
-            id: from.clone() + &to,
-            from,
-            to,
-        })
+        Some(DataSetEdge::new(from, to, kind))
+    }
+}
+
+// Parses a list of events encoded as JSON values, according to the format of the events of the
+// room version `room_version`. The events which can't be parsed are returned apart from the
+// others.
+fn parse_events(
+    json_events: &[JsonValue],
+    room_version: Option<RoomVersion>,
+) -> (Vec<Event>, Vec<ParseError>) {
+    let mut events = Vec::with_capacity(json_events.len());
+    let mut rejected = Vec::new();
+
+    for ev in json_events {
+        match Event::from_json(ev, room_version) {
+            Ok(event) => events.push(event),
+            Err(err) => rejected.push(err),
+        }
     }
+
+    (events, rejected)
 }
 
-// Parses a list of events encoded as JSON values.
-fn parse_events(json_events: &[JsonValue]) -> Vec<Event> {
+// Looks for the `m.room.create` event among `json_events` and reads the version of the room from
+// it.
+fn find_room_version(json_events: &[JsonValue]) -> Option<RoomVersion> {
     json_events
         .iter()
-        .map(|ev| {
-            serde_json::from_value(ev.clone()).unwrap_or_else(|_| {
-                panic!(
-                    "Failed to parse event:\n{}",
-                    serde_json::to_string_pretty(&ev).expect("Failed to fail...")
-                )
-            })
-        })
-        .collect()
+        .find(|ev| ev["type"] == "m.room.create" && ev["state_key"] == "")
+        .and_then(|ev| RoomVersion::from_create_content(&ev["content"]))
 }
 
+// Walks along the `prev_events` edges of `dag` in the direction `dir` from the events in
+// `from_indices`, i.e. towards the earlier events for `Outgoing` and towards the newer events for
+// `Incoming`, without having to reverse the graph. Returns the events which have been reached and
+// the edges between them and the rest of the DAG.
 fn new_nodes_edges(
-    dag: &Graph<Event, ()>,
+    dag: &Graph<Event, EdgeKind>,
     from_indices: HashSet<NodeIndex>,
-) -> (HashSet<NodeIndex>, HashSet<(NodeIndex, NodeIndex)>) {
-    let mut node_indices: HashSet<NodeIndex> = HashSet::from_iter(from_indices.iter().cloned());
-
-    for &from_idx in from_indices.iter() {
-        let mut bfs = Bfs::new(&dag, from_idx);
-
-        while let Some(idx) = bfs.next(&dag) {
-            node_indices.insert(idx);
+    dir: Direction,
+) -> (
+    HashSet<NodeIndex>,
+    HashSet<(NodeIndex, NodeIndex, EdgeKind)>,
+) {
+    let mut node_indices: HashSet<NodeIndex> = from_indices.clone();
+    let mut queue: VecDeque<NodeIndex> = from_indices.iter().cloned().collect();
+
+    // Only walk along the `prev_events` edges, the auth chain of an event doesn't tell whether it
+    // is earlier or newer than the events already displayed
+    while let Some(idx) = queue.pop_front() {
+        for e in dag
+            .edges_directed(idx, dir)
+            .filter(|e| *e.weight() == EdgeKind::Prev)
+        {
+            let next_idx = match dir {
+                Direction::Outgoing => e.target(),
+                Direction::Incoming => e.source(),
+            };
+
+            if node_indices.insert(next_idx) {
+                queue.push_back(next_idx);
+            }
         }
     }
 
     let new_node_indices: HashSet<NodeIndex> =
         node_indices.difference(&from_indices).cloned().collect();
 
-    let mut new_edges: HashSet<(NodeIndex, NodeIndex)> = HashSet::new();
+    // The edges linking the new events to the events they have been reached from
+    let mut new_edges: HashSet<(NodeIndex, NodeIndex, EdgeKind)> = HashSet::new();
 
     for edges in new_node_indices
         .iter()
-        .map(|idx| dag.edges_directed(*idx, Direction::Incoming))
+        .map(|idx| dag.edges_directed(*idx, dir.opposite()))
     {
         for e in edges {
-            new_edges.insert((e.source(), e.target()));
+            new_edges.insert((e.source(), e.target(), *e.weight()));
         }
     }
 
     (new_node_indices, new_edges)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::testing::Pdu;
+
+    fn batch() -> Batch {
+        Batch {
+            kind: BatchKind::File,
+            received_at: 0.0,
+        }
+    }
+
+    #[test]
+    fn quarantined_events_parsed_with_room_version() {
+        let create = Pdu::new("m.room.create")
+            .id("$create")
+            .state_key("")
+            .content(json!({ "creator": "@alice:a.example", "room_version": "6" }))
+            .build();
+        // Since rooms v3, the ID of an event is its reference hash rather than a field
+        let message = Pdu::new("m.room.message")
+            .content(json!({ "body": "Hello" }))
+            .depth(2)
+            .prev_events(&["$create"])
+            .auth_events(&["$create"])
+            .build();
+
+        let mut dag = RoomEvents::new("a.example", &HashSet::new());
+
+        dag.add_events(vec![message], batch());
+        assert!(dag.events().is_empty());
+        assert_eq!(dag.quarantine().len(), 1);
+
+        dag.add_events(vec![create], batch());
+        assert_eq!(dag.events().len(), 2);
+        assert!(dag.quarantine().is_empty());
+        assert!(dag.orphan_events().is_empty());
+    }
+
+    #[test]
+    fn shape_with_fork_and_merge() {
+        let event = |id: &str, etype: &str, prev_events: &[&str], depth: i64| {
+            let content = if etype == "m.room.create" {
+                json!({ "creator": "@alice:a.example", "room_version": "6" })
+            } else {
+                json!({})
+            };
+
+            Pdu::new(etype)
+                .id(id)
+                .state_key("")
+                .content(content)
+                .depth(depth)
+                .prev_events(prev_events)
+                .build()
+        };
+
+        // `$a` forks into `$b` and `$c`, which are merged back by `$d`
+        let mut dag = RoomEvents::new("a.example", &HashSet::new());
+        dag.add_events(
+            vec![
+                event("$create", "m.room.create", &[], 1),
+                event("$a", "m.room.topic", &["$create"], 2),
+                event("$b", "m.room.topic", &["$a"], 3),
+                event("$c", "m.room.name", &["$a"], 3),
+                event("$d", "m.room.topic", &["$b", "$c"], 4),
+            ],
+            batch(),
+        );
+
+        let shape = dag.shape();
+
+        assert_eq!(shape.fork_points, vec!["$a"]);
+        assert_eq!(shape.merge_points, vec!["$d"]);
+
+        let branches: Vec<(Vec<&str>, Vec<usize>, Vec<usize>)> = shape
+            .branches
+            .iter()
+            .map(|branch| {
+                (
+                    branch.events.iter().map(String::as_str).collect(),
+                    branch.parents.clone(),
+                    branch.children.clone(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            branches,
+            vec![
+                (vec!["$create", "$a"], vec![], vec![1, 2]),
+                (vec!["$b"], vec![0], vec![3]),
+                (vec!["$c"], vec![0], vec![3]),
+                (vec!["$d"], vec![1, 2], vec![]),
+            ]
+        );
+    }
+}
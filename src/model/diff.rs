@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use serde_derive::Serialize;
+
+use super::dag::RoomEvents;
+
+/// The differences between the DAGs of the same room retrieved by two views, usually from two
+/// different homeservers. This helps to see how the DAGs have diverged over federation.
+#[derive(Clone, Debug, Serialize)]
+pub struct DagDiff {
+    pub only_in_a: Vec<MissingEvent>, // The events of A which are missing from B
+    pub only_in_b: Vec<MissingEvent>, // The events of B which are missing from A
+    pub shared: Vec<String>,          // The IDs of the events which are in both DAGs
+    pub extremities_a: Vec<String>,   // The forward extremities of A
+    pub extremities_b: Vec<String>,   // The forward extremities of B
+}
+
+/// An event which is missing from one of the DAGs, along with its depth in the other DAG so it
+/// can be placed in the vis.js network.
+#[derive(Clone, Debug, Serialize)]
+pub struct MissingEvent {
+    pub id: String,
+    pub depth: i64,
+}
+
+impl DagDiff {
+    /// Compares the DAG `a` with the DAG `b`.
+    pub fn new(a: &RoomEvents, b: &RoomEvents) -> DagDiff {
+        let ids_a = a.event_ids();
+        let ids_b = b.event_ids();
+
+        let set_a: HashSet<&String> = ids_a.iter().collect();
+        let set_b: HashSet<&String> = ids_b.iter().collect();
+
+        DagDiff {
+            only_in_a: missing_events(a, &ids_a, &set_b),
+            only_in_b: missing_events(b, &ids_b, &set_a),
+            shared: ids_a
+                .iter()
+                .filter(|id| set_b.contains(id))
+                .cloned()
+                .collect(),
+            extremities_a: a.latest_events(),
+            extremities_b: b.latest_events(),
+        }
+    }
+
+    /// Whether the forward extremities of both DAGs are different.
+    pub fn extremities_differ(&self) -> bool {
+        self.extremities_a != self.extremities_b
+    }
+}
+
+// Get the events of `dag` whose IDs are in `ids` but not in `other`, with their depth in `dag`.
+fn missing_events(dag: &RoomEvents, ids: &[String], other: &HashSet<&String>) -> Vec<MissingEvent> {
+    ids.iter()
+        .filter(|id| !other.contains(id))
+        .map(|id| MissingEvent {
+            id: id.clone(),
+            depth: dag.get_event(id).map_or(-1, |ev| ev.depth),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::Value as JsonValue;
+
+    use crate::model::dag::{Batch, BatchKind};
+    use crate::model::testing::Pdu;
+
+    fn event(id: &str, depth: i64, prev_events: &[&str]) -> JsonValue {
+        Pdu::new("m.room.message")
+            .id(id)
+            .content(json!({ "body": "Hello" }))
+            .depth(depth)
+            .prev_events(prev_events)
+            .pairs()
+            .build()
+    }
+
+    fn room_events(server_name: &str, events: Vec<JsonValue>) -> RoomEvents {
+        let mut dag = RoomEvents::new(server_name, &HashSet::new());
+        dag.add_events(
+            events,
+            Batch {
+                kind: BatchKind::File,
+                received_at: 0.0,
+            },
+        );
+
+        dag
+    }
+
+    fn ids_and_depths(events: &[MissingEvent]) -> Vec<(&str, i64)> {
+        events.iter().map(|ev| (ev.id.as_str(), ev.depth)).collect()
+    }
+
+    #[test]
+    fn divergent_dags() {
+        // Both DAGs share their first two events and then diverge
+        let shared = vec![event("$1", 1, &[]), event("$2", 2, &["$1"])];
+        let mut events_a = shared.clone();
+        events_a.extend(vec![event("$a3", 3, &["$2"]), event("$a4", 4, &["$a3"])]);
+        let mut events_b = shared;
+        events_b.push(event("$b3", 3, &["$2"]));
+
+        let a = room_events("a.example", events_a);
+        let b = room_events("b.example", events_b);
+        let diff = DagDiff::new(&a, &b);
+
+        assert_eq!(
+            ids_and_depths(&diff.only_in_a),
+            vec![("$a3", 3), ("$a4", 4)]
+        );
+        assert_eq!(ids_and_depths(&diff.only_in_b), vec![("$b3", 3)]);
+        assert_eq!(diff.shared, vec!["$1", "$2"]);
+        assert_eq!(diff.extremities_a, vec!["$a4"]);
+        assert_eq!(diff.extremities_b, vec!["$b3"]);
+        assert!(diff.extremities_differ());
+
+        let diff = DagDiff::new(&a, &a);
+
+        assert!(diff.only_in_a.is_empty());
+        assert!(diff.only_in_b.is_empty());
+        assert_eq!(diff.shared, vec!["$1", "$2", "$a3", "$a4"]);
+        assert!(!diff.extremities_differ());
+    }
+}
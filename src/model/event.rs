@@ -1,31 +1,99 @@
 use std::collections::HashSet;
+use std::fmt;
 
+use failure::Fail;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
+use super::crypto::{self, HashCheck, ServerKeys, SignatureCheck};
 use super::dag::{DataSetNode, NodeColor};
+use super::room_version::{EventFormat, RoomVersion};
 
 /// The internal representation of an event in the DAG.
 #[derive(Default, Clone, Deserialize, Serialize)]
 pub struct Event {
-    room_id: String,       // Room identifier
-    sender: String,        // The ID of the user who has sent this event
-    origin: String,        // The `server_name` of the homeserver which created this event
-    origin_server_ts: i64, // Timestamp in milliseconds on origin homeserver when this event was created
+    pub room_id: String, // Room identifier
+    pub sender: String,  // The ID of the user who has sent this event
+    #[serde(default)]
+    origin: String, // The `server_name` of the homeserver which created this event
+    pub origin_server_ts: i64, // Timestamp in milliseconds on origin homeserver when this event was created
     #[serde(rename = "type")]
-    etype: String, // Event type
-    state_key: Option<String>, // Indicate whether this event is a state event
-    content: JsonValue,    // The content of the event
+    pub etype: String, // Event type
+    pub state_key: Option<String>, // Indicate whether this event is a state event
+    pub content: JsonValue,    // The content of the event
     prev_events: Vec<JsonValue>, // Event IDs for the most recent events in the room that the homeserver was aware of when it made this event
     pub depth: i64,              // The maximum depth of the `prev_events`, plus one
     auth_events: Vec<JsonValue>, // Event IDs and reference hashes for the authorization events that would allow this event to be in the room
-    redacts: Option<String>,     // For redaction events, the ID of the event being redacted
+    pub redacts: Option<String>, // For redaction events, the ID of the event being redacted
     unsigned: Option<JsonValue>, // Additional data added by the origin server but not covered by the `signatures`
-    pub event_id: String,        // The event ID
+    #[serde(default)]
+    pub event_id: String, // The event ID, computed from the reference hash of the event when it is missing
     hashes: JsonValue, // Content hashes of the PDU, following the algorithm specified in `Signing Events`
     signatures: JsonValue, // Signatures for the PDU, following the algorithm specified in `Signing Events`
+
+    #[serde(skip)]
+    prev_event_ids: Vec<String>, // The IDs of the `prev_events`, whatever the format of the event is
+    #[serde(skip)]
+    auth_event_ids: Vec<String>, // The IDs of the `auth_events`, whatever the format of the event is
+    #[serde(skip)]
+    json: JsonValue, // The JSON body of the event, as it has been received
+    #[serde(skip)]
+    pub hash_check: HashCheck, // Whether the content hash of the event matches the event
+    #[serde(skip)]
+    pub signature_check: SignatureCheck, // Whether the event is signed by the servers which had to sign it
+}
+
+/// The fields which must be in the JSON body of every event.
+const REQUIRED_FIELDS: [&str; 10] = [
+    "room_id",
+    "sender",
+    "origin_server_ts",
+    "type",
+    "content",
+    "prev_events",
+    "depth",
+    "auth_events",
+    "hashes",
+    "signatures",
+];
+
+/// An event which has been rejected when parsing its JSON body.
+#[derive(Clone, Debug)]
+pub struct ParseError {
+    pub json: JsonValue,      // The raw JSON body of the event
+    pub kind: ParseErrorKind, // Why the event has been rejected
 }
 
+/// The reasons for which an event can be rejected when parsing it.
+#[derive(Clone, Debug)]
+pub enum ParseErrorKind {
+    NotAnObject,
+    MissingField(&'static str),
+    InvalidField(&'static str),
+    Serde(String), // The message of the `serde_json` error, which is not `Clone`
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl Fail for ParseError {}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::NotAnObject => write!(f, "The event is not a JSON object"),
+            ParseErrorKind::MissingField(field) => write!(f, "Missing field `{}`", field),
+            ParseErrorKind::InvalidField(field) => write!(f, "Invalid field `{}`", field),
+            ParseErrorKind::Serde(msg) => write!(f, "Failed to deserialize the event: {}", msg),
+        }
+    }
+}
+
+impl Fail for ParseErrorKind {}
+
 /// Defines the fields of the events which will be included in the labels of the DAG's nodes.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Field {
@@ -41,39 +109,116 @@ pub enum Field {
 }
 
 impl Event {
-    /// This function is needed because the content of a the `prev_events` field can change
-    /// across the versions of rooms.
+    /// Parses the JSON body of an event from a room of version `version`.
+    ///
+    /// The references to other events are read according to the format of the events of this
+    /// version and the ID of the event is computed from its reference hash if it has no
+    /// `event_id` field. If the version of the room is not known, both formats of references are
+    /// accepted but the event must have an `event_id` field.
+    pub fn from_json(json: &JsonValue, version: Option<RoomVersion>) -> Result<Event, ParseError> {
+        let reject = |kind| ParseError {
+            json: json.clone(),
+            kind,
+        };
+
+        let object = json
+            .as_object()
+            .ok_or_else(|| reject(ParseErrorKind::NotAnObject))?;
+
+        if let Some(field) = REQUIRED_FIELDS.iter().find(|f| !object.contains_key(**f)) {
+            return Err(reject(ParseErrorKind::MissingField(field)));
+        }
+
+        let mut event: Event = serde_json::from_value(json.clone())
+            .map_err(|e| reject(ParseErrorKind::Serde(e.to_string())))?;
+        let format = version.map(RoomVersion::event_format);
+
+        event.prev_event_ids = parse_event_refs(&event.prev_events, format)
+            .ok_or_else(|| reject(ParseErrorKind::InvalidField("prev_events")))?;
+        event.auth_event_ids = parse_event_refs(&event.auth_events, format)
+            .ok_or_else(|| reject(ParseErrorKind::InvalidField("auth_events")))?;
+
+        // The `origin` field is not covered by the redaction algorithm anymore in recent room
+        // versions, the homeserver of the sender is used instead when it is missing
+        if event.origin.is_empty() {
+            event.origin = event
+                .sender
+                .split_once(':')
+                .map_or("", |(_, server)| server)
+                .to_string();
+        }
+
+        if event.event_id.is_empty() {
+            event.event_id = version
+                .and_then(|version| crypto::compute_event_id(json, version))
+                .ok_or_else(|| reject(ParseErrorKind::MissingField("event_id")))?;
+        }
+
+        event.hash_check = crypto::check_content_hash(json, version);
+        event.json = json.clone();
+
+        Ok(event)
+    }
+
+    /// Returns the `server_name` of the homeserver which created the event.
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    /// Returns the JSON body of the event, as it has been received.
+    pub fn json(&self) -> &JsonValue {
+        &self.json
+    }
+
+    /// Checks the content hash of the event again, once the version of the room is known.
+    pub fn check_content_hash(&mut self, version: RoomVersion) {
+        self.hash_check = crypto::check_content_hash(&self.json, Some(version));
+    }
+
+    /// Checks the signatures of the event with the keys of the servers in `server_keys`.
+    pub fn check_signatures(&mut self, version: Option<RoomVersion>, server_keys: &ServerKeys) {
+        self.signature_check = crypto::check_signatures(&self.json, version, server_keys);
+    }
+
+    /// Returns the IDs of the `prev_events` of the event. The content of this field can change
+    /// across the versions of rooms, it is read when the event is parsed.
     pub fn get_prev_events(&self) -> Vec<&str> {
-        self.prev_events
-            .iter()
-            .map(|prev_ev| {
-                if prev_ev.is_array() {
-                    prev_ev[0].as_str().unwrap()
-                } else {
-                    prev_ev.as_str().unwrap()
-                }
-            })
-            .collect()
+        self.prev_event_ids.iter().map(String::as_str).collect()
+    }
+
+    /// Returns the IDs of the `auth_events` of the event, the same way as `get_prev_events`.
+    pub fn get_auth_events(&self) -> Vec<&str> {
+        self.auth_event_ids.iter().map(String::as_str).collect()
     }
 
     /// Convert an event in a format usable by vis.js.
     /// `server_name` must be the HS from which the DAG was retrieved for coloring the node.
     /// `fields` is a set of events fields to include in the label.
     pub fn to_data_set_node(&self, server_name: &str, fields: &HashSet<Field>) -> DataSetNode {
-        let (border_color, background_color) = if self.origin == server_name {
-            ("#006633".to_string(), "#009900".to_string())
-        } else {
-            ("#990000".to_string(), "#ff6600".to_string())
+        // The events with bad or missing signatures have their own colours
+        let (border_color, background_color) = match self.signature_check {
+            SignatureCheck::Bad => ("#990033".to_string(), "#ff3399".to_string()),
+            SignatureCheck::Missing => ("#996600".to_string(), "#ffcc00".to_string()),
+            _ if self.origin == server_name => ("#006633".to_string(), "#009900".to_string()),
+            _ => ("#990000".to_string(), "#ff6600".to_string()),
+        };
+
+        // The border shows whether the content hash of the event matches the event
+        let (border_color, border_width) = match self.hash_check {
+            HashCheck::Verified => (border_color, 1),
+            HashCheck::Mismatch => ("#ff0000".to_string(), 4),
+            HashCheck::Unverifiable => ("#999999".to_string(), 2),
         };
 
         DataSetNode {
             id: self.event_id.clone(),
-            label: self.label(&fields),
+            label: self.label(fields),
             level: self.depth,
             color: NodeColor {
                 border: border_color,
                 background: background_color,
             },
+            border_width,
         }
     }
 
@@ -134,6 +279,21 @@ impl Event {
     }
 }
 
+// Reads the IDs of the events referenced in the `prev_events` or `auth_events` field of an event.
+// They are `[event_id, hashes]` pairs in the `V1` format and event IDs in the others.
+fn parse_event_refs(refs: &[JsonValue], format: Option<EventFormat>) -> Option<Vec<String>> {
+    refs.iter()
+        .map(|event_ref| match (format, event_ref) {
+            (Some(EventFormat::V1), JsonValue::Array(pair)) | (None, JsonValue::Array(pair)) => {
+                pair.first().and_then(JsonValue::as_str).map(String::from)
+            }
+            (Some(EventFormat::V1), _) => None,
+            (_, JsonValue::String(id)) => Some(id.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
 impl PartialEq for Event {
     fn eq(&self, other: &Event) -> bool {
         self.event_id == other.event_id
@@ -141,3 +301,55 @@ impl PartialEq for Event {
 }
 
 impl Eq for Event {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::testing::Pdu;
+
+    fn message() -> Pdu {
+        Pdu::new("m.room.message")
+            .content(json!({ "body": "Hello" }))
+            .depth(2)
+    }
+
+    #[test]
+    fn v1_event_references() {
+        let json = message()
+            .id("$event:a.example")
+            .prev_events(&["$prev:a.example"])
+            .pairs()
+            .build();
+
+        let event = Event::from_json(&json, Some(RoomVersion::V1)).unwrap();
+
+        assert_eq!(event.event_id, "$event:a.example");
+        assert_eq!(event.get_prev_events(), vec!["$prev:a.example"]);
+        assert_eq!(event.origin, "a.example");
+
+        // Since rooms v3, the events are referenced by their IDs only
+        assert!(Event::from_json(&json, Some(RoomVersion::V3)).is_err());
+    }
+
+    #[test]
+    fn reference_hash_event_ids() {
+        let json = message().prev_events(&["$prev"]).build();
+
+        let v3 = Event::from_json(&json, Some(RoomVersion::V3)).unwrap();
+        let v4 = Event::from_json(&json, Some(RoomVersion::V4)).unwrap();
+
+        assert_eq!(v3.get_prev_events(), vec!["$prev"]);
+        assert_eq!(
+            Some(v3.event_id),
+            crypto::compute_event_id(&json, RoomVersion::V3)
+        );
+        assert_eq!(
+            Some(v4.event_id),
+            crypto::compute_event_id(&json, RoomVersion::V4)
+        );
+
+        // The ID of an event can't be computed without the version of its room
+        assert!(Event::from_json(&json, None).is_err());
+        assert!(Event::from_json(&json, Some(RoomVersion::V1)).is_err());
+    }
+}
@@ -0,0 +1,519 @@
+use super::dag::{DataSet, EdgeKind, RoomEvents};
+use super::event::Event;
+
+// The attributes of the nodes in the GraphML and GEXF exports, with their GraphML type.
+const NODE_ATTRIBUTES: [(&str, &str); 8] = [
+    ("view", "int"),
+    ("event_id", "string"),
+    ("sender", "string"),
+    ("origin", "string"),
+    ("type", "string"),
+    ("state_key", "string"),
+    ("depth", "long"),
+    ("origin_server_ts", "long"),
+];
+
+// The nodes and edges of the DAGs of several views, with the IDs of the nodes prefixed by the
+// index of their view so they don't collide across views.
+struct Graph<'a> {
+    nodes: Vec<(String, usize, &'a Event)>,
+    edges: Vec<(String, String, EdgeKind)>,
+}
+
+/// Exports the DAGs of the views in `views`, given with the index of their view, as a Graphviz
+/// DOT document. Each view is drawn in its own cluster and only the edges whose kind is in
+/// `edge_kinds` are included.
+///
+/// The nodes have the same labels and colors as in the vis.js network, and the earliest events
+/// are at the top of the graph as well.
+pub fn to_dot(views: &[(usize, &RoomEvents)], edge_kinds: &[EdgeKind]) -> String {
+    let mut dot = String::new();
+
+    dot.push_str("digraph \"events\" {\n");
+    dot.push_str("    rankdir=BT;\n");
+    dot.push_str("    node [shape=box, style=filled];\n");
+
+    for (view_id, events_dag) in views {
+        let data_set = view_data_set(*view_id, events_dag);
+
+        dot.push_str(&format!("\n    subgraph \"cluster_{}\" {{\n", view_id));
+        dot.push_str(&format!(
+            "        label=\"{}\";\n",
+            escape_dot(&format!(
+                "View {}: {}",
+                view_id + 1,
+                events_dag.server_name()
+            ))
+        ));
+
+        for node in &data_set.nodes {
+            dot.push_str(&format!(
+                "        \"{}\" [label=\"{}\", color=\"{}\", fillcolor=\"{}\", penwidth={}];\n",
+                escape_dot(&node.id),
+                escape_dot(&node.label),
+                node.color.border,
+                node.color.background,
+                node.border_width,
+            ));
+        }
+
+        dot.push_str("    }\n\n");
+
+        for edge in data_set
+            .edges
+            .iter()
+            .filter(|edge| edge_kinds.contains(&edge.kind))
+        {
+            let style = match edge.kind {
+                EdgeKind::Prev => "",
+                EdgeKind::Auth => " [style=dashed, color=\"#9933cc\"]",
+            };
+
+            dot.push_str(&format!(
+                "    \"{}\" -> \"{}\"{};\n",
+                escape_dot(&edge.from),
+                escape_dot(&edge.to),
+                style
+            ));
+        }
+    }
+
+    dot.push_str("}\n");
+
+    dot
+}
+
+/// Exports the DAGs of the views in `views`, given with the index of their view, as a GraphML
+/// document. The fields of the events are attributes of the nodes and the kind of the edges is
+/// an attribute of the edges. Both the `prev_events` and the `auth_events` edges are included,
+/// since the graph tools can filter them by their kind.
+pub fn to_graphml(views: &[(usize, &RoomEvents)]) -> String {
+    let graph = graph(views);
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+
+    for (name, attr_type) in NODE_ATTRIBUTES.iter() {
+        xml.push_str(&format!(
+            "  <key id=\"{0}\" for=\"node\" attr.name=\"{0}\" attr.type=\"{1}\"/>\n",
+            name, attr_type
+        ));
+    }
+    xml.push_str("  <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n");
+
+    xml.push_str("  <graph id=\"events\" edgedefault=\"directed\">\n");
+
+    for (id, view_id, event) in &graph.nodes {
+        xml.push_str(&format!("    <node id=\"{}\">\n", escape_xml(id)));
+
+        for (name, value) in node_attributes(*view_id, event) {
+            xml.push_str(&format!(
+                "      <data key=\"{}\">{}</data>\n",
+                name,
+                escape_xml(&value)
+            ));
+        }
+
+        xml.push_str("    </node>\n");
+    }
+
+    for (from, to, kind) in &graph.edges {
+        xml.push_str(&format!(
+            "    <edge source=\"{}\" target=\"{}\">\n",
+            escape_xml(from),
+            escape_xml(to)
+        ));
+        xml.push_str(&format!(
+            "      <data key=\"kind\">{}</data>\n",
+            edge_kind(*kind)
+        ));
+        xml.push_str("    </edge>\n");
+    }
+
+    xml.push_str("  </graph>\n");
+    xml.push_str("</graphml>\n");
+
+    xml
+}
+
+/// Exports the DAGs of the views in `views`, given with the index of their view, as a GEXF
+/// document, with the same attributes and edges as `to_graphml`.
+pub fn to_gexf(views: &[(usize, &RoomEvents)]) -> String {
+    let graph = graph(views);
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<gexf xmlns=\"http://www.gexf.net/1.2draft\" version=\"1.2\">\n");
+    xml.push_str("  <graph mode=\"static\" defaultedgetype=\"directed\">\n");
+
+    xml.push_str("    <attributes class=\"node\">\n");
+    for (i, (name, attr_type)) in NODE_ATTRIBUTES.iter().enumerate() {
+        // GEXF calls `int` `integer`
+        let attr_type = if *attr_type == "int" {
+            "integer"
+        } else {
+            attr_type
+        };
+
+        xml.push_str(&format!(
+            "      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>\n",
+            i, name, attr_type
+        ));
+    }
+    xml.push_str("    </attributes>\n");
+
+    xml.push_str("    <attributes class=\"edge\">\n");
+    xml.push_str("      <attribute id=\"0\" title=\"kind\" type=\"string\"/>\n");
+    xml.push_str("    </attributes>\n");
+
+    xml.push_str("    <nodes>\n");
+    for (id, view_id, event) in &graph.nodes {
+        xml.push_str(&format!(
+            "      <node id=\"{}\" label=\"{}\">\n",
+            escape_xml(id),
+            escape_xml(&event.event_id)
+        ));
+        xml.push_str("        <attvalues>\n");
+
+        for (name, value) in node_attributes(*view_id, event) {
+            let i = NODE_ATTRIBUTES
+                .iter()
+                .position(|(attr, _)| *attr == name)
+                .unwrap();
+
+            xml.push_str(&format!(
+                "          <attvalue for=\"{}\" value=\"{}\"/>\n",
+                i,
+                escape_xml(&value)
+            ));
+        }
+
+        xml.push_str("        </attvalues>\n");
+        xml.push_str("      </node>\n");
+    }
+    xml.push_str("    </nodes>\n");
+
+    xml.push_str("    <edges>\n");
+    for (i, (from, to, kind)) in graph.edges.iter().enumerate() {
+        xml.push_str(&format!(
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\">\n",
+            i,
+            escape_xml(from),
+            escape_xml(to)
+        ));
+        xml.push_str(&format!(
+            "        <attvalues><attvalue for=\"0\" value=\"{}\"/></attvalues>\n",
+            edge_kind(*kind)
+        ));
+        xml.push_str("      </edge>\n");
+    }
+    xml.push_str("    </edges>\n");
+
+    xml.push_str("  </graph>\n");
+    xml.push_str("</gexf>\n");
+
+    xml
+}
+
+// Gathers the events and the edges of the DAGs of every views in `views`.
+fn graph<'a>(views: &[(usize, &'a RoomEvents)]) -> Graph<'a> {
+    let mut graph = Graph {
+        nodes: Vec::new(),
+        edges: Vec::new(),
+    };
+
+    for (view_id, events_dag) in views {
+        let prefix = format!("subdag_{}_", view_id);
+
+        for event in events_dag.events() {
+            graph
+                .nodes
+                .push((prefix.clone() + &event.event_id, *view_id, event));
+        }
+
+        for edge in events_dag.create_data_set().edges {
+            graph.edges.push((
+                prefix.clone() + &edge.from,
+                prefix.clone() + &edge.to,
+                edge.kind,
+            ));
+        }
+    }
+
+    graph
+}
+
+// Get the values of the attributes of the node of `event` in the view `view_id`. The state key
+// is left out for the events which aren't state events.
+fn node_attributes(view_id: usize, event: &Event) -> Vec<(&'static str, String)> {
+    let mut attributes = vec![
+        ("view", view_id.to_string()),
+        ("event_id", event.event_id.clone()),
+        ("sender", event.sender.clone()),
+        ("origin", event.origin().to_string()),
+        ("type", event.etype.clone()),
+    ];
+
+    if let Some(state_key) = &event.state_key {
+        attributes.push(("state_key", state_key.clone()));
+    }
+
+    attributes.push(("depth", event.depth.to_string()));
+    attributes.push(("origin_server_ts", event.origin_server_ts.to_string()));
+
+    attributes
+}
+
+fn edge_kind(kind: EdgeKind) -> &'static str {
+    match kind {
+        EdgeKind::Prev => "prev",
+        EdgeKind::Auth => "auth",
+    }
+}
+
+// Creates the data set of the DAG of the view `view_id`, with the prefix of the view so the IDs
+// of the nodes don't collide across views. The nodes are sorted so the exports are reproducible.
+fn view_data_set(view_id: usize, events_dag: &RoomEvents) -> DataSet {
+    let mut data_set = events_dag.create_data_set();
+
+    data_set
+        .nodes
+        .sort_by(|a, b| (a.level, &a.id).cmp(&(b.level, &b.id)));
+    data_set.add_prefix(&format!("subdag_{}_", view_id));
+
+    data_set
+}
+
+// Escapes a string so it can be put between double quotes in a DOT document.
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+// Escapes a string so it can be put in the text or in the attributes of an XML element.
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::model::dag::{Batch, BatchKind};
+    use crate::model::testing::Pdu;
+
+    // A room with a state key which has characters that must be escaped in the XML formats
+    fn room_events() -> RoomEvents {
+        let create = Pdu::new("m.room.create")
+            .id("$create")
+            .state_key("")
+            .content(json!({ "creator": "@alice:a.example", "room_version": "1" }))
+            .pairs()
+            .build();
+        let topic = Pdu::new("m.room.topic")
+            .id("$topic")
+            .state_key("<\"&'>")
+            .content(json!({ "topic": "Hello" }))
+            .depth(2)
+            .prev_events(&["$create"])
+            .auth_events(&["$create"])
+            .pairs()
+            .build();
+
+        let mut dag = RoomEvents::new("a.example", &HashSet::new());
+        dag.add_events(
+            vec![create, topic],
+            Batch {
+                kind: BatchKind::File,
+                received_at: 0.0,
+            },
+        );
+
+        dag
+    }
+
+    #[test]
+    fn dot_escaping() {
+        assert_eq!(escape_dot("$event:a.example"), "$event:a.example");
+        assert_eq!(escape_dot("say \"hi\"\nC:\\"), "say \\\"hi\\\"\\nC:\\\\");
+    }
+
+    #[test]
+    fn dot_document() {
+        let dag = room_events();
+
+        assert_eq!(
+            to_dot(&[(0, &dag)], &[EdgeKind::Prev, EdgeKind::Auth]),
+            r##"digraph "events" {
+    rankdir=BT;
+    node [shape=box, style=filled];
+
+    subgraph "cluster_0" {
+        label="View 1: a.example";
+        "subdag_0_$create" [label="", color="#999999", fillcolor="#ffcc00", penwidth=2];
+        "subdag_0_$topic" [label="", color="#999999", fillcolor="#ffcc00", penwidth=2];
+    }
+
+    "subdag_0_$topic" -> "subdag_0_$create";
+    "subdag_0_$topic" -> "subdag_0_$create" [style=dashed, color="#9933cc"];
+}
+"##
+        );
+
+        // Each view has its own cluster and only the chosen kinds of edges are drawn
+        assert_eq!(
+            to_dot(&[(0, &dag), (1, &dag)], &[EdgeKind::Auth]),
+            r##"digraph "events" {
+    rankdir=BT;
+    node [shape=box, style=filled];
+
+    subgraph "cluster_0" {
+        label="View 1: a.example";
+        "subdag_0_$create" [label="", color="#999999", fillcolor="#ffcc00", penwidth=2];
+        "subdag_0_$topic" [label="", color="#999999", fillcolor="#ffcc00", penwidth=2];
+    }
+
+    "subdag_0_$topic" -> "subdag_0_$create" [style=dashed, color="#9933cc"];
+
+    subgraph "cluster_1" {
+        label="View 2: a.example";
+        "subdag_1_$create" [label="", color="#999999", fillcolor="#ffcc00", penwidth=2];
+        "subdag_1_$topic" [label="", color="#999999", fillcolor="#ffcc00", penwidth=2];
+    }
+
+    "subdag_1_$topic" -> "subdag_1_$create" [style=dashed, color="#9933cc"];
+}
+"##
+        );
+    }
+
+    #[test]
+    fn xml_escaping() {
+        assert_eq!(escape_xml("m.room.topic"), "m.room.topic");
+        assert_eq!(
+            escape_xml(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+        // Ampersands are escaped first so the entities aren't escaped twice
+        assert_eq!(escape_xml("&lt;"), "&amp;lt;");
+    }
+
+    #[test]
+    fn graphml_document() {
+        let dag = room_events();
+
+        assert_eq!(
+            to_graphml(&[(0, &dag)]),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="view" for="node" attr.name="view" attr.type="int"/>
+  <key id="event_id" for="node" attr.name="event_id" attr.type="string"/>
+  <key id="sender" for="node" attr.name="sender" attr.type="string"/>
+  <key id="origin" for="node" attr.name="origin" attr.type="string"/>
+  <key id="type" for="node" attr.name="type" attr.type="string"/>
+  <key id="state_key" for="node" attr.name="state_key" attr.type="string"/>
+  <key id="depth" for="node" attr.name="depth" attr.type="long"/>
+  <key id="origin_server_ts" for="node" attr.name="origin_server_ts" attr.type="long"/>
+  <key id="kind" for="edge" attr.name="kind" attr.type="string"/>
+  <graph id="events" edgedefault="directed">
+    <node id="subdag_0_$create">
+      <data key="view">0</data>
+      <data key="event_id">$create</data>
+      <data key="sender">@alice:a.example</data>
+      <data key="origin">a.example</data>
+      <data key="type">m.room.create</data>
+      <data key="state_key"></data>
+      <data key="depth">1</data>
+      <data key="origin_server_ts">1</data>
+    </node>
+    <node id="subdag_0_$topic">
+      <data key="view">0</data>
+      <data key="event_id">$topic</data>
+      <data key="sender">@alice:a.example</data>
+      <data key="origin">a.example</data>
+      <data key="type">m.room.topic</data>
+      <data key="state_key">&lt;&quot;&amp;&apos;&gt;</data>
+      <data key="depth">2</data>
+      <data key="origin_server_ts">2</data>
+    </node>
+    <edge source="subdag_0_$topic" target="subdag_0_$create">
+      <data key="kind">prev</data>
+    </edge>
+    <edge source="subdag_0_$topic" target="subdag_0_$create">
+      <data key="kind">auth</data>
+    </edge>
+  </graph>
+</graphml>
+"#
+        );
+    }
+
+    #[test]
+    fn gexf_document() {
+        let dag = room_events();
+
+        assert_eq!(
+            to_gexf(&[(0, &dag)]),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<gexf xmlns="http://www.gexf.net/1.2draft" version="1.2">
+  <graph mode="static" defaultedgetype="directed">
+    <attributes class="node">
+      <attribute id="0" title="view" type="integer"/>
+      <attribute id="1" title="event_id" type="string"/>
+      <attribute id="2" title="sender" type="string"/>
+      <attribute id="3" title="origin" type="string"/>
+      <attribute id="4" title="type" type="string"/>
+      <attribute id="5" title="state_key" type="string"/>
+      <attribute id="6" title="depth" type="long"/>
+      <attribute id="7" title="origin_server_ts" type="long"/>
+    </attributes>
+    <attributes class="edge">
+      <attribute id="0" title="kind" type="string"/>
+    </attributes>
+    <nodes>
+      <node id="subdag_0_$create" label="$create">
+        <attvalues>
+          <attvalue for="0" value="0"/>
+          <attvalue for="1" value="$create"/>
+          <attvalue for="2" value="@alice:a.example"/>
+          <attvalue for="3" value="a.example"/>
+          <attvalue for="4" value="m.room.create"/>
+          <attvalue for="5" value=""/>
+          <attvalue for="6" value="1"/>
+          <attvalue for="7" value="1"/>
+        </attvalues>
+      </node>
+      <node id="subdag_0_$topic" label="$topic">
+        <attvalues>
+          <attvalue for="0" value="0"/>
+          <attvalue for="1" value="$topic"/>
+          <attvalue for="2" value="@alice:a.example"/>
+          <attvalue for="3" value="a.example"/>
+          <attvalue for="4" value="m.room.topic"/>
+          <attvalue for="5" value="&lt;&quot;&amp;&apos;&gt;"/>
+          <attvalue for="6" value="2"/>
+          <attvalue for="7" value="2"/>
+        </attvalues>
+      </node>
+    </nodes>
+    <edges>
+      <edge id="0" source="subdag_0_$topic" target="subdag_0_$create">
+        <attvalues><attvalue for="0" value="prev"/></attvalues>
+      </edge>
+      <edge id="1" source="subdag_0_$topic" target="subdag_0_$create">
+        <attvalues><attvalue for="0" value="auth"/></attvalues>
+      </edge>
+    </edges>
+  </graph>
+</gexf>
+"#
+        );
+    }
+}
@@ -1,2 +1,11 @@
+pub mod auth;
+pub mod crypto;
 pub mod dag;
+pub mod diff;
 pub mod event;
+pub mod export;
+pub mod room_version;
+pub mod snapshot;
+pub mod state;
+#[cfg(test)]
+pub mod testing;
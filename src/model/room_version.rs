@@ -0,0 +1,152 @@
+use serde_json::Value as JsonValue;
+
+/// The versions of rooms known by the application.
+///
+/// The variants are declared in chronological order, so they can be compared in order to know
+/// whether a rule introduced in a given version applies to a room.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RoomVersion {
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    V10,
+    V11,
+}
+
+/// The formats of the events used by the different room versions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EventFormat {
+    V1, // Events have an `event_id` field and reference other events with `[event_id, hashes]` pairs
+    V2, // The ID of an event is its reference hash encoded in standard base64, events are referenced by their IDs
+    V3, // Like `V2`, but the reference hashes are encoded in URL-safe base64
+}
+
+/// The state resolution algorithms used by the different room versions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StateResAlgorithm {
+    V1,
+    V2,
+}
+
+impl RoomVersion {
+    pub fn from_id(id: &str) -> Option<RoomVersion> {
+        match id {
+            "1" => Some(RoomVersion::V1),
+            "2" => Some(RoomVersion::V2),
+            "3" => Some(RoomVersion::V3),
+            "4" => Some(RoomVersion::V4),
+            "5" => Some(RoomVersion::V5),
+            "6" => Some(RoomVersion::V6),
+            "7" => Some(RoomVersion::V7),
+            "8" => Some(RoomVersion::V8),
+            "9" => Some(RoomVersion::V9),
+            "10" => Some(RoomVersion::V10),
+            "11" => Some(RoomVersion::V11),
+            _ => None,
+        }
+    }
+
+    /// Reads the version of a room from the content of its `m.room.create` event. Rooms created
+    /// before the introduction of room versions don't have a `room_version` field in this event
+    /// and are v1 rooms.
+    pub fn from_create_content(content: &JsonValue) -> Option<RoomVersion> {
+        match content.get("room_version") {
+            Some(id) => id.as_str().and_then(RoomVersion::from_id),
+            None => Some(RoomVersion::V1),
+        }
+    }
+
+    pub fn id(self) -> &'static str {
+        match self {
+            RoomVersion::V1 => "1",
+            RoomVersion::V2 => "2",
+            RoomVersion::V3 => "3",
+            RoomVersion::V4 => "4",
+            RoomVersion::V5 => "5",
+            RoomVersion::V6 => "6",
+            RoomVersion::V7 => "7",
+            RoomVersion::V8 => "8",
+            RoomVersion::V9 => "9",
+            RoomVersion::V10 => "10",
+            RoomVersion::V11 => "11",
+        }
+    }
+
+    pub fn event_format(self) -> EventFormat {
+        match self {
+            RoomVersion::V1 | RoomVersion::V2 => EventFormat::V1,
+            RoomVersion::V3 => EventFormat::V2,
+            _ => EventFormat::V3,
+        }
+    }
+
+    pub fn state_res(self) -> StateResAlgorithm {
+        match self {
+            RoomVersion::V1 => StateResAlgorithm::V1,
+            _ => StateResAlgorithm::V2,
+        }
+    }
+
+    /// Whether `m.room.aliases` events have their own authorization rule and keep their list of
+    /// aliases when they are redacted.
+    pub fn special_cased_aliases(self) -> bool {
+        self <= RoomVersion::V5
+    }
+
+    /// Whether the authorization of `m.room.redaction` events is checked with the authorization
+    /// rules instead of when the redaction is applied.
+    pub fn special_cased_redactions(self) -> bool {
+        self <= RoomVersion::V2
+    }
+
+    /// Whether the signatures of an event can only be checked with keys which were valid when it
+    /// was sent, according to the `valid_until_ts` of the keys of its servers.
+    pub fn enforce_key_validity(self) -> bool {
+        self >= RoomVersion::V5
+    }
+
+    /// Whether the `knock` membership and join rule exist.
+    pub fn knocking(self) -> bool {
+        self >= RoomVersion::V7
+    }
+
+    /// Whether the `restricted` join rule exists.
+    pub fn restricted_join(self) -> bool {
+        self >= RoomVersion::V8
+    }
+
+    /// Whether the `knock_restricted` join rule exists.
+    pub fn knock_restricted_join(self) -> bool {
+        self >= RoomVersion::V10
+    }
+
+    /// Whether the values in `m.room.power_levels` events must be integers.
+    pub fn integer_power_levels(self) -> bool {
+        self >= RoomVersion::V10
+    }
+
+    /// Whether the redaction algorithm keeps the `join_authorised_via_users_server` field of the
+    /// content of `m.room.member` events.
+    pub fn redaction_keeps_join_authoriser(self) -> bool {
+        self >= RoomVersion::V9
+    }
+
+    /// Whether the redaction algorithm is the one introduced in rooms v11, which keeps the whole
+    /// content of `m.room.create` events and drops the `origin`, `membership` and `prev_state`
+    /// top-level fields.
+    pub fn updated_redaction_rules(self) -> bool {
+        self >= RoomVersion::V11
+    }
+
+    /// Whether the creator of the room is the sender of the `m.room.create` event rather than
+    /// the `creator` field of its content.
+    pub fn creator_is_sender(self) -> bool {
+        self >= RoomVersion::V11
+    }
+}
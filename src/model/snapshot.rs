@@ -0,0 +1,108 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::dag::RoomEvents;
+use crate::BackendChoice;
+
+/// A snapshot of the events DAG of a view, which can be saved as a JSON file and loaded back
+/// later without connecting to any homeserver.
+#[derive(Deserialize, Serialize)]
+pub struct Snapshot {
+    pub server_name: String, // The name of the server the events were retrieved from
+    pub room_id: String,     // The ID of the room being observed
+    pub room_version: Option<String>, // The version of the room, if it was known
+    pub backend: BackendChoice, // The backend which retrieved the events
+    pub fetched_at: f64, // When the latest events were received, in milliseconds since the Unix epoch
+    pub events: Vec<JsonValue>, // The raw JSON bodies of the events, including the rejected ones
+}
+
+impl Snapshot {
+    /// Takes a snapshot of `events_dag`, retrieved from the room `room_id` with `backend` until
+    /// `fetched_at`.
+    pub fn new(
+        events_dag: &RoomEvents,
+        room_id: &str,
+        backend: BackendChoice,
+        fetched_at: f64,
+    ) -> Snapshot {
+        Snapshot {
+            server_name: events_dag.server_name().to_string(),
+            room_id: room_id.to_string(),
+            room_version: events_dag
+                .room_version()
+                .map(|version| version.id().to_string()),
+            backend,
+            fetched_at,
+            events: events_dag.raw_events(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::model::dag::{Batch, BatchKind};
+    use crate::model::testing::Pdu;
+
+    #[test]
+    fn json_round_trip() {
+        let event = |id: &str, etype: &str, prev_events: &[&str], depth: i64| {
+            Pdu::new(etype)
+                .id(id)
+                .state_key("")
+                .content(json!({ "creator": "@alice:a.example" }))
+                .depth(depth)
+                .prev_events(prev_events)
+                .pairs()
+                .build()
+        };
+        // In rooms v1, the events must reference each other with `[event_id, hashes]` pairs
+        let mut malformed = event("$malformed", "m.room.topic", &[], 3);
+        malformed["prev_events"] = json!(["$b"]);
+
+        // `$a` forks into `$b` and `$c`, which are both forward extremities
+        let mut dag = RoomEvents::new("a.example", &HashSet::new());
+        dag.add_events(
+            vec![
+                event("$create", "m.room.create", &[], 1),
+                event("$a", "m.room.topic", &["$create"], 2),
+                event("$b", "m.room.topic", &["$a"], 3),
+                event("$c", "m.room.name", &["$a"], 3),
+                malformed.clone(),
+            ],
+            Batch {
+                kind: BatchKind::File,
+                received_at: 0.0,
+            },
+        );
+
+        assert_eq!(dag.latest_events(), vec!["$b", "$c"]);
+        assert_eq!(dag.quarantine().len(), 1);
+
+        let snapshot = Snapshot::new(&dag, "!room:a.example", BackendChoice::File, 42.0);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(snapshot.room_id, "!room:a.example");
+        assert!(snapshot.backend == BackendChoice::File);
+        assert_eq!(snapshot.fetched_at, 42.0);
+
+        let loaded = RoomEvents::from_snapshot(&HashSet::new(), snapshot, 43.0);
+
+        let event_ids = |dag: &RoomEvents| -> Vec<String> {
+            dag.events()
+                .iter()
+                .map(|event| event.event_id.clone())
+                .collect()
+        };
+
+        assert_eq!(loaded.server_name(), "a.example");
+        assert_eq!(loaded.room_version(), dag.room_version());
+        assert_eq!(event_ids(&loaded), event_ids(&dag));
+        assert_eq!(loaded.latest_events(), vec!["$b", "$c"]);
+        assert_eq!(loaded.quarantine().len(), 1);
+        assert_eq!(loaded.quarantine()[0].json, malformed);
+    }
+}
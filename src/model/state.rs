@@ -0,0 +1,825 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+
+use failure::{format_err, Error};
+use serde_json::Value as JsonValue;
+use sha1::{Digest, Sha1};
+
+use super::auth::{self, AuthEvents, StateKey};
+use super::dag::RoomEvents;
+use super::event::Event;
+use super::room_version::{RoomVersion, StateResAlgorithm};
+
+/// The state of a room: the ID of the event holding each entry of the state.
+pub type StateMap = BTreeMap<StateKey, String>;
+
+/// Computes locally the state of the room at the events of a `RoomEvents`, by running the state
+/// resolution algorithm of the version of the room over the events held in the DAG.
+///
+/// The state after each visited event is kept, so resolving the state at several events of the
+/// same DAG only goes through their common ancestors once.
+///
+/// Only the ancestors which are in the DAG are taken into account: if some of them are missing,
+/// the result can differ from the one of the homeservers.
+pub struct StateResolver<'a> {
+    dag: &'a RoomEvents,
+    version: RoomVersion,
+
+    states: HashMap<String, StateMap>, // The state of the room after each visited event
+    rejected: HashSet<String>, // The ID of the state events which failed the authorization rules
+    auth_chains: HashMap<String, HashSet<String>>, // The IDs of the full auth chain of the visited events
+}
+
+impl<'a> StateResolver<'a> {
+    /// Creates a resolver for the events in `dag`. The version of the room must be known, which
+    /// requires its `m.room.create` event to have been seen.
+    pub fn new(dag: &'a RoomEvents) -> Result<StateResolver<'a>, Error> {
+        let version = dag
+            .room_version()
+            .ok_or_else(|| format_err!("The version of the room is unknown"))?;
+
+        Ok(StateResolver {
+            dag,
+            version,
+
+            states: HashMap::new(),
+            rejected: HashSet::new(),
+            auth_chains: HashMap::new(),
+        })
+    }
+
+    pub fn room_version(&self) -> RoomVersion {
+        self.version
+    }
+
+    /// Returns the state of the room after the event `event_id`: the state resolved from the
+    /// states after each of its `prev_events`, updated with the event itself if it is an
+    /// authorized state event.
+    pub fn state_after(&mut self, event_id: &str) -> Result<StateMap, Error> {
+        let event_id = self
+            .dag
+            .get_event(event_id)
+            .map(|ev| ev.event_id.as_str())
+            .ok_or_else(|| format_err!("Unknown event: {}", event_id))?;
+
+        for id in self.unresolved_ancestors(event_id) {
+            self.resolve_event(id);
+        }
+
+        Ok(self.states[event_id].clone())
+    }
+
+    /// Returns the state of the room before the event `event_id`, resolved from the states after
+    /// each of its `prev_events`.
+    pub fn state_before(&mut self, event_id: &str) -> Result<StateMap, Error> {
+        let event = self
+            .dag
+            .get_event(event_id)
+            .ok_or_else(|| format_err!("Unknown event: {}", event_id))?;
+
+        for prev_id in event.get_prev_events() {
+            if self.dag.get_event(prev_id).is_some() {
+                self.state_after(prev_id)?;
+            }
+        }
+
+        Ok(self.resolve_prev_states(event))
+    }
+
+    /// Whether the state event `event_id` was rejected by the authorization rules during the
+    /// previous resolutions.
+    pub fn is_rejected(&self, event_id: &str) -> bool {
+        self.rejected.contains(event_id)
+    }
+
+    // Returns the ancestors of `event_id` (itself included) whose state is not known yet, sorted
+    // so that every event comes after its `prev_events`.
+    fn unresolved_ancestors(&self, event_id: &'a str) -> Vec<&'a str> {
+        let mut sorted = Vec::new();
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<(&str, bool)> = vec![(event_id, false)];
+
+        while let Some((id, prev_events_done)) = stack.pop() {
+            if prev_events_done {
+                sorted.push(id);
+                continue;
+            }
+
+            if self.states.contains_key(id) || !visited.insert(id) {
+                continue;
+            }
+
+            stack.push((id, true));
+
+            if let Some(event) = self.dag.get_event(id) {
+                for prev_id in event.get_prev_events() {
+                    if let Some(prev_event) = self.dag.get_event(prev_id) {
+                        stack.push((&prev_event.event_id, false));
+                    }
+                }
+            }
+        }
+
+        sorted
+    }
+
+    // Computes the state after the event `event_id`. The states after its `prev_events` must
+    // already be known.
+    fn resolve_event(&mut self, event_id: &str) {
+        let dag = self.dag;
+        let event = match dag.get_event(event_id) {
+            Some(event) => event,
+            None => return,
+        };
+
+        let mut state = self.resolve_prev_states(event);
+
+        if let Some(key) = auth::state_key(event) {
+            if self.is_authorized(event) {
+                state.insert(key, event.event_id.clone());
+            } else {
+                self.rejected.insert(event.event_id.clone());
+            }
+        }
+
+        self.states.insert(event.event_id.clone(), state);
+    }
+
+    fn resolve_prev_states(&mut self, event: &Event) -> StateMap {
+        let mut prev_states: Vec<StateMap> = Vec::new();
+
+        for prev_id in event.get_prev_events() {
+            if let Some(state) = self.states.get(prev_id) {
+                if !prev_states.contains(state) {
+                    prev_states.push(state.clone());
+                }
+            }
+        }
+
+        match prev_states.len() {
+            0 => StateMap::new(),
+            1 => prev_states.pop().unwrap(),
+            _ => self.resolve(&prev_states),
+        }
+    }
+
+    // An event is authorized if it passes the authorization rules against its own auth events.
+    // Events whose auth events are not all in the DAG can't be checked and are accepted.
+    fn is_authorized(&self, event: &Event) -> bool {
+        let mut auth_events: AuthEvents = HashMap::new();
+
+        for auth_id in event.get_auth_events() {
+            match self.dag.get_event(auth_id) {
+                Some(auth_event) => {
+                    if let Some(key) = auth::state_key(auth_event) {
+                        auth_events.insert(key, auth_event);
+                    }
+                }
+                None => return true,
+            }
+        }
+
+        auth::check(event, &auth_events, self.version)
+    }
+
+    /// Resolves the conflicts between several states of the room with the state resolution
+    /// algorithm of the version of the room.
+    pub fn resolve(&mut self, states: &[StateMap]) -> StateMap {
+        match self.version.state_res() {
+            StateResAlgorithm::V1 => self.resolve_v1(states),
+            StateResAlgorithm::V2 => self.resolve_v2(states),
+        }
+    }
+
+    fn resolve_v1(&mut self, states: &[StateMap]) -> StateMap {
+        let dag = self.dag;
+        let version = self.version;
+
+        // In v1, an entry is only conflicted if the states which have it disagree on its value
+        let mut values: BTreeMap<&StateKey, BTreeSet<&str>> = BTreeMap::new();
+        for state in states {
+            for (key, id) in state {
+                values.entry(key).or_default().insert(id);
+            }
+        }
+
+        let mut resolved = StateMap::new();
+        let mut conflicted: BTreeMap<&StateKey, Vec<&Event>> = BTreeMap::new();
+
+        for (key, ids) in values {
+            if ids.len() == 1 {
+                resolved.insert(key.clone(), ids.iter().next().unwrap().to_string());
+            } else {
+                let events = ids.iter().filter_map(|id| dag.get_event(id)).collect();
+
+                conflicted.insert(key, events);
+            }
+        }
+
+        // The auth events used for the resolution come from the unconflicted state
+        let mut auth_events: AuthEvents = HashMap::new();
+        for event in conflicted.values().flatten() {
+            for key in auth::auth_types_for_event(event, version) {
+                if let Entry::Vacant(entry) = auth_events.entry(key) {
+                    if let Some(auth_event) =
+                        resolved.get(entry.key()).and_then(|id| dag.get_event(id))
+                    {
+                        entry.insert(auth_event);
+                    }
+                }
+            }
+        }
+
+        let mut resolved_conflicts: BTreeMap<&StateKey, &Event> = BTreeMap::new();
+
+        // The power levels are resolved first, then the join rules and the memberships, and
+        // finally the other state events
+        let auth_steps = [
+            ("m.room.power_levels", Some("")),
+            ("m.room.join_rules", Some("")),
+            ("m.room.member", None),
+        ];
+
+        for (etype, state_key) in auth_steps.iter() {
+            let in_step = conflicted.iter().filter(|(key, _)| {
+                key.0 == *etype && state_key.is_none_or(|state_key| key.1 == state_key)
+            });
+
+            for (key, events) in in_step {
+                if let Some(event) = resolve_auth_events_v1(events, &auth_events, version) {
+                    resolved_conflicts.insert(key, event);
+                    auth_events.insert((*key).clone(), event);
+                }
+            }
+        }
+
+        for (key, events) in conflicted.iter() {
+            if !resolved_conflicts.contains_key(key) {
+                if let Some(event) = resolve_normal_events_v1(events, &auth_events, version) {
+                    resolved_conflicts.insert(key, event);
+                }
+            }
+        }
+
+        for (key, event) in resolved_conflicts {
+            resolved.insert(key.clone(), event.event_id.clone());
+        }
+
+        resolved
+    }
+
+    fn resolve_v2(&mut self, states: &[StateMap]) -> StateMap {
+        let dag = self.dag;
+
+        // In v2, an entry is conflicted unless every state has it with the same value
+        let keys: BTreeSet<&StateKey> = states.iter().flat_map(|state| state.keys()).collect();
+
+        let mut unconflicted = StateMap::new();
+        let mut conflicted_ids: HashSet<String> = HashSet::new();
+
+        for key in keys {
+            let ids: BTreeSet<Option<&String>> =
+                states.iter().map(|state| state.get(key)).collect();
+
+            match (ids.len(), ids.iter().next()) {
+                (1, Some(Some(id))) => {
+                    unconflicted.insert(key.clone(), (*id).clone());
+                }
+                _ => conflicted_ids.extend(ids.into_iter().flatten().cloned()),
+            }
+        }
+
+        if conflicted_ids.is_empty() {
+            return unconflicted;
+        }
+
+        // The full conflicted set also contains the auth difference of the states
+        let auth_chains: Vec<HashSet<String>> = states
+            .iter()
+            .map(|state| self.state_auth_chain(state))
+            .collect();
+        let auth_union: HashSet<&String> = auth_chains.iter().flatten().collect();
+        let auth_difference = auth_union
+            .into_iter()
+            .filter(|id| !auth_chains.iter().all(|chain| chain.contains(*id)));
+
+        let full_conflicted: HashSet<String> = conflicted_ids
+            .iter()
+            .chain(auth_difference)
+            .filter(|id| dag.get_event(id).is_some())
+            .cloned()
+            .collect();
+
+        // Resolve the power events first, in the order of the auth DAG
+        let mut power_events: HashSet<String> = full_conflicted
+            .iter()
+            .filter(|id| is_power_event(dag.get_event(id).unwrap()))
+            .cloned()
+            .collect();
+
+        for id in power_events.clone() {
+            for auth_id in self.auth_chain(&id) {
+                if full_conflicted.contains(&auth_id) {
+                    power_events.insert(auth_id);
+                }
+            }
+        }
+
+        let sorted_power_events = self.reverse_topological_power_sort(&power_events);
+        let mut resolved = self.iterative_auth_checks(&sorted_power_events, unconflicted.clone());
+
+        // Then the other events, in the order of the mainline of the resolved power levels
+        let other_events: Vec<&String> = full_conflicted
+            .iter()
+            .filter(|id| !power_events.contains(*id))
+            .collect();
+        let power_levels_id = resolved
+            .get(&("m.room.power_levels".to_string(), String::new()))
+            .cloned();
+
+        let sorted_other_events = self.mainline_sort(&other_events, power_levels_id);
+        resolved = self.iterative_auth_checks(&sorted_other_events, resolved);
+
+        resolved.extend(unconflicted);
+
+        resolved
+    }
+
+    // Sorts the events so that each event comes after its auth events. The ties are broken by
+    // giving the priority to the events with the most powerful senders, then to the oldest ones.
+    fn reverse_topological_power_sort(&self, event_ids: &HashSet<String>) -> Vec<String> {
+        let dag = self.dag;
+
+        let mut remaining_auth_events: HashMap<&str, usize> = HashMap::new();
+        let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+
+        for id in event_ids {
+            let event = dag.get_event(id).unwrap();
+            let in_set: Vec<&str> = event
+                .get_auth_events()
+                .into_iter()
+                .filter(|auth_id| event_ids.contains(*auth_id))
+                .collect();
+
+            remaining_auth_events.insert(id, in_set.len());
+
+            for auth_id in in_set {
+                children.entry(auth_id).or_default().push(id);
+            }
+        }
+
+        let sort_key = |id: &str| {
+            let event = dag.get_event(id).unwrap();
+
+            (
+                Reverse(self.sender_power_level(event)),
+                event.origin_server_ts,
+                event.event_id.clone(),
+            )
+        };
+
+        let mut ready: BinaryHeap<Reverse<_>> = remaining_auth_events
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(id, _)| Reverse(sort_key(id)))
+            .collect();
+        let mut sorted = Vec::with_capacity(event_ids.len());
+
+        while let Some(Reverse((_, _, id))) = ready.pop() {
+            if let Some(children) = children.get(id.as_str()) {
+                for child in children {
+                    let count = remaining_auth_events.get_mut(child).unwrap();
+                    *count -= 1;
+
+                    if *count == 0 {
+                        ready.push(Reverse(sort_key(child)));
+                    }
+                }
+            }
+
+            sorted.push(id);
+        }
+
+        sorted
+    }
+
+    // Sorts the events by their position relatively to the mainline of the power levels event
+    // `power_levels_id`, then by their timestamps and finally by their IDs.
+    fn mainline_sort(&self, event_ids: &[&String], power_levels_id: Option<String>) -> Vec<String> {
+        let dag = self.dag;
+
+        let mut mainline = Vec::new();
+        let mut current = power_levels_id;
+        while let Some(id) = current {
+            current = dag
+                .get_event(&id)
+                .and_then(|ev| self.power_levels_auth_event(ev));
+            mainline.push(id);
+        }
+
+        // The oldest power levels event of the mainline has the position 1, events which are not
+        // preceded by any of them have the position 0
+        let positions: HashMap<&str, usize> = mainline
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(pos, id)| (id.as_str(), pos + 1))
+            .collect();
+
+        let mainline_position = |event: &Event| {
+            let mut current = Some(event.event_id.clone());
+
+            while let Some(id) = current {
+                if let Some(pos) = positions.get(id.as_str()) {
+                    return *pos;
+                }
+
+                current = dag
+                    .get_event(&id)
+                    .and_then(|ev| self.power_levels_auth_event(ev));
+            }
+
+            0
+        };
+
+        let mut events: Vec<(usize, i64, &String)> = event_ids
+            .iter()
+            .map(|id| {
+                let event = dag.get_event(id).unwrap();
+
+                (mainline_position(event), event.origin_server_ts, *id)
+            })
+            .collect();
+        events.sort();
+
+        events.into_iter().map(|(_, _, id)| id.clone()).collect()
+    }
+
+    // Checks one by one the events against the partially resolved state `state` and adds them to
+    // it if they are authorized.
+    fn iterative_auth_checks(&self, event_ids: &[String], mut state: StateMap) -> StateMap {
+        let dag = self.dag;
+
+        for id in event_ids {
+            let event = dag.get_event(id).unwrap();
+
+            let mut auth_events: AuthEvents = HashMap::new();
+
+            for auth_id in event.get_auth_events() {
+                if let Some(auth_event) = dag.get_event(auth_id) {
+                    if let Some(key) = auth::state_key(auth_event) {
+                        if !self.rejected.contains(auth_id) {
+                            auth_events.insert(key, auth_event);
+                        }
+                    }
+                }
+            }
+
+            for key in auth::auth_types_for_event(event, self.version) {
+                if let Some(state_event) = state.get(&key).and_then(|id| dag.get_event(id)) {
+                    auth_events.insert(key, state_event);
+                }
+            }
+
+            if auth::check(event, &auth_events, self.version) {
+                if let Some(key) = auth::state_key(event) {
+                    state.insert(key, id.clone());
+                }
+            }
+        }
+
+        state
+    }
+
+    fn sender_power_level(&self, event: &Event) -> i64 {
+        let mut auth_events: AuthEvents = HashMap::new();
+
+        for auth_id in event.get_auth_events() {
+            if let Some(auth_event) = self.dag.get_event(auth_id) {
+                if let Some(key) = auth::state_key(auth_event) {
+                    auth_events.insert(key, auth_event);
+                }
+            }
+        }
+
+        auth::get_user_power_level(&event.sender, &auth_events, self.version)
+    }
+
+    fn power_levels_auth_event(&self, event: &Event) -> Option<String> {
+        event
+            .get_auth_events()
+            .into_iter()
+            .filter_map(|id| self.dag.get_event(id))
+            .find(|ev| ev.etype == "m.room.power_levels" && ev.state_key == Some(String::new()))
+            .map(|ev| ev.event_id.clone())
+    }
+
+    fn state_auth_chain(&mut self, state: &StateMap) -> HashSet<String> {
+        let mut chain = HashSet::new();
+
+        for id in state.values() {
+            chain.extend(self.auth_chain(id));
+        }
+
+        chain
+    }
+
+    // Returns the IDs of every events which can be reached from `event_id` by following the auth
+    // events.
+    fn auth_chain(&mut self, event_id: &str) -> HashSet<String> {
+        if let Some(chain) = self.auth_chains.get(event_id) {
+            return chain.clone();
+        }
+
+        let mut chain: HashSet<String> = HashSet::new();
+        let mut stack = vec![event_id.to_string()];
+
+        while let Some(id) = stack.pop() {
+            if let Some(event) = self.dag.get_event(&id) {
+                for auth_id in event.get_auth_events() {
+                    if chain.insert(auth_id.to_string()) {
+                        stack.push(auth_id.to_string());
+                    }
+                }
+            }
+        }
+
+        self.auth_chains.insert(event_id.to_string(), chain.clone());
+
+        chain
+    }
+}
+
+/// Returns the JSON bodies of the events of `state`, in the same format as the state returned by
+/// the homeservers so that both can be compared.
+pub fn state_to_json(dag: &RoomEvents, state: &StateMap) -> JsonValue {
+    let events: Vec<&Event> = state.values().filter_map(|id| dag.get_event(id)).collect();
+
+    json!({ "events": events })
+}
+
+fn is_power_event(event: &Event) -> bool {
+    match (event.etype.as_str(), &event.state_key) {
+        ("m.room.create", Some(state_key))
+        | ("m.room.power_levels", Some(state_key))
+        | ("m.room.join_rules", Some(state_key)) => state_key.is_empty(),
+        ("m.room.member", Some(state_key)) => {
+            let membership = event.content.get("membership").and_then(JsonValue::as_str);
+
+            (membership == Some("leave") || membership == Some("ban")) && *state_key != event.sender
+        }
+        _ => false,
+    }
+}
+
+// Orders the events of a conflict by decreasing depth, the ties being broken with the SHA-1 of
+// their IDs.
+fn order_events_v1<'e>(events: &[&'e Event]) -> Vec<&'e Event> {
+    let mut ordered: Vec<(i64, String, &Event)> = events
+        .iter()
+        .map(|ev| {
+            (
+                ev.depth,
+                format!("{:x}", Sha1::digest(ev.event_id.as_bytes())),
+                *ev,
+            )
+        })
+        .collect();
+
+    ordered.sort_by(
+        |(depth_a, hash_a, _), (depth_b, hash_b, _)| match depth_b.cmp(depth_a) {
+            Ordering::Equal => hash_a.cmp(hash_b),
+            ordering => ordering,
+        },
+    );
+
+    ordered.into_iter().map(|(_, _, ev)| ev).collect()
+}
+
+// Starting from the oldest event of the conflict, keeps the newest event which is authorized by
+// the previous one.
+fn resolve_auth_events_v1<'e>(
+    events: &[&'e Event],
+    auth_events: &AuthEvents<'e>,
+    version: RoomVersion,
+) -> Option<&'e Event> {
+    let mut ordered = order_events_v1(events);
+    ordered.reverse();
+
+    let auth_keys: HashSet<StateKey> = events
+        .iter()
+        .flat_map(|ev| auth::auth_types_for_event(ev, version))
+        .collect();
+    let mut auth_events: AuthEvents = auth_events
+        .iter()
+        .filter(|(key, _)| auth_keys.contains(*key))
+        .map(|(key, ev)| (key.clone(), *ev))
+        .collect();
+
+    let mut iter = ordered.into_iter();
+    let mut prev_event = iter.next()?;
+
+    for event in iter {
+        if let Some(key) = auth::state_key(prev_event) {
+            auth_events.insert(key, prev_event);
+        }
+
+        if !auth::check(event, &auth_events, version) {
+            return Some(prev_event);
+        }
+
+        prev_event = event;
+    }
+
+    Some(prev_event)
+}
+
+// Keeps the newest event of the conflict which is authorized by the already resolved state.
+fn resolve_normal_events_v1<'e>(
+    events: &[&'e Event],
+    auth_events: &AuthEvents<'e>,
+    version: RoomVersion,
+) -> Option<&'e Event> {
+    let ordered = order_events_v1(events);
+
+    ordered
+        .iter()
+        .find(|ev| auth::check(ev, auth_events, version))
+        .or_else(|| ordered.last())
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::model::dag::{Batch, BatchKind};
+    use crate::model::testing::Pdu;
+
+    // Builds a DAG from `events`, which are given in chronological order: their depths and their
+    // timestamps follow this order.
+    fn dag(mut events: Vec<JsonValue>) -> RoomEvents {
+        for (i, event) in events.iter_mut().enumerate() {
+            event["depth"] = (i as i64 + 1).into();
+            event["origin_server_ts"] = (i as i64 + 1).into();
+        }
+
+        let mut dag = RoomEvents::new("a.example", &HashSet::new());
+        dag.add_events(
+            events,
+            Batch {
+                kind: BatchKind::File,
+                received_at: 0.0,
+            },
+        );
+
+        dag
+    }
+
+    // A public room created by Alice, which Bob has joined as a moderator.
+    fn room_events(version: &str) -> Vec<JsonValue> {
+        let alice = "@alice:a.example";
+        let bob = "@bob:b.example";
+
+        vec![
+            Pdu::new("m.room.create")
+                .id("$create")
+                .state_key("")
+                .content(json!({ "creator": alice, "room_version": version }))
+                .build(),
+            Pdu::new("m.room.member")
+                .id("$alice")
+                .state_key(alice)
+                .content(json!({ "membership": "join" }))
+                .prev_events(&["$create"])
+                .auth_events(&["$create"])
+                .build(),
+            Pdu::new("m.room.power_levels")
+                .id("$power_levels")
+                .state_key("")
+                .content(
+                    json!({ "users": { alice: 100, bob: 50 }, "ban": 50, "state_default": 50 }),
+                )
+                .prev_events(&["$alice"])
+                .auth_events(&["$create", "$alice"])
+                .build(),
+            Pdu::new("m.room.join_rules")
+                .id("$join_rules")
+                .state_key("")
+                .content(json!({ "join_rule": "public" }))
+                .prev_events(&["$power_levels"])
+                .auth_events(&["$create", "$alice", "$power_levels"])
+                .build(),
+            Pdu::new("m.room.member")
+                .id("$bob")
+                .sender(bob)
+                .state_key(bob)
+                .content(json!({ "membership": "join" }))
+                .prev_events(&["$join_rules"])
+                .auth_events(&["$create", "$power_levels", "$join_rules"])
+                .build(),
+        ]
+    }
+
+    fn key(etype: &str, state_key: &str) -> StateKey {
+        (etype.to_string(), state_key.to_string())
+    }
+
+    #[test]
+    fn linear_state() {
+        let dag = dag(room_events("6"));
+        let mut resolver = StateResolver::new(&dag).unwrap();
+
+        let state = resolver.state_after("$bob").unwrap();
+
+        assert_eq!(state.len(), 5);
+        assert_eq!(state[&key("m.room.power_levels", "")], "$power_levels");
+        assert_eq!(state[&key("m.room.member", "@bob:b.example")], "$bob");
+
+        let state = resolver.state_before("$bob").unwrap();
+
+        assert_eq!(state.len(), 4);
+        assert!(!state.contains_key(&key("m.room.member", "@bob:b.example")));
+    }
+
+    #[test]
+    fn rejected_state_event() {
+        let mut events = room_events("6");
+        events.push(
+            Pdu::new("m.room.name")
+                .id("$name")
+                .sender("@carol:c.example")
+                .state_key("")
+                .content(json!({ "name": "Not a member" }))
+                .prev_events(&["$bob"])
+                .auth_events(&["$create", "$power_levels"])
+                .build(),
+        );
+
+        let dag = dag(events);
+        let mut resolver = StateResolver::new(&dag).unwrap();
+
+        let state = resolver.state_after("$name").unwrap();
+
+        assert!(resolver.is_rejected("$name"));
+        assert!(!state.contains_key(&key("m.room.name", "")));
+    }
+
+    // Alice bans Bob while Bob changes the topic of the room concurrently: once the branches are
+    // merged, Bob is banned and his topic, which isn't allowed anymore, is left out.
+    #[test]
+    fn v2_conflicting_branches() {
+        let alice = "@alice:a.example";
+        let bob = "@bob:b.example";
+
+        let mut events = room_events("6");
+        events.push(
+            Pdu::new("m.room.member")
+                .id("$ban")
+                .sender(alice)
+                .state_key(bob)
+                .content(json!({ "membership": "ban" }))
+                .prev_events(&["$bob"])
+                .auth_events(&["$create", "$alice", "$power_levels", "$bob"])
+                .build(),
+        );
+        events.push(
+            Pdu::new("m.room.topic")
+                .id("$topic")
+                .sender(bob)
+                .state_key("")
+                .content(json!({ "topic": "Bob's topic" }))
+                .prev_events(&["$bob"])
+                .auth_events(&["$create", "$power_levels", "$bob"])
+                .build(),
+        );
+        events.push(
+            Pdu::new("m.room.message")
+                .id("$message")
+                .sender(alice)
+                .content(json!({ "body": "Hello" }))
+                .prev_events(&["$ban", "$topic"])
+                .auth_events(&["$create", "$alice", "$power_levels"])
+                .build(),
+        );
+
+        let dag = dag(events);
+        let mut resolver = StateResolver::new(&dag).unwrap();
+
+        assert_eq!(resolver.room_version(), RoomVersion::V6);
+
+        // Each branch alone has its own change
+        let ban_branch = resolver.state_after("$ban").unwrap();
+        assert_eq!(ban_branch[&key("m.room.member", bob)], "$ban");
+        let topic_branch = resolver.state_after("$topic").unwrap();
+        assert_eq!(topic_branch[&key("m.room.topic", "")], "$topic");
+
+        let state = resolver.state_before("$message").unwrap();
+
+        assert_eq!(state[&key("m.room.member", bob)], "$ban");
+        assert!(!state.contains_key(&key("m.room.topic", "")));
+        assert_eq!(state[&key("m.room.power_levels", "")], "$power_levels");
+    }
+}
@@ -0,0 +1,94 @@
+use serde_json::Value as JsonValue;
+
+/// Builds the JSON of an event, as it is exchanged between homeservers, for the tests.
+///
+/// The event is sent by `@alice:a.example` in the room `!room:a.example` at the depth 1, without
+/// any parents nor auth events, until told otherwise. It has no `event_id` unless one is given,
+/// like the events of the rooms v3 and later whose ID is their reference hash.
+pub struct Pdu {
+    json: JsonValue,
+    prev_events: Vec<String>,
+    auth_events: Vec<String>,
+    pairs: bool, // Whether the events are referenced by `[event_id, hashes]` pairs
+}
+
+impl Pdu {
+    pub fn new(etype: &str) -> Pdu {
+        Pdu {
+            json: json!({
+                "room_id": "!room:a.example",
+                "sender": "@alice:a.example",
+                "origin_server_ts": 1,
+                "type": etype,
+                "content": {},
+                "depth": 1,
+                "hashes": {},
+                "signatures": {},
+            }),
+            prev_events: Vec::new(),
+            auth_events: Vec::new(),
+            pairs: false,
+        }
+    }
+
+    pub fn id(mut self, event_id: &str) -> Pdu {
+        self.json["event_id"] = event_id.into();
+        self
+    }
+
+    pub fn sender(mut self, sender: &str) -> Pdu {
+        self.json["sender"] = sender.into();
+        self
+    }
+
+    pub fn state_key(mut self, state_key: &str) -> Pdu {
+        self.json["state_key"] = state_key.into();
+        self
+    }
+
+    pub fn content(mut self, content: JsonValue) -> Pdu {
+        self.json["content"] = content;
+        self
+    }
+
+    /// Sets the depth of the event, and its timestamp so both follow the same order.
+    pub fn depth(mut self, depth: i64) -> Pdu {
+        self.json["depth"] = depth.into();
+        self.json["origin_server_ts"] = depth.into();
+        self
+    }
+
+    pub fn prev_events(mut self, prev_events: &[&str]) -> Pdu {
+        self.prev_events = prev_events.iter().map(|id| id.to_string()).collect();
+        self
+    }
+
+    pub fn auth_events(mut self, auth_events: &[&str]) -> Pdu {
+        self.auth_events = auth_events.iter().map(|id| id.to_string()).collect();
+        self
+    }
+
+    /// References the parents and the auth events of the event with `[event_id, hashes]` pairs,
+    /// like in the rooms v1 and v2, rather than with their IDs alone.
+    pub fn pairs(mut self) -> Pdu {
+        self.pairs = true;
+        self
+    }
+
+    pub fn build(self) -> JsonValue {
+        let pairs = self.pairs;
+        let references = |ids: Vec<String>| -> JsonValue {
+            if pairs {
+                ids.into_iter().map(|id| json!([id, {}])).collect()
+            } else {
+                ids.into()
+            }
+        };
+
+        let mut json = self.json;
+        json["prev_events"] = references(self.prev_events);
+        json["auth_events"] = references(self.auth_events);
+
+        json
+    }
+}
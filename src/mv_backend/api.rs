@@ -0,0 +1,8 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// Represents the JSON body of the responses of the endpoints of the `/visualisations` API.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EventsResponse {
+    pub events: Vec<JsonValue>,
+}
@@ -1,24 +1,21 @@
 use std::sync::{Arc, RwLock};
 
-use failure::{format_err, Error};
-use serde_derive::{Deserialize, Serialize};
-use serde_json::Value as JsonValue;
+use failure::Error;
+use serde::de::DeserializeOwned;
 use yew::callback::Callback;
-use yew::format::{Json, Nothing};
-use yew::services::fetch::{FetchService, FetchTask, Request, Response, Uri};
+use yew::format::{Nothing, Text};
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
 
+use super::api::EventsResponse;
 use super::session::Session;
+use crate::cs_backend::api::{self as cs_api, ApiRequest, RoomAliasResponse, WellKnown};
+use crate::cs_backend::error::MatrixError;
 
 pub struct MatrixVisualisationsBackend {
     fetch: FetchService,
     session: Arc<RwLock<Session>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct EventsResponse {
-    pub events: Vec<JsonValue>,
-}
-
 impl MatrixVisualisationsBackend {
     pub fn with_session(session: Arc<RwLock<Session>>) -> Self {
         MatrixVisualisationsBackend {
@@ -27,19 +24,43 @@ impl MatrixVisualisationsBackend {
         }
     }
 
-    pub fn deepest(&mut self, callback: Callback<Result<EventsResponse, Error>>) -> FetchTask {
-        let (server_name, room_id) = {
+    /// Requests the `.well-known` file of the server of the session, which gives the base URL of
+    /// its homeserver, and then calls `callback` when it gets the response.
+    pub fn well_known(
+        &mut self,
+        callback: Callback<Result<WellKnown, Error>>,
+    ) -> Option<FetchTask> {
+        let request = cs_api::well_known(&self.session.read().unwrap().server_name);
+
+        self.cs_request(callback, request, "error discovering the homeserver")
+    }
+
+    /// Requests the ID of the room whose alias is given in the session and then calls `callback`
+    /// when it gets the response.
+    pub fn resolve_alias(
+        &mut self,
+        callback: Callback<Result<RoomAliasResponse, Error>>,
+    ) -> Option<FetchTask> {
+        let request = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.room_id.clone())
+            cs_api::resolve_alias(&session.base_url(), &session.room_id)
         };
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(format!("/visualisations/deepest/{}", room_id).as_str())
-            .build()
-            .expect("Failed to build URI.");
+        self.cs_request(callback, request, "error resolving the room alias")
+    }
+
+    pub fn deepest(
+        &mut self,
+        callback: Callback<Result<EventsResponse, Error>>,
+    ) -> Option<FetchTask> {
+        let (base_url, room_id) = {
+            let session = self.session.read().unwrap();
+
+            (session.base_url(), cs_api::encode(&session.room_id))
+        };
+
+        let uri = format!("{}/visualisations/deepest/{}", base_url, room_id);
 
         self.request(callback, uri)
     }
@@ -48,26 +69,18 @@ impl MatrixVisualisationsBackend {
         &mut self,
         callback: Callback<Result<EventsResponse, Error>>,
         from: &[String],
-    ) -> FetchTask {
-        let (server_name, room_id) = {
+    ) -> Option<FetchTask> {
+        let (base_url, room_id) = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.room_id.clone())
+            (session.base_url(), cs_api::encode(&session.room_id))
         };
-        let events_list = from.join(",");
-
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(
-                format!(
-                    "/visualisations/ancestors/{}?from={}&limit=10",
-                    room_id, events_list
-                )
-                .as_str(),
-            )
-            .build()
-            .expect("Failed to build URI.");
+        let events_list = encode_events(from);
+
+        let uri = format!(
+            "{}/visualisations/ancestors/{}?from={}&limit=10",
+            base_url, room_id, events_list
+        );
 
         self.request(callback, uri)
     }
@@ -76,26 +89,18 @@ impl MatrixVisualisationsBackend {
         &mut self,
         callback: Callback<Result<EventsResponse, Error>>,
         from: &[String],
-    ) -> FetchTask {
-        let (server_name, room_id) = {
+    ) -> Option<FetchTask> {
+        let (base_url, room_id) = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.room_id.clone())
+            (session.base_url(), cs_api::encode(&session.room_id))
         };
-        let events_list = from.join(",");
-
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(
-                format!(
-                    "/visualisations/descendants/{}?from={}&limit=10",
-                    room_id, events_list
-                )
-                .as_str(),
-            )
-            .build()
-            .expect("Failed to build URI.");
+        let events_list = encode_events(from);
+
+        let uri = format!(
+            "{}/visualisations/descendants/{}?from={}&limit=10",
+            base_url, room_id, events_list
+        );
 
         self.request(callback, uri)
     }
@@ -104,78 +109,127 @@ impl MatrixVisualisationsBackend {
         &mut self,
         callback: Callback<Result<EventsResponse, Error>>,
         from: &str,
-    ) -> FetchTask {
-        let (server_name, room_id) = {
+    ) -> Option<FetchTask> {
+        let (base_url, room_id) = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.room_id.clone())
+            (session.base_url(), cs_api::encode(&session.room_id))
         };
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(format!("/visualisations/state/{}?from={}", room_id, from).as_str())
-            .build()
-            .expect("Failed to build URI.");
+        let uri = format!(
+            "{}/visualisations/state/{}?from={}",
+            base_url,
+            room_id,
+            cs_api::encode(from)
+        );
 
         self.request(callback, uri)
     }
 
-    pub fn stop(&mut self, callback: Callback<Result<(), Error>>) -> FetchTask {
-        let (server_name, room_id) = {
+    pub fn stop(&mut self, callback: Callback<Result<(), Error>>) -> Option<FetchTask> {
+        let (base_url, room_id) = {
             let session = self.session.read().unwrap();
 
-            (session.server_name.clone(), session.room_id.clone())
+            (session.base_url(), cs_api::encode(&session.room_id))
         };
 
-        let uri = Uri::builder()
-            .scheme("https")
-            .authority(server_name.as_str())
-            .path_and_query(format!("/visualisations/stop/{}", room_id).as_str())
-            .build()
-            .expect("Failed to build URI.");
-
-        let request = Request::get(uri)
-            .header("Content-Type", "application/json")
-            .body(Nothing)
-            .expect("Failed to buid request.");
-
-        let handler = move |response: Response<Nothing>| {
-            let (meta, _) = response.into_parts();
-
-            if meta.status.is_success() {
-                callback.emit(Ok(()))
-            } else {
-                callback.emit(Err(format_err!(
-                    "{}: error stopping the backend",
-                    meta.status
-                )))
+        let uri = format!("{}/visualisations/stop/{}", base_url, room_id);
+
+        let request = match build_request(&uri) {
+            Ok(request) => request,
+            Err(e) => {
+                callback.emit(Err(e.into()));
+                return None;
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        let handler = move |response: Response<Text>| {
+            let (meta, body) = response.into_parts();
+            let error = "error stopping the backend";
+
+            callback.emit(
+                body.map_err(|e| MatrixError::without_response(error, &e).into())
+                    .and_then(|body| cs_api::check_response(meta.status, &body, error)),
+            )
+        };
+
+        Some(self.fetch.fetch(request, handler.into()))
     }
 
     fn request(
         &mut self,
         callback: Callback<Result<EventsResponse, Error>>,
-        uri: Uri,
-    ) -> FetchTask {
-        let request = Request::get(uri)
-            .header("Content-Type", "application/json")
-            .body(Nothing)
-            .expect("Failed to buid request.");
-
-        let handler = move |response: Response<Json<Result<EventsResponse, Error>>>| {
-            let (meta, Json(data)) = response.into_parts();
-
-            if meta.status.is_success() {
-                callback.emit(data)
-            } else {
-                callback.emit(Err(format_err!("{}: error fetching events", meta.status)))
+        uri: String,
+    ) -> Option<FetchTask> {
+        let request = match build_request(&uri) {
+            Ok(request) => request,
+            Err(e) => {
+                callback.emit(Err(e.into()));
+                return None;
             }
         };
 
-        self.fetch.fetch(request, handler.into())
+        // The body is parsed like the ones of the Client-Server API so the errors give the
+        // standard error of the Matrix APIs
+        let handler = move |response: Response<Text>| {
+            let (meta, body) = response.into_parts();
+            let error = "error fetching events";
+
+            callback.emit(
+                body.map_err(|e| MatrixError::without_response(error, &e).into())
+                    .and_then(|body| cs_api::parse_response(meta.status, &body, error)),
+            )
+        };
+
+        Some(self.fetch.fetch(request, handler.into()))
     }
+
+    // Sends a request to the Client-Server API of the homeserver, which doesn't need to be
+    // authenticated, and then calls `callback` when it gets the response.
+    fn cs_request<T: DeserializeOwned + 'static>(
+        &mut self,
+        callback: Callback<Result<T, Error>>,
+        request: Result<ApiRequest, MatrixError>,
+        error: &'static str,
+    ) -> Option<FetchTask> {
+        let (parts, _) = match request {
+            Ok(request) => request.into_parts(),
+            Err(e) => {
+                callback.emit(Err(e.into()));
+                return None;
+            }
+        };
+
+        let handler = move |response: Response<Text>| {
+            let (meta, body) = response.into_parts();
+
+            callback.emit(
+                body.map_err(|e| MatrixError::without_response(error, &e).into())
+                    .and_then(|body| cs_api::parse_response(meta.status, &body, error)),
+            )
+        };
+
+        Some(
+            self.fetch
+                .fetch(Request::from_parts(parts, Nothing), handler.into()),
+        )
+    }
+}
+
+// Encodes the IDs of the events `from` into the comma-separated list taken by the requests for
+// the ancestors and the descendants of events.
+fn encode_events(from: &[String]) -> String {
+    from.iter()
+        .map(|id| cs_api::encode(id))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Builds a request to the Matrix Visualisations backend, which fails if the base URL of the
+// homeserver, which can be given by the user, doesn't make a valid URI.
+fn build_request(uri: &str) -> Result<Request<Nothing>, MatrixError> {
+    Request::get(uri)
+        .header("Content-Type", "application/json")
+        .body(Nothing)
+        .map_err(|e| MatrixError::invalid_request(uri, &e))
 }
@@ -1,7 +1,11 @@
+use crate::cs_backend::api;
+
 #[derive(Clone, Debug)]
 pub struct Session {
     pub server_name: String,
-    pub room_id: String,
+    pub base_url: String, // The base URL of the homeserver given by the user, if it can't be discovered
+    pub discovered_base_url: Option<String>, // The base URL of the homeserver found via `.well-known`
+    pub room_id: String, // The ID of the room, or its alias until it is resolved
     pub connected: bool,
 }
 
@@ -9,8 +13,25 @@ impl Session {
     pub fn empty() -> Self {
         Session {
             server_name: String::new(),
+            base_url: String::new(),
+            discovered_base_url: None,
             room_id: String::new(),
             connected: false,
         }
     }
+
+    /// Get the base URL of the homeserver, to which the requests are sent.
+    pub fn base_url(&self) -> String {
+        api::base_url(
+            &self.server_name,
+            &self.base_url,
+            self.discovered_base_url.as_deref(),
+        )
+    }
+
+    /// Whether the base URL of the homeserver must be discovered via `.well-known` before sending
+    /// any request to it.
+    pub fn needs_discovery(&self) -> bool {
+        self.base_url.trim().is_empty() && self.discovered_base_url.is_none()
+    }
 }
@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
 use serde_derive::Serialize;
@@ -6,7 +7,8 @@ use stdweb::web::IParentNode;
 use stdweb::Value;
 
 use crate::model::dag::RoomEvents;
-use crate::model::dag::{DataSet, OrphanInfo};
+use crate::model::dag::{DataSet, NodeColor, OrphanInfo, ReplayStep};
+use crate::model::diff::{DagDiff, MissingEvent};
 use crate::BackendChoice;
 
 /// This struct contains the DAG displayed by the application.
@@ -15,19 +17,46 @@ use crate::BackendChoice;
 /// within the same vis.js network. Each node stored there has a prefix of the form "subdag_X_"
 /// which means that this node belongs to the view X.
 ///
-/// Each of `earliest_events`, `latest_events` and `orphan_events` variables contains a list of
-/// lists of the earliest/latest/orphan events' IDs currently displayed for each views. So
-/// `*_events[X]` corresponds with the view X.
+/// Each of `earliest_events` and `orphan_events` variables contains a list of lists of the
+/// earliest/orphan events' IDs currently displayed for each views. So `*_events[X]` corresponds
+/// with the view X.
+///
+/// While the construction of the DAG of a view is replayed, this view is only updated with the
+/// steps of the replay and its whole DAG is displayed again once the replay is stopped.
+///
+/// When the branches are colored, `branch_colors[X]` keeps the style given to each node of the
+/// view X, so only the nodes whose branch changed are colored again when the DAG grows.
+///
+/// The differences shown by `show_diff` become outdated as soon as the DAG of one of the two
+/// compared views changes, which `outdated_diff` tells so they can be computed again.
 pub struct VisJsService {
     lib: Option<Value>,
     network: Option<Value>,
     bk_type: Arc<RwLock<BackendChoice>>,
     data: Option<Value>,
     earliest_events: Vec<Vec<String>>,
-    latest_events: Vec<Vec<String>>,
     orphan_events: Vec<Vec<OrphanInfo>>,
+    shown_edges: ShownEdges,
+    branches_colored: bool,
+    branch_colors: Vec<HashMap<String, BranchStyle>>,
+    replayed_view: Option<usize>, // The view whose construction is being replayed
+    diff_views: Option<(usize, usize)>, // The views whose differences are shown
+    diff_outdated: bool,          // Whether the DAG of one of these views changed since then
 }
 
+// The colors given to the branches of the DAG, as pairs of border and background colors. They
+// are reused when there are more branches than colors.
+const BRANCH_COLORS: [(&str, &str); 8] = [
+    ("#1f3d7a", "#4d79ff"),
+    ("#7a1f5c", "#e65cb8"),
+    ("#7a5c1f", "#e6b84d"),
+    ("#1f7a6b", "#4de6cc"),
+    ("#5c1f7a", "#b84de6"),
+    ("#7a2e1f", "#e6734d"),
+    ("#3d7a1f", "#8ce64d"),
+    ("#1f5c7a", "#4db8e6"),
+];
+
 // This enables the serialization of the ID of a view, so it can be used within the `js!`
 // macro.
 #[derive(Clone, Copy, Serialize)]
@@ -35,6 +64,34 @@ struct ViewId {
     id: usize,
 }
 
+// The new colors of a node of the vis.js network. The fork and merge points of the DAG have a
+// thicker border when the branches are colored.
+#[derive(Serialize)]
+struct NodeColorUpdate {
+    id: String,
+    color: NodeColor,
+    #[serde(rename = "borderWidth")]
+    border_width: u32,
+}
+
+// The new label of a node of the vis.js network.
+#[derive(Serialize)]
+struct NodeLabelUpdate {
+    id: String,
+    label: String,
+}
+
+// The style of a node when the branches are colored: the index of the colors of its branch in
+// `BRANCH_COLORS` and whether it is a fork or merge point.
+type BranchStyle = (usize, bool);
+
+// The kinds of edges which are currently displayed in the vis.js network.
+#[derive(Clone, Copy, Serialize)]
+struct ShownEdges {
+    prev: bool,
+    auth: bool,
+}
+
 impl VisJsService {
     pub fn new(bk_type: Arc<RwLock<BackendChoice>>) -> Self {
         let lib = js! {
@@ -47,8 +104,16 @@ impl VisJsService {
             bk_type,
             data: None,
             earliest_events: Vec::new(),
-            latest_events: Vec::new(),
             orphan_events: Vec::new(),
+            shown_edges: ShownEdges {
+                prev: true,
+                auth: false,
+            },
+            branches_colored: false,
+            branch_colors: Vec::new(),
+            replayed_view: None,
+            diff_views: None,
+            diff_outdated: false,
         }
     }
 
@@ -98,6 +163,9 @@ impl VisJsService {
         js_serializable!(DataSet);
         js_serializable!(OrphanInfo);
         js_serializable!(ViewId);
+        js_serializable!(ShownEdges);
+        js_serializable!(NodeColorUpdate);
+        js_serializable!(MissingEvent);
 
         self.data = Some(js! {
             var nodes = new vis.DataSet({});
@@ -105,9 +173,39 @@ impl VisJsService {
 
             var data = {
                 nodes: nodes,
-                edges: edges
+                edges: edges,
+                shown_edges: @{self.shown_edges}
             };
 
+            // Draw the auth edges in their own style and hide the edges of the kinds which
+            // aren't selected as soon as they are added to the network
+            edges.on("add", function(event, properties) {
+                var styled_edges = [];
+
+                for (let id of properties.items) {
+                    let edge = edges.get(id);
+
+                    if (edge.kind == "auth") {
+                        styled_edges.push({
+                            id: id,
+                            dashes: true,
+                            color: {
+                                color: "#9933cc",
+                                highlight: "#660099"
+                            },
+                            hidden: !data.shown_edges.auth
+                        });
+                    } else if (edge.kind == "prev") {
+                        styled_edges.push({
+                            id: id,
+                            hidden: !data.shown_edges.prev
+                        });
+                    }
+                }
+
+                edges.update(styled_edges);
+            });
+
             return data;
         });
 
@@ -200,6 +298,8 @@ impl VisJsService {
 
     /// Adds a new `events_dag` for the view `view_id`.
     pub fn add_dag(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        self.dag_changed(view_id);
+
         let backend = *self.bk_type.read().unwrap();
         let events_dag = events_dag.read().unwrap();
 
@@ -210,21 +310,21 @@ impl VisJsService {
         while self.earliest_events.len() <= view_id {
             self.earliest_events.push(Vec::new());
         }
-        while self.latest_events.len() <= view_id {
-            self.latest_events.push(Vec::new());
-        }
         while self.orphan_events.len() <= view_id {
             self.orphan_events.push(Vec::new());
         }
+        while self.branch_colors.len() <= view_id {
+            self.branch_colors.push(HashMap::new());
+        }
 
-        self.earliest_events[view_id] = events_dag.earliest_events.clone();
-        self.latest_events[view_id] = events_dag.latest_events.clone();
-        self.orphan_events[view_id] = events_dag.orphan_events.clone();
+        self.earliest_events[view_id] = events_dag.earliest_events();
+        self.orphan_events[view_id] = events_dag.orphan_events();
+        self.branch_colors[view_id].clear();
 
         let view_id = ViewId { id: view_id };
 
         match backend {
-            BackendChoice::CS => {
+            BackendChoice::CS | BackendChoice::File | BackendChoice::Federation => {
                 self.data = Some(js! {
                     var view_id = @{view_id};
                     var data = @{data};
@@ -285,15 +385,25 @@ impl VisJsService {
                 });
             }
         }
+
+        if self.branches_colored {
+            self.update_node_colors(&events_dag, view_id.id);
+        }
     }
 
-    /// Removes the DAG of the view `view_id`.
+    /// Removes the DAG of the view `view_id`. This also ends the replay of this view.
     pub fn remove_dag(&mut self, view_id: usize) {
+        if self.replayed_view == Some(view_id) {
+            self.replayed_view = None;
+        }
+
+        self.dag_changed(view_id);
+
         let data = self.data.as_ref().expect("No data set found");
 
         self.earliest_events[view_id] = Vec::new();
-        self.latest_events[view_id] = Vec::new();
         self.orphan_events[view_id] = Vec::new();
+        self.branch_colors[view_id].clear();
 
         let view_id = ViewId { id: view_id };
 
@@ -317,45 +427,55 @@ impl VisJsService {
         });
     }
 
-    /// Updates the DAG of the view `view_id` so that each additional events in `events_dag`
-    /// is added to the vis.js network.
-    pub fn update_dag(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+    /// Updates the DAG of the view `view_id` with `new_events`, the data set of the events which
+    /// have just been added to `events_dag`, and moves the buttons for loading more events.
+    pub fn update_dag(
+        &mut self,
+        events_dag: Arc<RwLock<RoomEvents>>,
+        view_id: usize,
+        mut new_events: DataSet,
+    ) {
+        self.dag_changed(view_id);
+
+        // The new events will be displayed at the end of the replay
+        if self.replayed_view == Some(view_id) {
+            return;
+        }
+
         let events_dag = events_dag.read().unwrap();
         let backend = *self.bk_type.read().unwrap();
 
-        if self.earliest_events[view_id] != events_dag.earliest_events {
-            let old_earliest_events = self.earliest_events[view_id].clone();
-            let new_earliest_events = events_dag.earliest_events.clone();
-            let old_orphan_events = self.orphan_events[view_id].clone();
-            let new_orphan_events = events_dag.orphan_events.clone();
+        // The nodes which have been added or changed are displayed with their original colors
+        for node in &new_events.nodes {
+            self.branch_colors[view_id].remove(&node.id);
+        }
 
-            let data = self.data.as_ref().expect("No data set found");
+        let data = self.data.as_ref().expect("No data set found");
+        new_events.add_prefix(&format!("subdag_{}_", view_id));
 
-            let mut earlier_events = DataSet::default();
-            events_dag
-                .add_earlier_events_to_data_set(&mut earlier_events, old_earliest_events.clone());
-            earlier_events.add_prefix(&format!("subdag_{}_", view_id));
-
-            let view_id = ViewId { id: view_id };
-
-            match backend {
-                BackendChoice::CS => {
-                    self.data = Some(js! {
-                        var view_id = @{view_id};
-                        var data = @{data};
-                        var ev = @{earlier_events};
-
-                        var min_depth = -1;
-                        for (let n of ev.nodes) {
-                            if (min_depth == -1 || n["level"] < min_depth) {
-                                min_depth = n["level"];
-                            }
-                        }
+        let old_earliest_events = self.earliest_events[view_id].clone();
+        let new_earliest_events = events_dag.earliest_events();
+        let old_orphan_events = self.orphan_events[view_id].clone();
+        let new_orphan_events = events_dag.orphan_events();
+
+        let earliest_events_changed = old_earliest_events != new_earliest_events;
+        let orphan_events_changed = old_orphan_events != new_orphan_events;
+        let more_ev_level = (events_dag.min_depth() - 1) as i32;
+
+        let view_id = ViewId { id: view_id };
+
+        match backend {
+            BackendChoice::CS | BackendChoice::File | BackendChoice::Federation => {
+                self.data = Some(js! {
+                    var view_id = @{view_id};
+                    var data = @{data};
+                    var ev = @{new_events};
 
-                        data.nodes.add(ev.nodes);
-                        data.edges.add(ev.edges);
+                    data.nodes.update(ev.nodes);
+                    data.edges.add(ev.edges);
 
-                        // Update the position of the button to load more events
+                    // Update the position of the button to load more events
+                    if (@{earliest_events_changed}) {
                         for (let ev of @{old_earliest_events}) {
                             data.edges.remove("subdag_" + view_id.id + "_" + ev + "_more_ev");
                         }
@@ -363,33 +483,36 @@ impl VisJsService {
                         data.nodes.add({
                             id: "subdag_" + view_id.id + "_more_ev",
                             label: "Load more events",
-                            level: min_depth - 1
+                            level: @{more_ev_level}
                         });
-                        for (let ev of @{new_earliest_events}) {
+                        for (let ev of @{&new_earliest_events}) {
                             data.edges.add({
-                                id: "subdag_" + view_id.id + "_" + ev + "_move_ev",
+                                id: "subdag_" + view_id.id + "_" + ev + "_more_ev",
                                 from: "subdag_" + view_id.id + "_" + ev,
                                 to: "subdag_" + view_id.id + "_more_ev"
                             });
                         }
+                    }
 
-                        return data;
-                    });
-                }
-                BackendChoice::MV => {
-                    self.data = Some(js! {
-                        var view_id = @{view_id};
-                        var data = @{data};
-                        var ev = @{earlier_events};
+                    return data;
+                });
+            }
+            BackendChoice::MV => {
+                self.data = Some(js! {
+                    var view_id = @{view_id};
+                    var data = @{data};
+                    var ev = @{new_events};
 
-                        data.nodes.add(ev.nodes);
-                        data.edges.add(ev.edges);
+                    data.nodes.update(ev.nodes);
+                    data.edges.add(ev.edges);
 
+                    // Update the buttons to load ancestors
+                    if (@{orphan_events_changed}) {
                         for (let ev of @{old_orphan_events}) {
                             data.edges.remove("subdag_" + view_id.id + "_" + ev.id + "_more_of");
                             data.nodes.remove("subdag_" + view_id.id + "_more_of_" + ev.id);
                         }
-                        for (let ev of @{new_orphan_events}) {
+                        for (let ev of @{&new_orphan_events}) {
                             data.nodes.add({
                                 id: "subdag_" + view_id.id + "_more_of_" + ev.id,
                                 label: "Load ancestors",
@@ -402,57 +525,308 @@ impl VisJsService {
                                 to: "subdag_" + view_id.id + "_more_of_" + ev.id
                             });
                         }
+                    }
+
+                    return data;
+                });
+            }
+        }
+
+        self.earliest_events[view_id.id] = new_earliest_events;
+        self.orphan_events[view_id.id] = new_orphan_events;
+
+        if self.branches_colored {
+            self.update_node_colors(&events_dag, view_id.id);
+        }
+    }
+
+    /// Updates the labels of the nodes corresponding to the events in `events_dag` in the view
+    /// `view_id`.
+    pub fn update_labels(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        if self.replayed_view == Some(view_id) {
+            return;
+        }
+
+        let data = self.data.as_ref().expect("No data set found");
+        let events_dag = events_dag.read().unwrap();
+
+        let prefix = format!("subdag_{}_", view_id);
+
+        // Only the labels are updated, so the nodes keep their colors
+        let updates: Vec<NodeLabelUpdate> = events_dag
+            .data_set_nodes()
+            .into_iter()
+            .map(|node| NodeLabelUpdate {
+                id: prefix.clone() + &node.id,
+                label: node.label,
+            })
+            .collect();
+
+        self.data = Some(js! {
+            var data = @{data};
+
+            data.nodes.update(@{updates});
+
+            return data;
+        });
+    }
+
+    /// Enables or disables the coloring of the nodes of every views according to the branch of
+    /// the DAG they belong to. The colors are applied to each view with `update_colors`.
+    pub fn color_branches(&mut self, enabled: bool) {
+        self.branches_colored = enabled;
+
+        for colors in &mut self.branch_colors {
+            colors.clear();
+        }
+    }
 
-                        return data;
+    /// Updates the colors of the nodes corresponding to the events in `events_dag` in the view
+    /// `view_id`, either with the colors of their branch or with their original colors.
+    pub fn update_colors(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        let events_dag = events_dag.read().unwrap();
+
+        self.update_node_colors(&events_dag, view_id);
+    }
+
+    fn update_node_colors(&mut self, events_dag: &RoomEvents, view_id: usize) {
+        if self.replayed_view == Some(view_id) {
+            return;
+        }
+
+        let data = self.data.as_ref().expect("No data set found");
+        let prefix = format!("subdag_{}_", view_id);
+
+        let updates: Vec<NodeColorUpdate> = if self.branches_colored {
+            let shape = events_dag.shape();
+            let junctions: HashSet<&String> = shape
+                .fork_points
+                .iter()
+                .chain(shape.merge_points.iter())
+                .collect();
+            let colors = &mut self.branch_colors[view_id];
+            let mut updates = Vec::new();
+
+            // Only the nodes whose branch changed since they were last colored are updated
+            for branch in &shape.branches {
+                let color_idx = branch.id % BRANCH_COLORS.len();
+                let (border, background) = BRANCH_COLORS[color_idx];
+
+                for id in &branch.events {
+                    let style = (color_idx, junctions.contains(id));
+
+                    if colors.get(id) == Some(&style) {
+                        continue;
+                    }
+
+                    colors.insert(id.clone(), style);
+                    updates.push(NodeColorUpdate {
+                        id: prefix.clone() + id,
+                        color: NodeColor {
+                            border: border.to_string(),
+                            background: background.to_string(),
+                        },
+                        border_width: if style.1 { 3 } else { 1 },
                     });
                 }
             }
 
-            self.earliest_events[view_id.id] = events_dag.earliest_events.clone();
-            self.orphan_events[view_id.id] = events_dag.orphan_events.clone();
+            updates
+        } else {
+            self.branch_colors[view_id].clear();
+
+            events_dag
+                .data_set_nodes()
+                .into_iter()
+                .map(|node| NodeColorUpdate {
+                    id: prefix.clone() + &node.id,
+                    color: node.color,
+                    border_width: node.border_width,
+                })
+                .collect()
+        };
+
+        if updates.is_empty() {
+            return;
         }
 
-        if self.latest_events[view_id] != events_dag.latest_events {
-            let data = self.data.as_ref().expect("No data set found");
+        self.data = Some(js! {
+            var data = @{data};
+
+            data.nodes.update(@{updates});
+
+            return data;
+        });
+    }
+
+    /// Shows the differences between the DAGs of the views `view_a` and `view_b`: the events
+    /// which are missing from a view are marked in it by placeholders with a dashed border, at
+    /// the depth they have in the other view. The previous differences are cleared first.
+    pub fn show_diff(&mut self, view_a: usize, view_b: usize, diff: &DagDiff) {
+        self.clear_diff();
+
+        self.diff_views = Some((view_a, view_b));
 
-            let mut new_events = DataSet::default();
-            events_dag.add_new_events_to_data_set(&mut new_events, self.latest_events[0].clone());
-            new_events.add_prefix(&format!("subdag_{}_", view_id));
+        let data = self.data.as_ref().expect("No data set found");
+        let view_a = ViewId { id: view_a };
+        let view_b = ViewId { id: view_b };
+
+        self.data = Some(js! {
+            var data = @{data};
+
+            function add_missing_events(view_id, events) {
+                var nodes = [];
+
+                for (let ev of events) {
+                    nodes.push({
+                        id: "subdag_" + view_id.id + "_missing_" + ev.id,
+                        label: "Missing: " + ev.id,
+                        level: ev.depth,
+                        color: {
+                            border: "#666666",
+                            background: "#ffffff"
+                        },
+                        borderWidth: 2,
+                        shapeProperties: {
+                            borderDashes: [5, 5]
+                        }
+                    });
+                }
+
+                data.nodes.update(nodes);
+            }
+
+            add_missing_events(@{view_a}, @{&diff.only_in_b});
+            add_missing_events(@{view_b}, @{&diff.only_in_a});
+
+            return data;
+        });
+    }
+
+    /// Removes the placeholders of the missing events added by `show_diff` from every views.
+    pub fn clear_diff(&mut self) {
+        self.diff_views = None;
+        self.diff_outdated = false;
+
+        let data = self.data.as_ref().expect("No data set found");
+
+        self.data = Some(js! {
+            var data = @{data};
+            var pattern = new RegExp("^subdag_[0-9]+_missing_");
 
-            self.data = Some(js! {
+            for (let node of data.nodes.get()) {
+                if (pattern.test(node.id)) {
+                    data.nodes.remove(node.id);
+                }
+            }
+
+            return data;
+        });
+    }
+
+    /// The views whose differences are shown, if the DAG of one of them changed since then.
+    pub fn outdated_diff(&self) -> Option<(usize, usize)> {
+        self.diff_views.filter(|_| self.diff_outdated)
+    }
+
+    // Marks the differences shown as outdated if the DAG of the view `view_id` is one of the
+    // compared DAGs.
+    fn dag_changed(&mut self, view_id: usize) {
+        if let Some((view_a, view_b)) = self.diff_views {
+            self.diff_outdated |= view_id == view_a || view_id == view_b;
+        }
+    }
+
+    /// Shows or hides the `prev_events` edges and the `auth_events` edges of every views.
+    pub fn show_edges(&mut self, prev: bool, auth: bool) {
+        self.shown_edges = ShownEdges { prev, auth };
+
+        if let Some(data) = &self.data {
+            js! {
                 var data = @{data};
-                var ev = @{new_events};
+                var shown_edges = @{self.shown_edges};
+                var updated_edges = [];
 
-                data.nodes.add(ev.nodes);
-                data.edges.add(ev.edges);
+                data.shown_edges = shown_edges;
 
-                return data;
-            });
+                for (let edge of data.edges.get()) {
+                    if (edge.kind == "prev" || edge.kind == "auth") {
+                        updated_edges.push({
+                            id: edge.id,
+                            hidden: !shown_edges[edge.kind]
+                        });
+                    }
+                }
 
-            self.latest_events[view_id] = events_dag.latest_events.clone();
+                data.edges.update(updated_edges);
+            };
         }
     }
 
-    /// Updates the labels of the nodes corresponding to the events in `events_dag` in the view
-    /// `view_id`.
-    pub fn update_labels(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
-        self.update_dag(events_dag.clone(), view_id);
+    /// Starts the replay of the construction of the DAG of the view `view_id` by removing its
+    /// DAG from the network. The steps of the replay are then displayed with `show_replay_steps`.
+    pub fn start_replay(&mut self, view_id: usize) {
+        self.remove_dag(view_id);
+        self.replayed_view = Some(view_id);
+    }
+
+    /// Displays the `steps` of the replay of the view `view_id` after the ones already displayed,
+    /// or in place of them if `reset` is true.
+    pub fn show_replay_steps(&mut self, view_id: usize, steps: &[ReplayStep], reset: bool) {
+        if self.replayed_view != Some(view_id) {
+            return;
+        }
 
         let data = self.data.as_ref().expect("No data set found");
-        let events_dag = events_dag.read().unwrap();
-        let mut new_data = events_dag.create_data_set();
-        new_data.add_prefix(&format!("subdag_{}_", view_id));
+
+        let mut events = DataSet::default();
+        for step in steps {
+            events.nodes.extend(step.data_set.nodes.iter().cloned());
+            events.edges.extend(step.data_set.edges.iter().cloned());
+        }
+        events.add_prefix(&format!("subdag_{}_", view_id));
+
+        let view_id = ViewId { id: view_id };
 
         self.data = Some(js! {
+            var view_id = @{view_id};
             var data = @{data};
-            var new_data = @{new_data};
+            var events = @{events};
+
+            if (@{reset}) {
+                var prefix = "subdag_" + view_id.id + "_";
 
-            data.nodes.update(new_data.nodes);
+                data.edges.remove(data.edges.getIds({
+                    filter: function(edge) { return edge.id.startsWith(prefix); }
+                }));
+                data.nodes.remove(data.nodes.getIds({
+                    filter: function(node) { return node.id.startsWith(prefix); }
+                }));
+            }
+
+            data.nodes.add(events.nodes);
+            data.edges.add(events.edges);
 
             return data;
         });
     }
 
+    /// Stops the replay and displays the whole `events_dag` of the view `view_id` again.
+    pub fn stop_replay(&mut self, events_dag: Arc<RwLock<RoomEvents>>, view_id: usize) {
+        if self.replayed_view != Some(view_id) {
+            return;
+        }
+
+        self.replayed_view = None;
+        self.remove_dag(view_id);
+        self.add_dag(events_dag, view_id);
+    }
+
+    /// Whether the construction of the DAG of the view `view_id` is being replayed.
+    pub fn is_replaying(&self, view_id: usize) -> bool {
+        self.replayed_view == Some(view_id)
+    }
+
     // TODO: maybe this will have to change
     pub fn is_active(&self) -> bool {
         self.network.is_some()